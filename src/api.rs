@@ -1,14 +1,18 @@
 use crate::config::AppConfig;
-use crate::utils::find_char_boundary;
+use crate::python_exec::Language;
+use crate::utils::{find_char_boundary, pypi_packages};
 use anyhow::{anyhow, Context, Result};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 // ── Provider abstraction ────────────────────────────────────────────────
 
 /// Supported LLM providers.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Provider {
     /// HuggingFace Inference API (cloud, requires HF_TOKEN).
     HuggingFace,
@@ -20,10 +24,16 @@ pub enum Provider {
 
 /// Default HuggingFace API URL — used to detect whether the user explicitly
 /// overrode `api_url` in the config.
-const HF_DEFAULT_URL: &str = "https://router.huggingface.co/v1/chat/completions";
-const OLLAMA_DEFAULT_URL: &str = "http://localhost:11434/v1/chat/completions";
+pub const HF_DEFAULT_URL: &str = "https://router.huggingface.co/v1/chat/completions";
+/// Default Ollama API URL (OpenAI-compatible endpoint on the local server).
+pub const OLLAMA_DEFAULT_URL: &str = "http://localhost:11434/v1/chat/completions";
 
 impl Provider {
+    /// Every supported provider, in the order they are listed to users.
+    pub fn all() -> &'static [Provider] {
+        &[Self::HuggingFace, Self::Ollama, Self::OpenAiCompatible]
+    }
+
     /// Parse the provider string from config into a `Provider` enum.
     pub fn from_config(s: &str) -> Result<Self> {
         match s.to_lowercase().as_str() {
@@ -107,6 +117,229 @@ impl Provider {
     }
 }
 
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.display_name())
+    }
+}
+
+impl std::str::FromStr for Provider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_config(s)
+    }
+}
+
+/// Failure of a request to the LLM provider, by cause, so callers can react
+/// to a bad token differently from a rate limit or a malformed reply.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// The provider or its credentials are not set up (unknown provider,
+    /// missing `api_url`, missing token, offline mode).
+    #[error("{0}")]
+    Config(String),
+    /// The provider rejected the credentials (401/403).
+    #[error("{provider} rejected the credentials ({status}): {message}")]
+    Auth { provider: Provider, status: u16, message: String },
+    /// Too many requests (429), with the delay the server asked for, if any.
+    #[error("{provider} rate limit reached: {message}")]
+    RateLimited { provider: Provider, retry_after: Option<Duration>, message: String },
+    /// The provider failed to handle the request (5xx).
+    #[error("{provider} server error {status}: {message}")]
+    Server { provider: Provider, status: u16, message: String },
+    /// The request was refused for another reason (other 4xx, e.g. an unknown model).
+    #[error("{provider} error {status}: {message}")]
+    Request { provider: Provider, status: u16, message: String },
+    /// The reply could not be understood.
+    #[error("{0}")]
+    Parse(String),
+    /// The server could not be reached or the connection broke.
+    #[error("{0}")]
+    Network(String),
+    /// The provider did not answer in time.
+    #[error("{provider} did not respond within {}s", .after.as_secs())]
+    Timeout { provider: Provider, after: Duration },
+    /// The request was cancelled through its [`CancellationToken`].
+    #[error("Generation cancelled")]
+    Cancelled,
+}
+
+impl ApiError {
+    /// Error for a non-success HTTP `status` with response `body`.
+    fn from_status(provider: Provider, status: reqwest::StatusCode, retry_after: Option<Duration>, body: String) -> Self {
+        let code = status.as_u16();
+        match code {
+            401 | 403 => Self::Auth { provider, status: code, message: body },
+            429 => Self::RateLimited { provider, retry_after, message: body },
+            500..=599 => Self::Server { provider, status: code, message: body },
+            _ => Self::Request { provider, status: code, message: body },
+        }
+    }
+
+    /// Error for a failed connection or read.
+    fn from_reqwest(provider: Provider, api_url: &str, e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout { provider, after: REQUEST_TIMEOUT }
+        } else {
+            Self::Network(format!("HTTP error to {} ({}): {}", provider.display_name(), api_url, e))
+        }
+    }
+
+    /// Whether the request may succeed if sent again (network errors, 429, 5xx).
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::RateLimited { .. } | Self::Server { .. } | Self::Network(_) | Self::Timeout { .. })
+    }
+
+    /// A short suggestion on how to fix the error, for display to users.
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Self::Auth { provider: Provider::HuggingFace, .. } => {
+                Some("Check HF_TOKEN in .env (https://huggingface.co/settings/tokens).".to_string())
+            }
+            Self::Auth { .. } => Some("Check LLM_API_KEY in .env.".to_string()),
+            Self::RateLimited { retry_after: Some(after), .. } => {
+                Some(format!("Wait {}s before retrying, or switch models with /model.", after.as_secs()))
+            }
+            Self::RateLimited { .. } => Some("Wait a moment before retrying, or switch models with /model.".to_string()),
+            Self::Server { .. } => Some("The provider is having trouble; try again later or switch models with /model.".to_string()),
+            Self::Request { status: 404, .. } => Some("Check api_url and the model name (/model lists them).".to_string()),
+            Self::Network(_) => Some("Is the server running? For Ollama, start it with `ollama serve`.".to_string()),
+            Self::Timeout { .. } => Some("Try a smaller max_tokens or a faster model.".to_string()),
+            Self::Config(_) | Self::Request { .. } | Self::Parse(_) | Self::Cancelled => None,
+        }
+    }
+}
+
+/// Seconds to wait from a `Retry-After` header (the HTTP-date form is ignored).
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Default API URL of every provider that has one (OpenAI-compatible
+/// providers must be configured explicitly).
+pub fn provider_defaults() -> Vec<(Provider, &'static str)> {
+    Provider::all()
+        .iter()
+        .map(|p| (*p, p.default_api_url()))
+        .filter(|(_, url)| !url.is_empty())
+        .collect()
+}
+
+/// Whether `url` points at this machine (`localhost`, `127.x.x.x`, `::1`).
+fn is_local_url(url: &str) -> bool {
+    let Some(host) = reqwest::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)) else {
+        return false;
+    };
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_matches(['[', ']'])
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Resolve the configured provider and API URL. In offline mode, anything
+/// that isn't on this machine is rejected before a request is made.
+fn resolve_endpoint(config: &AppConfig) -> Result<(Provider, String)> {
+    let provider = Provider::from_config(&config.provider)?;
+    let api_url = provider.resolve_api_url(&config.api_url)?;
+    if config.offline && !is_local_url(&api_url) {
+        return Err(anyhow!(
+            "Offline mode: {} at {} needs network access. Switch to a local model with /provider ollama \
+             or point api_url at localhost.",
+            provider.display_name(),
+            api_url
+        ));
+    }
+    Ok((provider, api_url))
+}
+
+/// Check that the configured provider can be used: credentials are present
+/// and its server answers a cheap GET on the API URL. Any HTTP status other
+/// than 401/403 counts as reachable. Returns a short status line.
+pub async fn probe_provider(config: &AppConfig) -> Result<String> {
+    let (provider, api_url) = resolve_endpoint(config)?;
+    let headers = provider.auth_headers()?;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    let resp = client
+        .get(&api_url)
+        .headers(headers)
+        .send()
+        .await
+        .with_context(|| format!("{} is unreachable at {}", provider.display_name(), api_url))?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        return Err(anyhow!("{} rejected the credentials ({})", provider.display_name(), status));
+    }
+    Ok(format!("{} reachable at {}", provider.display_name(), api_url))
+}
+
+/// Make `config` usable offline: a cloud HuggingFace setup is switched to the
+/// local Ollama server, and the default HuggingFace model to a local one.
+/// Returns a notice describing the switch, or an error when the configured
+/// endpoint is remote and can't be replaced automatically.
+pub fn apply_offline(config: &mut AppConfig) -> Result<Option<String>> {
+    if !config.offline || resolve_endpoint(config).is_ok() {
+        return Ok(None);
+    }
+    if Provider::from_config(&config.provider)? != Provider::HuggingFace {
+        return resolve_endpoint(config).map(|_| None);
+    }
+
+    let mut local = config.clone();
+    switch_provider(&mut local, "ollama", None)?;
+    if local.model == AppConfig::default().model {
+        local.model = curated_ollama_models().swap_remove(0);
+    }
+    resolve_endpoint(&local)?;
+    *config = local;
+    Ok(Some(format!("Offline mode: using Ollama (local) with model {}", config.model)))
+}
+
+/// Switch `config` to the provider named `name`, returning the resolved API URL.
+///
+/// The API URL becomes `api_url` when given, otherwise the new provider's
+/// default; a custom URL that isn't another provider's default is kept. Fails
+/// (leaving `config` untouched) if the name is unknown, no URL can be
+/// resolved, or the provider's required credentials are missing.
+pub fn switch_provider(config: &mut AppConfig, name: &str, api_url: Option<&str>) -> Result<String> {
+    let provider = Provider::from_config(name)?;
+
+    let is_builtin_default = config.api_url == HF_DEFAULT_URL || config.api_url == OLLAMA_DEFAULT_URL;
+    let new_url = match api_url {
+        Some(url) => url.to_string(),
+        None if is_builtin_default => match provider.default_api_url() {
+            "" => {
+                return Err(anyhow!(
+                    "Provider '{}' requires an API URL: /provider {} <url>",
+                    provider.display_name(),
+                    name
+                ))
+            }
+            default => default.to_string(),
+        },
+        None => config.api_url.clone(),
+    };
+
+    let resolved = provider.resolve_api_url(&new_url)?;
+    provider.auth_headers()?;
+
+    config.provider = name.to_lowercase();
+    config.api_url = new_url;
+    Ok(resolved)
+}
+
 // ── Request / Response types (OpenAI chat completions format) ───────────
 
 #[derive(Serialize)]
@@ -120,6 +353,9 @@ struct ChatRequest {
     /// Explicitly disable streaming (some Ollama versions default to stream).
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
+    /// Ollama only: how long the model stays loaded after this request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -128,6 +364,29 @@ pub struct Message {
     pub content: String,
 }
 
+/// Body for Ollama's native `/api/generate` (see `ollama_native_api`).
+#[derive(Serialize)]
+struct OllamaGenerateRequest {
+    model: String,
+    prompt: String,
+    system: String,
+    stream: bool,
+    options: OllamaOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<String>,
+}
+
+#[derive(Serialize)]
+struct OllamaOptions {
+    num_predict: u32,
+    temperature: f32,
+}
+
+#[derive(Deserialize)]
+struct OllamaGenerateResponse {
+    response: String,
+}
+
 #[derive(Deserialize)]
 struct ChatResponse {
     choices: Vec<Choice>,
@@ -190,102 +449,610 @@ When the request involves a game or graphical application:\n\
 \n\
 === WHEN FIXING / REFINING CODE ===\n\
 26. When asked to fix an error, output the COMPLETE corrected script — not just the changed lines.\n\
-27. Preserve all existing features unless explicitly told to remove them.";
+27. Preserve all existing features unless explicitly told to remove them.\n\
+\n\
+=== MULTI-FILE PROJECTS ===\n\
+28. Only when the user explicitly asks for multiple files: emit one fence per file, annotated with its name, e.g. ```python title=utils.py\n\
+29. The entry point must be main.py; list third-party packages in a ```text title=requirements.txt``` fence.";
 
-/// Generate code with conversation history for multi-turn refinement.
-///
-/// Routes to the configured provider (HuggingFace, Ollama, or any
-/// OpenAI-compatible endpoint). All providers use the same chat
-/// completions request/response format.
-pub async fn generate_code_with_history(
-    messages: &[Message],
-    config: &AppConfig,
-) -> Result<String> {
-    let provider = Provider::from_config(&config.provider)?;
-    let api_url = provider.resolve_api_url(&config.api_url)?;
-    let headers = provider.auth_headers()?;
+/// System prompt for `language`. Other languages reuse the Python prompt's
+/// output rules with an override that switches the target language.
+pub fn system_prompt(language: Language) -> String {
+    if language == Language::Python {
+        return SYSTEM_PROMPT.to_string();
+    }
+    format!(
+        "{SYSTEM_PROMPT}\n\n\
+         === LANGUAGE OVERRIDE ===\n\
+         Ignore the Python-specific rules above. Write a single, complete {name} script instead, \
+         runnable with `{interpreter} script.{ext}`. Use only tools and modules that ship with {interpreter}. \
+         Respond with one ```{tag} ... ``` fence and put explanations in {name} comments.",
+        name = language.name(),
+        interpreter = language.interpreter().unwrap_or("python3"),
+        ext = language.extension(),
+        tag = language.fence_tag(),
+    )
+}
 
+/// System prompt for `/explain`: a prose walkthrough of existing code.
+const EXPLAIN_PROMPT: &str = "\
+You are a patient programming tutor. You receive a script and explain it to a beginner.\n\
+1. Walk through the code section by section, in order: imports, setup, each function or class, then the main flow.\n\
+2. For each section, say what it does and why, in plain language. Name the lines or functions you are talking about.\n\
+3. Point out any library a beginner may not know and what it is used for here.\n\
+4. Do not rewrite, fix or extend the code, and do not repeat it in full. Quote at most a line or two at a time.\n\
+5. End with one short paragraph on how to run the script and what output to expect.";
+
+/// Build the chat request for `messages`, prefixed with the `system` prompt.
+fn chat_request(messages: &[Message], config: &AppConfig, system: &str, stream: bool) -> ChatRequest {
     // Ensure system message is at the beginning
     let mut full_messages = vec![Message {
         role: "system".to_string(),
-        content: SYSTEM_PROMPT.to_string(),
+        content: system.to_string(),
     }];
 
     // Add conversation history
     full_messages.extend_from_slice(messages);
 
-    let body = ChatRequest {
+    ChatRequest {
         model: config.model.clone(),
         messages: full_messages,
         max_tokens: Some(config.max_tokens),
         temperature: Some(config.temperature),
-        stream: Some(false), // always disable streaming
-    };
+        // Always set explicitly: some Ollama versions default to stream
+        stream: Some(stream),
+        keep_alive: ollama_keep_alive(config),
+    }
+}
+
+/// The `keep_alive` to send with requests: `ollama_keep_alive` for the
+/// Ollama provider, nothing for other providers or when it's empty.
+fn ollama_keep_alive(config: &AppConfig) -> Option<String> {
+    let is_ollama = Provider::from_config(&config.provider).is_ok_and(|p| p == Provider::Ollama);
+    let keep_alive = config.ollama_keep_alive.trim();
+    (is_ollama && !keep_alive.is_empty()).then(|| keep_alive.to_string())
+}
+
+/// Load the configured Ollama model into memory ahead of the first request,
+/// so it doesn't pay the 10–20s load time. Does nothing for other providers
+/// or with `ollama_preload = false`.
+pub async fn preload_model(config: &AppConfig) -> Result<()> {
+    if !config.ollama_preload {
+        return Ok(());
+    }
+    let (provider, api_url) = resolve_endpoint(config)?;
+    if provider != Provider::Ollama {
+        return Ok(());
+    }
+    // A generate request without a prompt only loads the model
+    let mut body = serde_json::json!({ "model": config.model });
+    if let Some(keep_alive) = ollama_keep_alive(config) {
+        body["keep_alive"] = serde_json::Value::String(keep_alive);
+    }
+    let url = ollama_generate_url(&api_url);
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?
+        .post(&url)
+        .json(&body)
+        .send()
+        .await
+        .with_context(|| format!("Could not reach Ollama at {}", url))?
+        .error_for_status()
+        .with_context(|| format!("Ollama could not load {}", config.model))?;
+    Ok(())
+}
+
+/// Flatten the conversation into one prompt for Ollama's `/api/generate`,
+/// one `Role: content` block per message, ending with the assistant's turn.
+fn ollama_prompt(messages: &[Message]) -> String {
+    let mut prompt = String::new();
+    for message in messages {
+        let role = match message.role.as_str() {
+            "assistant" => "Assistant",
+            "system" => "System",
+            _ => "User",
+        };
+        prompt.push_str(&format!("{}: {}\n\n", role, message.content));
+    }
+    prompt.push_str("Assistant:");
+    prompt
+}
+
+/// Ollama's native endpoint on the same server as the configured
+/// OpenAI-compatible `api_url`.
+fn ollama_generate_url(api_url: &str) -> String {
+    let base = api_url.trim_end_matches('/');
+    let base = base
+        .strip_suffix("/api/generate")
+        .or_else(|| base.strip_suffix("/v1/chat/completions"))
+        .unwrap_or(base);
+    format!("{}/api/generate", base)
+}
 
+/// Timeout for a whole generation request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Longest `Retry-After` worth waiting for; a 429 asking for more fails
+/// right away with `RateLimited` instead of hanging the prompt.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// POST `body` to the configured provider, retrying network errors, 429 and
+/// 5xx responses with exponential backoff. Returns the first successful response.
+/// A 429 whose `Retry-After` exceeds [`MAX_RETRY_AFTER`] is not retried.
+async fn send_with_retries(
+    provider: Provider,
+    api_url: &str,
+    body: &impl Serialize,
+    max_retries: u32,
+) -> std::result::Result<reqwest::Response, ApiError> {
+    let headers = provider.auth_headers().map_err(|e| ApiError::Config(format!("{:#}", e)))?;
     let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(120))
+        .timeout(REQUEST_TIMEOUT)
         .build()
-        .context("Failed to create HTTP client")?;
+        .map_err(|e| ApiError::Config(format!("Failed to create HTTP client: {}", e)))?;
 
     // Retry loop with exponential backoff
-    let mut last_err: Option<anyhow::Error> = None;
-    for attempt in 0..=config.max_retries {
+    let mut last_err: Option<ApiError> = None;
+    for attempt in 0..=max_retries {
         if attempt > 0 {
             let base_delay = Duration::from_secs(1u64 << (attempt - 1)); // 1s, 2s, 4s, ...
             let jitter = Duration::from_millis(rand::random::<u64>() % 500);
-            tokio::time::sleep(base_delay + jitter).await;
+            // Wait at least as long as a 429 asked for
+            let requested = match last_err {
+                Some(ApiError::RateLimited { retry_after: Some(after), .. }) => after,
+                _ => Duration::ZERO,
+            };
+            tokio::time::sleep((base_delay + jitter).max(requested)).await;
         }
 
         let result = client
-            .post(&api_url)
+            .post(api_url)
             .headers(headers.clone())
-            .json(&body)
+            .json(body)
             .send()
             .await;
 
         let resp = match result {
             Ok(r) => r,
             Err(e) => {
-                last_err = Some(anyhow!("HTTP error to {} ({}): {}", provider.display_name(), api_url, e));
+                last_err = Some(ApiError::from_reqwest(provider, api_url, e));
                 continue; // network error → retry
             }
         };
 
         let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+
+        let retry_after = retry_after(resp.headers());
         let text_body = resp
             .text()
             .await
-            .context("Failed to read API response")?;
+            .map_err(|e| ApiError::from_reqwest(provider, api_url, e))?;
 
-        if status.is_success() {
-            let parsed: ChatResponse = serde_json::from_str(&text_body)
-                .with_context(|| format!(
-                    "Failed to parse {} JSON response. Raw body:\n{}",
-                    provider.display_name(),
-                    &text_body[..find_char_boundary(&text_body, 500)]
-                ))?;
+        // Rate-limited or server errors are retried; client errors
+        // (400, 401, 403, etc.) fail fast
+        let err = ApiError::from_status(provider, status, retry_after, text_body);
+        if !err.is_retryable() || retry_after.is_some_and(|after| after > MAX_RETRY_AFTER) {
+            return Err(err);
+        }
+        last_err = Some(err);
+    }
+
+    Err(last_err.unwrap_or_else(|| ApiError::Network("All retry attempts exhausted".to_string())))
+}
+
+/// Extract the reply from a complete (non-streamed) chat completions body.
+fn parse_completion(provider: Provider, text_body: &str) -> std::result::Result<String, ApiError> {
+    let parsed: ChatResponse = serde_json::from_str(text_body).map_err(|e| ApiError::Parse(format!(
+        "Failed to parse {} JSON response: {}. Raw body:\n{}",
+        provider.display_name(),
+        e,
+        &text_body[..find_char_boundary(text_body, 500)]
+    )))?;
+
+    parsed
+        .choices
+        .first()
+        .map(|choice| choice.message.content.clone())
+        .ok_or_else(|| ApiError::Parse(format!("No choices in {} response", provider.display_name())))
+}
+
+/// Generate code with conversation history for multi-turn refinement.
+///
+/// Routes to the configured provider (HuggingFace, Ollama, or any
+/// OpenAI-compatible endpoint). All providers use the same chat
+/// completions request/response format. Triggering `cancel` aborts the
+/// request (including retry waits) with [`ApiError::Cancelled`].
+pub async fn generate_code_with_history(
+    messages: &[Message],
+    config: &AppConfig,
+    cancel: &CancellationToken,
+) -> std::result::Result<String, ApiError> {
+    chat_plain(messages, config, &system_prompt(config.language), cancel).await
+}
+
+/// Send `messages` with an arbitrary `system_prompt` and return the raw
+/// assistant text. For free-form replies (explanations, diagnoses, naming
+/// suggestions) that must not go through the code-only prompt or code
+/// extraction. Routes, retries and cancels like [`generate_code_with_history`].
+pub async fn chat_plain(
+    messages: &[Message],
+    config: &AppConfig,
+    system_prompt: &str,
+    cancel: &CancellationToken,
+) -> std::result::Result<String, ApiError> {
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Err(ApiError::Cancelled),
+        result = request_completion(messages, config, system_prompt) => result,
+    }
+}
+
+/// Ask the model to explain `code` section by section, using a tutoring
+/// system prompt. The reply is prose to print as is.
+pub async fn explain_code(
+    code: &str,
+    config: &AppConfig,
+    cancel: &CancellationToken,
+) -> std::result::Result<String, ApiError> {
+    let messages = [Message {
+        role: "user".to_string(),
+        content: format!(
+            "Explain this {} code section by section. Do not rewrite it.\n\n```{}\n{}\n```",
+            config.language.name(),
+            config.language.fence_tag(),
+            code
+        ),
+    }];
+    chat_plain(&messages, config, EXPLAIN_PROMPT, cancel).await
+}
+
+async fn request_completion(
+    messages: &[Message],
+    config: &AppConfig,
+    system: &str,
+) -> std::result::Result<String, ApiError> {
+    let (provider, api_url) = resolve_endpoint(config).map_err(|e| ApiError::Config(format!("{:#}", e)))?;
+    if provider == Provider::Ollama && config.ollama_native_api {
+        return request_ollama_generate(messages, config, system, &api_url).await;
+    }
+    let body = chat_request(messages, config, system, false);
+
+    let resp = send_with_retries(provider, &api_url, &body, config.max_retries).await?;
+    let text_body = resp
+        .text()
+        .await
+        .map_err(|e| ApiError::from_reqwest(provider, &api_url, e))?;
+    parse_completion(provider, &text_body)
+}
+
+/// Send the conversation to Ollama's native `/api/generate` as one flat
+/// prompt, for models that misbehave on the chat endpoint.
+async fn request_ollama_generate(
+    messages: &[Message],
+    config: &AppConfig,
+    system: &str,
+    api_url: &str,
+) -> std::result::Result<String, ApiError> {
+    let provider = Provider::Ollama;
+    let url = ollama_generate_url(api_url);
+    let body = OllamaGenerateRequest {
+        model: config.model.clone(),
+        prompt: ollama_prompt(messages),
+        system: system.to_string(),
+        stream: false,
+        options: OllamaOptions { num_predict: config.max_tokens, temperature: config.temperature },
+        keep_alive: ollama_keep_alive(config),
+    };
+
+    let resp = send_with_retries(provider, &url, &body, config.max_retries).await?;
+    let text_body = resp.text().await.map_err(|e| ApiError::from_reqwest(provider, &url, e))?;
+    let parsed: OllamaGenerateResponse = serde_json::from_str(&text_body).map_err(|e| ApiError::Parse(format!(
+        "Failed to parse Ollama /api/generate response: {}. Raw body:\n{}",
+        e,
+        &text_body[..find_char_boundary(&text_body, 500)]
+    )))?;
+    Ok(parsed.response)
+}
+
+/// Like [`generate_code_with_history`], but requests a streamed response and
+/// calls `on_token` with each chunk of text as it arrives. Returns the full reply.
+///
+/// Retries only happen before the stream starts. Servers that ignore
+/// `stream: true` and send a plain JSON body are handled too, as a single chunk,
+/// and so is Ollama's native API (`ollama_native_api`), which isn't streamed.
+pub async fn generate_code_streaming(
+    messages: &[Message],
+    config: &AppConfig,
+    on_token: impl FnMut(&str),
+    cancel: &CancellationToken,
+) -> std::result::Result<String, ApiError> {
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Err(ApiError::Cancelled),
+        result = request_streaming(messages, config, on_token) => result,
+    }
+}
+
+async fn request_streaming(
+    messages: &[Message],
+    config: &AppConfig,
+    mut on_token: impl FnMut(&str),
+) -> std::result::Result<String, ApiError> {
+    let (provider, api_url) = resolve_endpoint(config).map_err(|e| ApiError::Config(format!("{:#}", e)))?;
+    if provider == Provider::Ollama && config.ollama_native_api {
+        let system = system_prompt(config.language);
+        let generated = request_ollama_generate(messages, config, &system, &api_url).await?;
+        on_token(&generated);
+        return Ok(generated);
+    }
+    let body = chat_request(messages, config, &system_prompt(config.language), true);
+
+    let mut resp = send_with_retries(provider, &api_url, &body, config.max_retries).await?;
 
-            let generated = parsed
-                .choices
-                .first()
-                .map(|choice| choice.message.content.clone())
-                .ok_or_else(|| anyhow!("No choices in {} response", provider.display_name()))?;
+    let is_event_stream = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"));
+    if !is_event_stream {
+        let text_body = resp.text().await.map_err(|e| ApiError::from_reqwest(provider, &api_url, e))?;
+        let generated = parse_completion(provider, &text_body)?;
+        on_token(&generated);
+        return Ok(generated);
+    }
 
-            return Ok(generated);
+    // Server-sent events: one `data: {...}` line per chunk. Buffer bytes so a
+    // line (or a UTF-8 character) split across network chunks stays intact.
+    let mut generated = String::new();
+    let mut pending: Vec<u8> = Vec::new();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| match ApiError::from_reqwest(provider, &api_url, e) {
+            ApiError::Network(msg) => ApiError::Network(format!("{} stream interrupted: {}", provider.display_name(), msg)),
+            other => other,
+        })?
+    {
+        pending.extend_from_slice(&chunk);
+        while let Some(newline) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=newline).collect();
+            if let Some(token) = stream_line_delta(&String::from_utf8_lossy(&line))? {
+                on_token(&token);
+                generated.push_str(&token);
+            }
         }
+    }
+    if let Some(token) = stream_line_delta(&String::from_utf8_lossy(&pending))? {
+        on_token(&token);
+        generated.push_str(&token);
+    }
 
-        // Decide whether to retry based on status code
-        let code = status.as_u16();
-        if code == 429 || (500..600).contains(&code) {
-            last_err = Some(anyhow!("{} error {}: {}", provider.display_name(), status, text_body));
-            continue; // rate-limited or server error → retry
+    Ok(generated)
+}
+
+#[derive(Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+}
+
+#[derive(Deserialize)]
+struct StreamChoice {
+    #[serde(default)]
+    delta: StreamDelta,
+}
+
+#[derive(Deserialize, Default)]
+struct StreamDelta {
+    content: Option<String>,
+}
+
+/// Text carried by one server-sent-events line, or `None` for blank lines,
+/// comments, role-only deltas and the final `[DONE]` marker.
+fn stream_line_delta(line: &str) -> std::result::Result<Option<String>, ApiError> {
+    let Some(data) = line.trim().strip_prefix("data:") else {
+        return Ok(None);
+    };
+    let data = data.trim();
+    if data.is_empty() || data == "[DONE]" {
+        return Ok(None);
+    }
+    let chunk: StreamChunk = serde_json::from_str(data).map_err(|e| {
+        ApiError::Parse(format!("Failed to parse stream chunk ({}): {}", e, &data[..find_char_boundary(data, 200)]))
+    })?;
+    Ok(chunk
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.delta.content)
+        .filter(|content| !content.is_empty()))
+}
+
+// ── Model discovery ─────────────────────────────────────────────────────
+
+/// Fetch the locally installed models from Ollama's /api/tags endpoint.
+/// Falls back to a curated list if Ollama is not running.
+pub async fn fetch_ollama_models() -> Vec<String> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(3))
+        .build()
+        .unwrap_or_default();
+
+    match client
+        .get("http://localhost:11434/api/tags")
+        .send()
+        .await
+    {
+        Ok(resp) if resp.status().is_success() => {
+            if let Ok(body) = resp.json::<serde_json::Value>().await {
+                if let Some(models) = body["models"].as_array() {
+                    let mut names: Vec<String> = models
+                        .iter()
+                        .filter_map(|m| m["name"].as_str().map(|s| s.to_string()))
+                        .collect();
+                    if !names.is_empty() {
+                        names.sort();
+                        return names;
+                    }
+                }
+            }
+            curated_ollama_models()
+        }
+        _ => curated_ollama_models(),
+    }
+}
+
+fn curated_ollama_models() -> Vec<String> {
+    vec![
+        "qwen2.5-coder:32b".to_string(),
+        "qwen2.5-coder:14b".to_string(),
+        "qwen2.5-coder:7b".to_string(),
+        "codellama:13b".to_string(),
+        "codellama:7b".to_string(),
+        "deepseek-coder-v2:16b".to_string(),
+        "deepseek-coder:6.7b".to_string(),
+        "llama3.3:70b".to_string(),
+        "mistral:7b".to_string(),
+    ]
+}
+
+/// Fetch the live model list from HuggingFace's /v1/models endpoint.
+/// Falls back to a small curated list if the request fails.
+pub async fn fetch_hf_models() -> Vec<String> {
+    let token = std::env::var("HF_TOKEN").unwrap_or_default();
+    if token.is_empty() {
+        return curated_hf_models();
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .unwrap_or_default();
+
+    match client
+        .get("https://router.huggingface.co/v1/models")
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await
+    {
+        Ok(resp) if resp.status().is_success() => {
+            if let Ok(body) = resp.json::<serde_json::Value>().await {
+                if let Some(models) = body["data"].as_array() {
+                    let mut names: Vec<String> = models
+                        .iter()
+                        .filter_map(|m| m["id"].as_str().map(|s| s.to_string()))
+                        .collect();
+                    if !names.is_empty() {
+                        // Sort: put coding-oriented models first, then alphabetical
+                        names.sort_by(|a, b| {
+                            let a_code = a.to_lowercase().contains("coder")
+                                || a.to_lowercase().contains("code");
+                            let b_code = b.to_lowercase().contains("coder")
+                                || b.to_lowercase().contains("code");
+                            match (a_code, b_code) {
+                                (true, false) => std::cmp::Ordering::Less,
+                                (false, true) => std::cmp::Ordering::Greater,
+                                _ => a.cmp(b),
+                            }
+                        });
+                        return names;
+                    }
+                }
+            }
+            curated_hf_models()
         }
+        _ => curated_hf_models(),
+    }
+}
+
+/// Fallback HF model list when the API is unreachable or token is missing.
+fn curated_hf_models() -> Vec<String> {
+    vec![
+        "Qwen/Qwen2.5-Coder-32B-Instruct".to_string(),
+        "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+        "meta-llama/Llama-3.3-70B-Instruct".to_string(),
+        "meta-llama/Llama-3.1-8B-Instruct".to_string(),
+        "deepseek-ai/DeepSeek-R1".to_string(),
+        "Qwen/Qwen3-32B".to_string(),
+    ]
+}
+
+/// Well-known model names for OpenAI-compatible endpoints (no discovery API).
+pub fn curated_openai_models() -> Vec<String> {
+    vec![
+        "gpt-4o".to_string(),
+        "gpt-4o-mini".to_string(),
+        "gpt-4-turbo".to_string(),
+        "gpt-3.5-turbo".to_string(),
+        "o3-mini".to_string(),
+        "claude-3-5-sonnet-20241022".to_string(),
+        "deepseek-chat".to_string(),
+        "deepseek-coder".to_string(),
+    ]
+}
 
-        // Client errors (400, 401, 403, etc.) — fail fast
-        return Err(anyhow!("{} error {}: {}", provider.display_name(), status, text_body));
+/// List models for `provider`: live from HuggingFace / Ollama, curated for
+/// OpenAI-compatible endpoints. `offline` skips the HuggingFace fetch.
+pub async fn list_models(provider: Provider, offline: bool) -> Vec<String> {
+    match provider {
+        Provider::HuggingFace if offline => curated_hf_models(),
+        Provider::HuggingFace => fetch_hf_models().await,
+        Provider::Ollama => fetch_ollama_models().await,
+        Provider::OpenAiCompatible => curated_openai_models(),
     }
+}
+
+// ── PyPI lookup ─────────────────────────────────────────────────────────
 
-    Err(last_err.unwrap_or_else(|| anyhow!("All retry attempts exhausted")))
+const PYPI_URL: &str = "https://pypi.org/pypi";
+
+/// PyPI lookups made this session: package name → exists.
+static PYPI_CACHE: LazyLock<Mutex<HashMap<String, bool>>> = LazyLock::new(Default::default);
+
+/// Detected dependencies (import names) whose PyPI package doesn't exist,
+/// usually a typo or a local module. Returns the PyPI names looked up.
+///
+/// Lookups that fail (offline, timeout, 5xx) count as found so they never
+/// get in the way of an install; definite answers are cached.
+pub async fn find_missing_pypi_packages(imports: &[String]) -> Vec<String> {
+    missing_pypi_packages(PYPI_URL, imports).await
+}
+
+async fn missing_pypi_packages(base_url: &str, imports: &[String]) -> Vec<String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(3))
+        .build()
+        .unwrap_or_default();
+
+    let mut missing = Vec::new();
+    for package in pypi_packages(imports) {
+        let cache_key = format!("{}/{}", base_url, package.to_lowercase());
+        let cached = PYPI_CACHE.lock().ok().and_then(|c| c.get(&cache_key).copied());
+        let exists = match cached {
+            Some(exists) => exists,
+            None => {
+                let url = format!("{}/{}/json", base_url, package);
+                let exists = match client.head(&url).send().await {
+                    Ok(resp) if resp.status() == reqwest::StatusCode::NOT_FOUND => false,
+                    Ok(resp) if resp.status().is_success() => true,
+                    // Unknown: don't cache, don't warn
+                    _ => continue,
+                };
+                if let Ok(mut cache) = PYPI_CACHE.lock() {
+                    cache.insert(cache_key, exists);
+                }
+                exists
+            }
+        };
+        if !exists {
+            missing.push(package);
+        }
+    }
+    missing
 }
 
 #[cfg(test)]
@@ -330,6 +1097,7 @@ mod tests {
             max_tokens: Some(100),
             temperature: Some(0.5),
             stream: Some(false),
+            keep_alive: None,
         };
 
         let json = serde_json::to_string(&request);
@@ -396,6 +1164,7 @@ mod tests {
             max_tokens: None,
             temperature: None,
             stream: None,
+            keep_alive: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -403,6 +1172,7 @@ mod tests {
         assert!(!json.contains("max_tokens"));
         assert!(!json.contains("temperature"));
         assert!(!json.contains("stream"));
+        assert!(!json.contains("keep_alive"));
     }
 
     #[test]
@@ -411,6 +1181,280 @@ mod tests {
         assert!(SYSTEM_PROMPT.contains("Python"));
     }
 
+    #[test]
+    fn test_system_prompt_language_override() {
+        assert_eq!(system_prompt(Language::Python), SYSTEM_PROMPT);
+        let js = system_prompt(Language::JavaScript);
+        assert!(js.starts_with(SYSTEM_PROMPT));
+        assert!(js.contains("complete JavaScript script"));
+        assert!(js.contains("```javascript"));
+        assert!(js.contains("node script.js"));
+    }
+
+    #[test]
+    fn test_stream_line_delta() {
+        let line = r#"data: {"choices":[{"delta":{"content":"print("}}]}"#;
+        assert_eq!(stream_line_delta(line).unwrap().as_deref(), Some("print("));
+        assert_eq!(stream_line_delta(r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#).unwrap(), None);
+        assert_eq!(stream_line_delta("data: [DONE]").unwrap(), None);
+        assert_eq!(stream_line_delta(": keep-alive").unwrap(), None);
+        assert_eq!(stream_line_delta("").unwrap(), None);
+        assert!(stream_line_delta("data: {not json").is_err());
+    }
+
+    fn streaming_config(api_url: String) -> AppConfig {
+        AppConfig {
+            provider: "openai-compatible".to_string(),
+            api_url,
+            max_retries: 0,
+            ..AppConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_returns_structured_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let config = streaming_config(format!("{}/v1/chat/completions", server.url()));
+
+        let denied = server.mock("POST", "/v1/chat/completions").with_status(401).with_body("bad key").create_async().await;
+        let err = generate_code_with_history(&[], &config, &CancellationToken::new()).await.unwrap_err();
+        assert!(matches!(err, ApiError::Auth { status: 401, .. }), "{:?}", err);
+        assert!(err.hint().unwrap().contains("LLM_API_KEY"));
+        denied.remove_async().await;
+
+        let limited = server
+            .mock("POST", "/v1/chat/completions")
+            .with_status(429)
+            .with_header("retry-after", "7")
+            .create_async()
+            .await;
+        let err = generate_code_with_history(&[], &config, &CancellationToken::new()).await.unwrap_err();
+        assert!(matches!(err, ApiError::RateLimited { retry_after: Some(d), .. } if d == Duration::from_secs(7)));
+        limited.remove_async().await;
+
+        // A Retry-After beyond MAX_RETRY_AFTER fails at once, even with retries left
+        let patient = AppConfig { max_retries: 3, ..config.clone() };
+        let throttled = server
+            .mock("POST", "/v1/chat/completions")
+            .with_status(429)
+            .with_header("retry-after", "3600")
+            .expect(1)
+            .create_async()
+            .await;
+        let err = generate_code_with_history(&[], &patient, &CancellationToken::new()).await.unwrap_err();
+        assert!(matches!(err, ApiError::RateLimited { retry_after: Some(d), .. } if d == Duration::from_secs(3600)));
+        throttled.assert_async().await;
+        throttled.remove_async().await;
+
+        let _garbled = server.mock("POST", "/v1/chat/completions").with_body("not json").create_async().await;
+        let err = generate_code_with_history(&[], &config, &CancellationToken::new()).await.unwrap_err();
+        assert!(matches!(err, ApiError::Parse(_)));
+
+        let cancelled = CancellationToken::new();
+        cancelled.cancel();
+        assert!(matches!(generate_code_with_history(&[], &config, &cancelled).await.unwrap_err(), ApiError::Cancelled));
+
+        let offline = AppConfig { offline: true, ..AppConfig::default() };
+        assert!(matches!(generate_code_with_history(&[], &offline, &CancellationToken::new()).await.unwrap_err(), ApiError::Config(_)));
+    }
+
+    #[test]
+    fn test_ollama_prompt_and_url() {
+        let messages = vec![
+            Message { role: "user".to_string(), content: "say hi".to_string() },
+            Message { role: "assistant".to_string(), content: "print('hi')".to_string() },
+            Message { role: "user".to_string(), content: "louder".to_string() },
+        ];
+        assert_eq!(ollama_prompt(&messages), "User: say hi\n\nAssistant: print('hi')\n\nUser: louder\n\nAssistant:");
+        assert_eq!(ollama_generate_url(OLLAMA_DEFAULT_URL), "http://localhost:11434/api/generate");
+        assert_eq!(ollama_generate_url("http://gpu-box:11434/"), "http://gpu-box:11434/api/generate");
+        assert_eq!(ollama_generate_url("http://gpu-box:11434/api/generate"), "http://gpu-box:11434/api/generate");
+    }
+
+    #[tokio::test]
+    async fn test_generate_with_ollama_native_api() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/generate")
+            .match_body(mockito::Matcher::PartialJsonString(r#"{"stream":false,"prompt":"User: say hi\n\nAssistant:"}"#.to_string()))
+            .with_body(r#"{"model":"qwen2.5-coder","response":"print('hi')","done":true}"#)
+            .create_async()
+            .await;
+        let config = AppConfig {
+            provider: "ollama".to_string(),
+            api_url: format!("{}/v1/chat/completions", server.url()),
+            ollama_native_api: true,
+            max_retries: 0,
+            ..AppConfig::default()
+        };
+        let messages = [Message { role: "user".to_string(), content: "say hi".to_string() }];
+
+        let reply = generate_code_with_history(&messages, &config, &CancellationToken::new()).await.unwrap();
+        assert_eq!(reply, "print('hi')");
+        let streamed = generate_code_streaming(&messages, &config, |_| {}, &CancellationToken::new()).await.unwrap();
+        assert_eq!(streamed, "print('hi')");
+        mock.expect(2).assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_chat_plain_sends_custom_system_prompt() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/chat/completions")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"messages":[{"role":"system","content":"Answer in one word."},{"role":"user","content":"Name this script"}]}"#.to_string(),
+            ))
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"```python\nnot code\n```"}}]}"#)
+            .create_async()
+            .await;
+        let config = AppConfig {
+            provider: "ollama".to_string(),
+            api_url: format!("{}/v1/chat/completions", server.url()),
+            max_retries: 0,
+            ..AppConfig::default()
+        };
+        let messages = [Message { role: "user".to_string(), content: "Name this script".to_string() }];
+
+        // The reply comes back untouched: no code extraction
+        let reply = chat_plain(&messages, &config, "Answer in one word.", &CancellationToken::new()).await.unwrap();
+        assert_eq!(reply, "```python\nnot code\n```");
+        mock.assert_async().await;
+
+        let cancelled = CancellationToken::new();
+        cancelled.cancel();
+        assert!(matches!(chat_plain(&messages, &config, "x", &cancelled).await.unwrap_err(), ApiError::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_explain_code_uses_tutor_prompt() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/chat/completions")
+            .match_body(mockito::Matcher::Regex("patient programming tutor".to_string()))
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"It prints a greeting."}}]}"#)
+            .create_async()
+            .await;
+        let config = AppConfig {
+            provider: "ollama".to_string(),
+            api_url: format!("{}/v1/chat/completions", server.url()),
+            max_retries: 0,
+            ..AppConfig::default()
+        };
+
+        let reply = explain_code("print('hi')", &config, &CancellationToken::new()).await.unwrap();
+        assert_eq!(reply, "It prints a greeting.");
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_ollama_keep_alive_only_for_ollama() {
+        let ollama = AppConfig { provider: "ollama".to_string(), ..AppConfig::default() };
+        assert_eq!(chat_request(&[], &ollama, SYSTEM_PROMPT, false).keep_alive.as_deref(), Some("10m"));
+        let no_keep_alive = AppConfig { ollama_keep_alive: String::new(), ..ollama };
+        assert!(chat_request(&[], &no_keep_alive, SYSTEM_PROMPT, false).keep_alive.is_none());
+        assert!(chat_request(&[], &AppConfig::default(), SYSTEM_PROMPT, false).keep_alive.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_preload_model() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/generate")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"model": "qwen2.5-coder:7b", "keep_alive": "10m"})))
+            .with_body(r#"{"model":"qwen2.5-coder:7b","response":"","done":true}"#)
+            .create_async()
+            .await;
+        let config = AppConfig {
+            provider: "ollama".to_string(),
+            model: "qwen2.5-coder:7b".to_string(),
+            api_url: format!("{}/v1/chat/completions", server.url()),
+            ..AppConfig::default()
+        };
+        preload_model(&config).await.unwrap();
+        mock.assert_async().await;
+
+        // Other providers and ollama_preload = false never send anything
+        preload_model(&AppConfig { ollama_preload: false, ..config }).await.unwrap();
+        preload_model(&AppConfig::default()).await.unwrap();
+        mock.expect(1).assert_async().await;
+    }
+
+    #[test]
+    fn test_api_error_from_status() {
+        let err = |code| ApiError::from_status(Provider::Ollama, reqwest::StatusCode::from_u16(code).unwrap(), None, String::new());
+        assert!(matches!(err(403), ApiError::Auth { .. }));
+        assert!(matches!(err(503), ApiError::Server { status: 503, .. }));
+        assert!(matches!(err(404), ApiError::Request { status: 404, .. }));
+        assert!(err(500).is_retryable());
+        assert!(!err(400).is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_generate_code_streaming_sse() {
+        let mut server = mockito::Server::new_async().await;
+        let body = [
+            r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#,
+            r#"data: {"choices":[{"delta":{"content":"print("}}]}"#,
+            r#"data: {"choices":[{"delta":{"content":"'hé')"}}]}"#,
+            "data: [DONE]",
+        ]
+        .map(|line| format!("{}\n\n", line))
+        .concat();
+        let mock = server
+            .mock("POST", "/v1/chat/completions")
+            .match_body(mockito::Matcher::Regex(r#""stream":true"#.to_string()))
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let config = streaming_config(format!("{}/v1/chat/completions", server.url()));
+        let mut tokens = Vec::new();
+        let full = generate_code_streaming(&[], &config, |t| tokens.push(t.to_string()), &CancellationToken::new())
+            .await
+            .unwrap();
+        mock.assert_async().await;
+        assert_eq!(tokens, vec!["print(", "'hé')"]);
+        assert_eq!(full, "print('hé')");
+    }
+
+    #[tokio::test]
+    async fn test_generate_code_streaming_plain_json_fallback() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("POST", "/v1/chat/completions")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"print(1)"}}]}"#)
+            .create_async()
+            .await;
+
+        let config = streaming_config(format!("{}/v1/chat/completions", server.url()));
+        let mut tokens = Vec::new();
+        let full = generate_code_streaming(&[], &config, |t| tokens.push(t.to_string()), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(tokens, vec!["print(1)"]);
+        assert_eq!(full, "print(1)");
+    }
+
+    #[tokio::test]
+    async fn test_missing_pypi_packages() {
+        let mut server = mockito::Server::new_async().await;
+        let found = server.mock("HEAD", "/requests/json").with_status(200).expect(1).create_async().await;
+        let typo = server.mock("HEAD", "/reqeusts/json").with_status(404).create_async().await;
+        let mapped = server.mock("HEAD", "/opencv-python/json").with_status(200).create_async().await;
+        let _flaky = server.mock("HEAD", "/flaky/json").with_status(503).create_async().await;
+
+        let imports: Vec<String> = ["requests", "reqeusts", "cv2", "flaky"].map(String::from).to_vec();
+        assert_eq!(missing_pypi_packages(&server.url(), &imports).await, vec!["reqeusts"]);
+        // The second lookup of `requests` is served from the cache
+        assert!(missing_pypi_packages(&server.url(), &imports[..1]).await.is_empty());
+
+        found.assert_async().await;
+        typo.assert_async().await;
+        mapped.assert_async().await;
+    }
+
     // ── Provider tests ──────────────────────────────────────────────────
 
     #[test]
@@ -471,6 +1515,20 @@ mod tests {
         assert_eq!(Provider::OpenAiCompatible.display_name(), "OpenAI-compatible");
     }
 
+    #[test]
+    fn test_provider_display_and_from_str() {
+        assert_eq!(Provider::Ollama.to_string(), "Ollama (local)");
+        assert_eq!("hf".parse::<Provider>().unwrap(), Provider::HuggingFace);
+        assert!("nope".parse::<Provider>().is_err());
+    }
+
+    #[test]
+    fn test_provider_all_and_defaults() {
+        assert_eq!(Provider::all().len(), 3);
+        let defaults = provider_defaults();
+        assert_eq!(defaults, vec![(Provider::HuggingFace, HF_DEFAULT_URL), (Provider::Ollama, OLLAMA_DEFAULT_URL)]);
+    }
+
     #[test]
     fn test_provider_ollama_auth_no_key() {
         // Ollama should not require any env var when LLM_API_KEY is unset
@@ -479,4 +1537,101 @@ mod tests {
         let headers = Provider::Ollama.auth_headers().unwrap();
         assert!(!headers.contains_key(AUTHORIZATION));
     }
+
+    #[tokio::test]
+    async fn test_list_models_openai_compatible_is_curated() {
+        let models = list_models(Provider::OpenAiCompatible, false).await;
+        assert_eq!(models, curated_openai_models());
+        assert!(models.iter().any(|m| m == "gpt-4o"));
+    }
+
+    #[test]
+    fn test_switch_provider_to_ollama_uses_default_url() {
+        let mut config = AppConfig::default();
+        let url = switch_provider(&mut config, "ollama", None).unwrap();
+        assert_eq!(url, OLLAMA_DEFAULT_URL);
+        assert_eq!(config.provider, "ollama");
+        assert_eq!(config.api_url, OLLAMA_DEFAULT_URL);
+    }
+
+    #[test]
+    fn test_switch_provider_openai_needs_url() {
+        let mut config = AppConfig {
+            provider: "ollama".to_string(),
+            api_url: OLLAMA_DEFAULT_URL.to_string(),
+            ..AppConfig::default()
+        };
+        assert!(switch_provider(&mut config, "openai", None).is_err());
+        // Failed switch leaves the config untouched
+        assert_eq!(config.provider, "ollama");
+
+        let url = switch_provider(&mut config, "openai", Some("http://localhost:8000/v1/chat/completions")).unwrap();
+        assert_eq!(url, "http://localhost:8000/v1/chat/completions");
+        assert_eq!(config.provider, "openai");
+    }
+
+    #[test]
+    fn test_switch_provider_unknown() {
+        let mut config = AppConfig::default();
+        let err = switch_provider(&mut config, "anthropic", None).unwrap_err();
+        assert!(err.to_string().contains("Unknown provider"));
+        assert_eq!(config.provider, "huggingface");
+    }
+
+    #[test]
+    fn test_apply_offline() {
+        // Default HuggingFace setup switches to local Ollama
+        let mut config = AppConfig { offline: true, ..AppConfig::default() };
+        let notice = apply_offline(&mut config).unwrap().unwrap();
+        assert!(notice.contains("Ollama"));
+        assert_eq!(config.provider, "ollama");
+        assert_eq!(config.model, curated_ollama_models()[0]);
+        assert!(resolve_endpoint(&config).is_ok());
+
+        // A local OpenAI-compatible server is left alone
+        let mut local = AppConfig {
+            offline: true,
+            provider: "openai".to_string(),
+            api_url: "http://127.0.0.1:8000/v1/chat/completions".to_string(),
+            ..AppConfig::default()
+        };
+        assert!(apply_offline(&mut local).unwrap().is_none());
+        assert_eq!(local.provider, "openai");
+
+        // A remote one can't be fixed automatically
+        let mut remote = AppConfig {
+            offline: true,
+            provider: "openai".to_string(),
+            api_url: "https://api.openai.com/v1/chat/completions".to_string(),
+            ..AppConfig::default()
+        };
+        assert!(apply_offline(&mut remote).unwrap_err().to_string().contains("Offline mode"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_fails_fast_for_cloud_provider_offline() {
+        let config = AppConfig { offline: true, ..AppConfig::default() };
+        let err = generate_code_with_history(&[], &config, &CancellationToken::new()).await.unwrap_err();
+        assert!(err.to_string().contains("Offline mode: HuggingFace"));
+        assert!(is_local_url("http://[::1]:11434/v1"));
+        assert!(!is_local_url("https://router.huggingface.co/v1"));
+    }
+
+    #[tokio::test]
+    async fn test_probe_provider() {
+        let mut server = mockito::Server::new_async().await;
+        let config = AppConfig {
+            provider: "openai".to_string(),
+            api_url: format!("{}/v1/chat/completions", server.url()),
+            ..AppConfig::default()
+        };
+
+        // Chat endpoints usually reject GET, which still proves the server is up
+        let up = server.mock("GET", "/v1/chat/completions").with_status(405).create_async().await;
+        assert!(probe_provider(&config).await.unwrap().contains("reachable"));
+        up.remove_async().await;
+
+        let _denied = server.mock("GET", "/v1/chat/completions").with_status(401).create_async().await;
+        assert!(probe_provider(&config).await.unwrap_err().to_string().contains("credentials"));
+    }
 }
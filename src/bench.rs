@@ -0,0 +1,436 @@
+use crate::api::{self, Message};
+use crate::config::AppConfig;
+use crate::highlight::Themed;
+use crate::interface::build_executor;
+use crate::logger::SessionMetrics;
+use crate::python_exec::{format_ms, timed, CodeExecutor, ExecutionMode, SecuritySeverity};
+use crate::utils::extract_python_code;
+use anyhow::{Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use wait_timeout::ChildExt;
+
+/// A benchmark suite (`pymakebot bench <suite.toml>`):
+///
+/// ```toml
+/// models = ["qwen2.5-coder:7b", "llama3.1:8b"]  # optional, default: the configured model
+///
+/// [[case]]
+/// name = "fizzbuzz"
+/// prompt = "Print FizzBuzz for 1 to 15, one per line"
+/// expected_stdout = "1\n2\nFizz\n..."
+///
+/// [[case]]
+/// prompt = "Print the first 10 primes as a JSON list"
+/// checker = "checks/primes.py"  # gets the script's stdout on stdin; exit 0 = pass
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct BenchSuite {
+    #[serde(default)]
+    pub models: Vec<String>,
+    #[serde(rename = "case", default)]
+    pub cases: Vec<BenchCase>,
+}
+
+/// One prompt and how to judge the script generated for it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BenchCase {
+    #[serde(default)]
+    pub name: String,
+    pub prompt: String,
+    /// Compared with the script's stdout, ignoring trailing whitespace.
+    pub expected_stdout: Option<String>,
+    /// Python script receiving the generated script's stdout on stdin.
+    /// Relative paths are resolved against the suite file.
+    pub checker: Option<PathBuf>,
+}
+
+impl BenchSuite {
+    /// Parse a suite file. Every case needs exactly one of `expected_stdout`
+    /// and `checker`; unnamed cases are called `case-<n>`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read the suite {}", path.display()))?;
+        let mut suite: BenchSuite = toml::from_str(&content)
+            .with_context(|| format!("Invalid suite {}", path.display()))?;
+        if suite.cases.is_empty() {
+            anyhow::bail!("{} has no [[case]] entries", path.display());
+        }
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        for (i, case) in suite.cases.iter_mut().enumerate() {
+            if case.name.is_empty() {
+                case.name = format!("case-{}", i + 1);
+            }
+            if case.expected_stdout.is_some() == case.checker.is_some() {
+                anyhow::bail!("Case '{}' needs exactly one of expected_stdout and checker", case.name);
+            }
+            if let Some(checker) = case.checker.as_mut() {
+                if checker.is_relative() {
+                    *checker = base_dir.join(&*checker);
+                }
+            }
+        }
+        Ok(suite)
+    }
+}
+
+/// Result of one case for one model.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseResult {
+    pub model: String,
+    pub case: String,
+    pub passed: bool,
+    pub generation_ms: u64,
+    pub run_ms: u64,
+    pub exit_code: Option<i32>,
+    /// Why the case failed before its output could be judged.
+    pub error: Option<String>,
+}
+
+/// Pass rate and timing of one model over the whole suite.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelSummary {
+    pub model: String,
+    pub passed: usize,
+    pub total: usize,
+    pub pass_rate: f64,
+    pub avg_generation_ms: Option<u64>,
+    pub total_run_ms: u64,
+}
+
+/// Everything `pymakebot bench` reports, printed as JSON with `--json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub models: Vec<ModelSummary>,
+    pub cases: Vec<CaseResult>,
+}
+
+impl BenchReport {
+    pub fn all_passed(&self) -> bool {
+        self.cases.iter().all(|c| c.passed)
+    }
+
+    /// One row per case and model, for spreadsheets and CI artifacts.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("model,case,passed,generation_ms,run_ms,exit_code,error\n");
+        for c in &self.cases {
+            let exit_code = c.exit_code.map(|code| code.to_string()).unwrap_or_default();
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                csv_field(&c.model),
+                csv_field(&c.case),
+                c.passed,
+                c.generation_ms,
+                c.run_ms,
+                exit_code,
+                csv_field(c.error.as_deref().unwrap_or_default()),
+            ));
+        }
+        csv
+    }
+
+    /// Print the per-case results and the per-model pass rates.
+    pub fn display(&self) {
+        println!("\n{}", "━━━━━━━━━━━━ Benchmark Results ━━━━━━━━━━━━".frame().bold());
+        for c in &self.cases {
+            let status = if c.passed { "✓ pass".green() } else { "✗ fail".red() };
+            println!(
+                "{}  {:<24} {:<20} gen {:>7}  run {:>7}  {}",
+                status,
+                c.model,
+                c.case,
+                format_ms(c.generation_ms),
+                format_ms(c.run_ms),
+                c.error.as_deref().unwrap_or_default().dimmed()
+            );
+        }
+        println!();
+        for m in &self.models {
+            println!(
+                "{:<24} {}/{} passed ({:.1}%)  avg generation {}",
+                m.model.bold(),
+                m.passed,
+                m.total,
+                m.pass_rate,
+                m.avg_generation_ms.map_or("n/a".to_string(), format_ms)
+            );
+        }
+        println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".frame());
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Normalize program output for comparison: trailing whitespace on each line
+/// and trailing blank lines are ignored.
+pub fn normalize_output(output: &str) -> String {
+    output.lines().map(str::trim_end).collect::<Vec<_>>().join("\n").trim_end().to_string()
+}
+
+/// Run every case of `suite` against each of its models (or the configured
+/// one). Scripts run non-interactively with dependencies installed, in
+/// Docker when `use_docker` is set and available.
+pub async fn run_suite(config: &AppConfig, suite: &BenchSuite) -> BenchReport {
+    let models = if suite.models.is_empty() { vec![config.model.clone()] } else { suite.models.clone() };
+    let use_docker = config.use_docker && CodeExecutor::check_docker_available().is_ok();
+    let mut report = BenchReport { models: Vec::new(), cases: Vec::new() };
+
+    for model in models {
+        let mut config = config.clone();
+        config.model = model.clone();
+        let mut metrics = SessionMetrics::new();
+        let mut total_run_ms = 0;
+
+        for case in &suite.cases {
+            eprintln!("{} {} / {}", "▶".frame(), model, case.name);
+            let result = run_case(&config, use_docker, case, &mut metrics).await;
+            total_run_ms += result.run_ms;
+            report.cases.push(result);
+        }
+
+        let stats = metrics.per_model.get(&model).cloned().unwrap_or_default();
+        report.models.push(ModelSummary {
+            model,
+            passed: stats.successes,
+            total: suite.cases.len(),
+            pass_rate: if suite.cases.is_empty() { 0.0 } else { stats.successes as f64 * 100.0 / suite.cases.len() as f64 },
+            avg_generation_ms: metrics.average_api_latency_ms(),
+            total_run_ms,
+        });
+    }
+    report
+}
+
+async fn run_case(config: &AppConfig, use_docker: bool, case: &BenchCase, metrics: &mut SessionMetrics) -> CaseResult {
+    let mut result = CaseResult {
+        model: config.model.clone(),
+        case: case.name.clone(),
+        passed: false,
+        generation_ms: 0,
+        run_ms: 0,
+        exit_code: None,
+        error: None,
+    };
+
+    metrics.record_request(&config.model);
+    let messages = vec![Message { role: "user".to_string(), content: case.prompt.clone() }];
+    let started = std::time::Instant::now();
+    let response = api::generate_code_with_history(&messages, config, &CancellationToken::new()).await;
+    metrics.record_api_latency(started.elapsed());
+    result.generation_ms = started.elapsed().as_millis() as u64;
+    let raw_response = match response {
+        Ok(r) => r,
+        Err(e) => {
+            metrics.api_errors += 1;
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+
+    let config = config.clone();
+    let case = case.clone();
+    let judged = tokio::task::spawn_blocking(move || execute_and_judge(&config, use_docker, &case, &raw_response, result))
+        .await
+        .expect("bench case task panicked");
+    metrics.record_execution(&judged.model, judged.passed);
+    judged
+}
+
+/// Blocking half of [`run_case`]: write, run and judge the generated script.
+/// Like `generate_once`, `dry_run` and blocking security findings skip
+/// execution, and dependencies are only installed into a venv with
+/// `auto_install_deps` or `auto_confirm`.
+fn execute_and_judge(config: &AppConfig, use_docker: bool, case: &BenchCase, raw_response: &str, mut result: CaseResult) -> CaseResult {
+    let executor = build_executor(config, use_docker);
+    let code = extract_python_code(raw_response);
+    let script_path = match executor.write_script_for_prompt(&code, Some(&case.prompt)) {
+        Ok(path) => path,
+        Err(e) => {
+            result.error = Some(format!("{:#}", e));
+            return result;
+        }
+    };
+    if let Err(e) = executor.syntax_check(&script_path) {
+        result.error = Some(format!("syntax error: {}", e));
+        return result;
+    }
+    if config.dry_run {
+        result.error = Some("not executed (dry run)".to_string());
+        return result;
+    }
+    if config.use_security_check && CodeExecutor::check_security_scanner_available() {
+        if let Ok(security) = executor.security_check(&script_path) {
+            if security.blocks_at(config.security_block_level) {
+                result.error = Some(format!(
+                    "security findings at or above {} severity",
+                    config.security_block_level.threshold().unwrap_or(SecuritySeverity::High)
+                ));
+                return result;
+            }
+        }
+    }
+
+    let deps = executor.detect_script_dependencies(&code, &script_path);
+    let (venv, deps_ready) = executor.prepare_venv(&deps).unwrap_or(None).unzip();
+    // Without a venv, installing would touch the host's own Python
+    let may_install = config.auto_install_deps || config.auto_confirm;
+    if let Some(ref venv_path) = venv {
+        if !deps.is_empty() && deps_ready != Some(true) && may_install {
+            let _ = executor.install_packages(&deps, Some(venv_path));
+        }
+    }
+    let (execution, run_ms) = timed(|| {
+        executor.execute_script(&script_path, ExecutionMode::Captured, config.execution_timeout_secs, venv.as_deref(), &deps)
+    });
+    if let Some(ref venv_path) = venv {
+        executor.cleanup_venv(venv_path);
+    }
+    result.run_ms = run_ms;
+    let execution = match execution {
+        Ok(execution) => execution,
+        Err(e) => {
+            result.error = Some(format!("{:#}", e));
+            return result;
+        }
+    };
+    result.exit_code = execution.exit_code;
+    if execution.exit_code != Some(0) {
+        result.error = Some(format!("script exited with {}", execution.exit_code.map_or("timeout".to_string(), |c| c.to_string())));
+        return result;
+    }
+
+    match (&case.expected_stdout, &case.checker) {
+        (Some(expected), _) => {
+            result.passed = normalize_output(&execution.stdout) == normalize_output(expected);
+            if !result.passed {
+                result.error = Some("unexpected output".to_string());
+            }
+        }
+        (None, Some(checker)) => match run_checker(&config.python_executable, checker, &execution.stdout, config.execution_timeout_secs) {
+            Ok(true) => result.passed = true,
+            Ok(false) => result.error = Some("checker rejected the output".to_string()),
+            Err(e) => result.error = Some(format!("{:#}", e)),
+        },
+        (None, None) => unreachable!("BenchSuite::load requires expected_stdout or checker"),
+    }
+    result
+}
+
+/// Run a checker script on the host with `stdout` on its stdin, killing it
+/// after `timeout_secs`.
+fn run_checker(python: &str, checker: &Path, stdout: &str, timeout_secs: u64) -> Result<bool> {
+    let mut child = Command::new(python)
+        .arg(checker)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Could not run the checker {}", checker.display()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A checker that exits without reading everything is fine
+        let _ = stdin.write_all(stdout.as_bytes());
+    }
+    match child.wait_timeout(Duration::from_secs(timeout_secs))? {
+        Some(status) => Ok(status.success()),
+        None => {
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(anyhow::anyhow!("The checker {} timed out after {}s", checker.display(), timeout_secs))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_suite() {
+        let dir = std::env::temp_dir().join("test_bench_suite");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("suite.toml");
+        fs::write(
+            &path,
+            "[[case]]\nprompt = \"say hi\"\nexpected_stdout = \"hi\"\n\n[[case]]\nname = \"primes\"\nprompt = \"primes\"\nchecker = \"check.py\"\n",
+        )
+        .unwrap();
+        let suite = BenchSuite::load(&path).unwrap();
+        assert!(suite.models.is_empty());
+        assert_eq!(suite.cases[0].name, "case-1");
+        assert_eq!(suite.cases[1].checker.as_deref(), Some(dir.join("check.py").as_path()));
+
+        fs::write(&path, "[[case]]\nprompt = \"say hi\"\n").unwrap();
+        assert!(BenchSuite::load(&path).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_normalize_output() {
+        assert_eq!(normalize_output("1  \n2\n\n"), normalize_output("1\n2"));
+        assert_ne!(normalize_output("1\n2"), normalize_output("1 2"));
+    }
+
+    #[test]
+    fn test_run_checker_times_out() {
+        let dir = std::env::temp_dir().join(format!("test_bench_checker_{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+        let pass = dir.join("pass.py");
+        let hang = dir.join("hang.py");
+        fs::write(&pass, "import sys\nsys.exit(0 if sys.stdin.read().strip() == 'hi' else 1)\n").unwrap();
+        fs::write(&hang, "import time\ntime.sleep(30)\n").unwrap();
+
+        assert!(run_checker("python3", &pass, "hi\n", 10).unwrap());
+        assert!(!run_checker("python3", &pass, "bye\n", 10).unwrap());
+        assert!(run_checker("python3", &hang, "", 1).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_run_suite() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("POST", "/v1/chat/completions")
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"```python\nprint('hi')\n```"}}]}"#)
+            .create_async()
+            .await;
+        let dir = std::env::temp_dir().join(format!("test_bench_run_{}", uuid::Uuid::new_v4().simple()));
+        let config = AppConfig {
+            provider: "openai-compatible".to_string(),
+            api_url: format!("{}/v1/chat/completions", server.url()),
+            max_retries: 0,
+            use_docker: false,
+            use_venv: false,
+            generated_dir: dir.display().to_string(),
+            ..AppConfig::default()
+        };
+        let case = |name: &str, expected: &str| BenchCase {
+            name: name.to_string(),
+            prompt: "say hi".to_string(),
+            expected_stdout: Some(expected.to_string()),
+            checker: None,
+        };
+        let suite = BenchSuite { models: vec![], cases: vec![case("right", "hi\n"), case("wrong", "bye")] };
+
+        let report = run_suite(&config, &suite).await;
+        assert_eq!(report.cases.iter().map(|c| c.passed).collect::<Vec<_>>(), vec![true, false], "{:?}", report.cases);
+        assert_eq!(report.models[0].passed, 1);
+        assert_eq!(report.models[0].pass_rate, 50.0);
+        assert!(!report.all_passed());
+        let csv = report.to_csv();
+        assert!(csv.starts_with("model,case,passed,"));
+        assert!(csv.lines().nth(2).unwrap().ends_with(",0,unexpected output"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
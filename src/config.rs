@@ -1,29 +1,142 @@
-use serde::Deserialize;
+use crate::api::{Provider, HF_DEFAULT_URL};
+use crate::highlight::{ColorMode, Theme};
+use crate::logger::LogFormat;
+use crate::python_exec::{
+    DockerMount, DockerNetwork, Language, LintOptions, RetentionPolicy, SecurityBlockLevel, SecurityOptions,
+    SecuritySeverity, DEFAULT_SCRIPT_NAME_TEMPLATE,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Upper bound on `max_retries` and `pip_max_retries`; beyond this a failing
+/// API or package index just hangs the REPL.
+const MAX_RETRIES_LIMIT: u32 = 10;
+
+/// Prefix of environment variables that override config fields, e.g. `PYMAKEBOT_MODEL`.
+const ENV_PREFIX: &str = "PYMAKEBOT_";
+
+/// Optional string fields; they are missing from the serialized config while unset.
+const OPTIONAL_STRING_FIELDS: &[&str] = &["ruff_config", "dashboard_token"];
 
 /// Application configuration, loaded from `pymakebot.toml`.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
 pub struct AppConfig {
     pub provider: String,
     pub model: String,
     pub api_url: String,
+    /// With provider "ollama", use the native /api/generate instead of the chat endpoint.
+    pub ollama_native_api: bool,
+    /// How long Ollama keeps the model loaded after a request, e.g. "10m" ("" = server default).
+    pub ollama_keep_alive: String,
+    /// Load the Ollama model at startup so the first request doesn't wait for it.
+    pub ollama_preload: bool,
     pub max_tokens: u32,
     pub temperature: f32,
     pub execution_timeout_secs: u64,
+    /// Kill Interactive-mode scripts after this many seconds (0 = no limit).
+    pub interactive_timeout_secs: u64,
+    pub output_limit_bytes: usize,
+    /// Extra environment variables passed to executed scripts.
+    pub script_env: HashMap<String, String>,
+    /// Whether host executions inherit the bot's environment (API credentials are always stripped).
+    pub inherit_env: bool,
     pub auto_install_deps: bool,
+    /// Retries of pip installs failing with network errors.
+    pub pip_max_retries: u32,
+    /// Look up detected dependencies on PyPI and warn about unknown names (needs network).
+    pub check_pypi: bool,
+    /// Make no connections beyond localhost: local LLM only, no PyPI or model-list fetches (also `--offline`).
+    pub offline: bool,
     pub max_history_messages: usize,
+    /// Estimated-token budget for the history sent with each request (0 = unlimited); oldest turns are dropped first.
+    pub max_context_tokens: usize,
+    /// Send `/refine` requests with only the current code, not the whole conversation.
+    pub compact_refine: bool,
+    /// Longest prompt sent to the model, in characters (0 = unlimited); longer ones are truncated after asking.
+    pub max_prompt_chars: usize,
     pub max_retries: u32,
     pub use_docker: bool,
+    /// Extra host directories mounted into the Docker sandbox, as `host:container[:ro|rw]`.
+    pub docker_extra_mounts: Vec<String>,
+    /// Sandbox network: auto (default), none, bridge, host or a Docker network name.
+    pub docker_network: DockerNetwork,
     pub use_venv: bool,
+    /// Keep host venvs in `~/.cache/pymakebot/venvs`, one per dependency set, and reuse them.
+    pub venv_cache: bool,
     pub use_linting: bool,
+    /// Optional ruff config file passed as `--config`.
+    pub ruff_config: Option<String>,
+    /// Ruff rule codes passed as `--select` (empty = ruff defaults).
+    pub ruff_select: Vec<String>,
+    /// Ruff rule codes passed as `--ignore`.
+    pub ruff_ignore: Vec<String>,
     pub use_security_check: bool,
+    /// Minimum bandit severity that halts execution: none, high, medium or low.
+    pub security_block_level: SecurityBlockLevel,
+    /// Bandit test IDs passed as `--skip`, e.g. ["B101"].
+    pub bandit_skip: Vec<String>,
+    /// Drop bandit findings below this confidence: low (default, report everything), medium or high.
+    pub bandit_min_confidence: SecuritySeverity,
+    pub format_generated_code: bool,
+    /// Extra code patterns that force Interactive mode (no timeout, inherited stdio).
+    pub interactive_keywords: Vec<String>,
+    /// Top-level modules scripts may not import, e.g. ["socket", "subprocess"].
+    pub import_denylist: Vec<String>,
+    /// Language of generated scripts: python (default), bash or javascript.
+    pub language: Language,
+    /// Generate and check code but never execute it (also `--dry-run`).
+    pub dry_run: bool,
+    /// Answer "yes" to the execute and install-dependencies prompts (also `--yes`).
+    pub auto_confirm: bool,
+    /// Also accept the auto-refine prompts after syntax, lint and runtime errors.
+    pub auto_confirm_refine: bool,
+    /// Auto-refines allowed per generation before giving up (0 disables auto-refine).
+    pub max_auto_refine_attempts: u32,
+    /// Animate a spinner on stderr while waiting (never drawn when stderr isn't a terminal; also `--no-spinner`).
+    pub spinner: bool,
+    /// Print the model's raw reply before code extraction (also `--verbose`).
+    pub verbose: bool,
+    /// Show script output longer than this many lines in `$PAGER` (0 = never page).
+    pub pager_lines: usize,
+    /// Terminal colors: auto (default, honours `NO_COLOR`), always or never (also `--no-color`).
+    pub color: ColorMode,
+    /// Palette for dark (default) or light terminal backgrounds.
+    pub theme: Theme,
     pub log_dir: String,
+    /// Save a `<script>.result.json` audit record (prompt, model, lint, security, output) after each run.
+    pub write_execution_report: bool,
+    /// Session log format: text (default) or json (one object per line).
+    pub log_format: LogFormat,
+    /// Mask API keys, bearer tokens and similar secrets in session logs.
+    pub log_redact_secrets: bool,
+    /// Session logs kept in `log_dir`; older ones are deleted at startup (0 = keep all).
+    pub max_log_files: usize,
+    /// Size at which a session log rolls over to `session_<ts>.1.log` (0 = no cap).
+    pub max_log_file_bytes: u64,
     pub generated_dir: String,
+    /// Newest generated scripts kept in `generated_dir`; older ones are deleted (0 = keep all).
+    pub generated_max_files: usize,
+    /// Generated scripts older than this many days are deleted (0 = keep all).
+    pub generated_max_age_days: u64,
+    /// File name of generated scripts without extension: {ts}, {seq} and {slug} are replaced.
+    pub script_name_template: String,
     pub python_executable: String,
     pub enable_dashboard: bool,
+    /// Interface the dashboard binds to. The dashboard is unauthenticated
+    /// unless `dashboard_token` is set, so keep it local otherwise.
+    pub dashboard_host: String,
     pub dashboard_port: u16,
+    /// When set, every dashboard request must carry this token.
+    pub dashboard_token: Option<String>,
+    /// Requests per minute allowed on `/api/generate` and `/api/execute` (0 = unlimited).
+    pub dashboard_max_requests_per_min: u32,
+    /// Fix dashboard generations that fail the syntax check with one
+    /// automatic refinement round before showing them.
+    pub dashboard_auto_refine: bool,
 }
 
 impl Default for AppConfig {
@@ -31,29 +144,349 @@ impl Default for AppConfig {
         Self {
             provider: "huggingface".to_string(),
             model: "Qwen/Qwen2.5-Coder-32B-Instruct".to_string(),
-            api_url: "https://router.huggingface.co/v1/chat/completions".to_string(),
+            api_url: HF_DEFAULT_URL.to_string(),
+            ollama_native_api: false,
+            ollama_keep_alive: "10m".to_string(),
+            ollama_preload: true,
             max_tokens: 16384,
             temperature: 0.2,
             execution_timeout_secs: 30,
+            interactive_timeout_secs: 0,
+            output_limit_bytes: 1024 * 1024,
+            script_env: HashMap::new(),
+            inherit_env: true,
             auto_install_deps: false,
+            pip_max_retries: 2,
+            check_pypi: true,
+            offline: false,
             max_history_messages: 20,
+            max_context_tokens: 24_000,
+            compact_refine: false,
+            max_prompt_chars: 32_000,
             max_retries: 3,
             use_docker: false,
+            docker_extra_mounts: Vec::new(),
+            docker_network: DockerNetwork::Auto,
             use_venv: true,
+            venv_cache: false,
             use_linting: true,
+            ruff_config: None,
+            ruff_select: Vec::new(),
+            ruff_ignore: Vec::new(),
             use_security_check: true,
+            security_block_level: SecurityBlockLevel::High,
+            bandit_skip: Vec::new(),
+            bandit_min_confidence: SecuritySeverity::Low,
+            format_generated_code: false,
+            interactive_keywords: Vec::new(),
+            import_denylist: Vec::new(),
+            language: Language::Python,
+            dry_run: false,
+            auto_confirm: false,
+            auto_confirm_refine: false,
+            max_auto_refine_attempts: 3,
+            spinner: true,
+            verbose: false,
+            pager_lines: 200,
+            color: ColorMode::Auto,
+            theme: Theme::Dark,
             log_dir: "logs".to_string(),
+            write_execution_report: false,
+            log_format: LogFormat::Text,
+            log_redact_secrets: true,
+            max_log_files: 20,
+            max_log_file_bytes: 10 * 1024 * 1024,
             generated_dir: "generated".to_string(),
+            generated_max_files: 0,
+            generated_max_age_days: 0,
+            script_name_template: DEFAULT_SCRIPT_NAME_TEMPLATE.to_string(),
             python_executable: "python3".to_string(),
             enable_dashboard: false,
+            dashboard_host: "127.0.0.1".to_string(),
             dashboard_port: 3000,
+            dashboard_token: None,
+            dashboard_max_requests_per_min: 30,
+            dashboard_auto_refine: true,
+        }
+    }
+}
+
+/// Flags understood by [`AppConfig::apply_args`] that take the next
+/// argument as their value.
+const VALUE_FLAGS: &[&str] = &["--python"];
+
+/// The arguments that are neither flags nor flag values, e.g. the words of
+/// a prompt or a suite path.
+pub fn positional_args(args: &[String]) -> Vec<String> {
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if VALUE_FLAGS.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            positional.push(arg.clone());
         }
     }
+    positional
 }
 
 impl AppConfig {
-    /// Load configuration with the chain: `./pymakebot.toml` -> `~/.pymakebot.toml` -> defaults.
+    /// Ruff options derived from `ruff_config`, `ruff_select` and `ruff_ignore`.
+    pub fn lint_options(&self) -> LintOptions {
+        LintOptions {
+            config: self.ruff_config.clone(),
+            select: self.ruff_select.clone(),
+            ignore: self.ruff_ignore.clone(),
+        }
+    }
+
+    /// Retention policy derived from `generated_max_files` and `generated_max_age_days`.
+    pub fn retention_policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_files: self.generated_max_files,
+            max_age_days: self.generated_max_age_days,
+        }
+    }
+
+    /// Where cached venvs live when `venv_cache` is on: `~/.cache/pymakebot/venvs`.
+    pub fn venv_cache_dir(&self) -> Option<std::path::PathBuf> {
+        self.venv_cache
+            .then(dirs::cache_dir)
+            .flatten()
+            .map(|dir| dir.join("pymakebot").join("venvs"))
+    }
+
+    /// Bandit options derived from `bandit_skip` and `bandit_min_confidence`.
+    pub fn security_options(&self) -> SecurityOptions {
+        SecurityOptions {
+            skip: self.bandit_skip.clone(),
+            min_confidence: self.bandit_min_confidence,
+        }
+    }
+
+    /// Parsed `docker_extra_mounts`. Invalid entries are rejected by
+    /// [`validate`](Self::validate) and skipped here.
+    pub fn docker_mounts(&self) -> Vec<DockerMount> {
+        self.docker_extra_mounts
+            .iter()
+            .filter_map(|spec| DockerMount::parse(spec).ok())
+            .collect()
+    }
+
+    /// The dashboard token, treating an empty string as unset.
+    pub fn dashboard_token(&self) -> Option<&str> {
+        self.dashboard_token.as_deref().filter(|t| !t.is_empty())
+    }
+
+    /// Apply command-line flags (`--dry-run`, `--yes`, `--offline`) on top of the file config.
+    pub fn apply_args(&mut self, args: impl IntoIterator<Item = String>) {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dry-run" => self.dry_run = true,
+                "--yes" | "-y" => self.auto_confirm = true,
+                "--offline" => self.offline = true,
+                "--no-spinner" => self.spinner = false,
+                "--verbose" | "-v" => self.verbose = true,
+                "--no-color" => self.color = ColorMode::Never,
+                "--python" => {
+                    if let Some(python) = args.next() {
+                        self.python_executable = python;
+                    }
+                }
+                _ => {
+                    if let Some(python) = arg.strip_prefix("--python=") {
+                        self.python_executable = python.to_string();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check for values that can't work, returning every problem at once.
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        if !(0.0..=2.0).contains(&self.temperature) {
+            errors.push(format!("temperature = {} is out of range; use a value between 0.0 and 2.0", self.temperature));
+        }
+        if self.max_tokens == 0 {
+            errors.push("max_tokens = 0 leaves no room for a reply; use a positive value such as 4096".to_string());
+        }
+        if self.max_retries > MAX_RETRIES_LIMIT {
+            errors.push(format!("max_retries = {} is too high; use at most {}", self.max_retries, MAX_RETRIES_LIMIT));
+        }
+        if !self.script_name_template.contains("{ts}") {
+            errors.push(format!(
+                "script_name_template = {:?} needs {{ts}}: scripts are listed and sorted by that timestamp",
+                self.script_name_template
+            ));
+        }
+        if self.pip_max_retries > MAX_RETRIES_LIMIT {
+            errors.push(format!("pip_max_retries = {} is too high; use at most {}", self.pip_max_retries, MAX_RETRIES_LIMIT));
+        }
+        if self.execution_timeout_secs == 0 {
+            errors.push("execution_timeout_secs = 0 would kill every script immediately; use a positive value".to_string());
+        }
+        for spec in &self.docker_extra_mounts {
+            if let Err(e) = DockerMount::parse(spec) {
+                errors.push(format!("{:#}", e));
+            }
+        }
+        match Provider::from_config(&self.provider) {
+            Ok(Provider::OpenAiCompatible) if self.api_url.trim().is_empty() => {
+                errors.push("provider \"openai-compatible\" requires api_url, e.g. \"http://localhost:8000/v1/chat/completions\"".to_string());
+            }
+            Ok(_) => {}
+            Err(e) => errors.push(e.to_string()),
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Invalid configuration in pymakebot.toml:\n  - {}", errors.join("\n  - ")))
+        }
+    }
+
+    /// Legal but questionable values worth pointing out at startup.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.temperature > 1.0 {
+            warnings.push(format!("temperature = {} is high; generated code may be erratic (0.2 is recommended)", self.temperature));
+        }
+        if self.max_tokens > 0 && self.max_tokens < 512 {
+            warnings.push(format!("max_tokens = {} is low; longer scripts will be cut off", self.max_tokens));
+        }
+        if self.max_history_messages == 0 {
+            warnings.push("max_history_messages = 0 disables conversation context; /refine and follow-ups won't see earlier code".to_string());
+        }
+        if self.use_docker && self.language != Language::Python {
+            warnings.push(format!("use_docker = true only supports Python; {} scripts will fail to run", self.language.name()));
+        }
+        if !self.use_docker && !self.docker_extra_mounts.is_empty() {
+            warnings.push("docker_extra_mounts only applies with use_docker = true; host runs can already read those paths".to_string());
+        }
+        for mount in self.docker_mounts().iter().filter(|m| !m.read_only) {
+            warnings.push(format!("{} is mounted read-write into the sandbox; generated scripts can modify or delete files there", mount.host.display()));
+        }
+        warnings
+    }
+
+    /// A complete `pymakebot.toml` listing every field at its default value,
+    /// each with a comment explaining it.
+    pub fn default_toml() -> String {
+        let d = Self::default();
+        let mut out = String::from(
+            "# Python Maker Bot configuration. Every field is optional; these are the defaults.\n",
+        );
+        let mut section = |title: &str, fields: &[(&str, String, &str)]| {
+            out.push_str(&format!("\n# {}\n", title));
+            for (key, value, comment) in fields {
+                let line = format!("{} = {}", key, value);
+                out.push_str(&format!("{:<30} # {}\n", line, comment));
+            }
+        };
+
+        section("LLM provider", &[
+            ("provider", toml_value(&d.provider), "\"huggingface\", \"ollama\" or \"openai-compatible\""),
+            ("model", toml_value(&d.model), "Model name sent to the provider"),
+            ("api_url", toml_value(&d.api_url), "Chat completions endpoint (required for openai-compatible)"),
+            ("ollama_native_api", toml_value(&d.ollama_native_api), "Ollama only: send a flat prompt to /api/generate instead of the chat endpoint"),
+            ("ollama_keep_alive", toml_value(&d.ollama_keep_alive), "Ollama only: keep the model loaded this long after a request (\"-1\" = forever, \"\" = server default)"),
+            ("ollama_preload", toml_value(&d.ollama_preload), "Ollama only: load the model at startup to cut first-request latency"),
+            ("max_tokens", toml_value(&d.max_tokens), "Maximum tokens in each reply"),
+            ("temperature", d.temperature.to_string(), "Sampling temperature, 0.0-2.0 (low = more deterministic)"),
+            ("max_retries", toml_value(&d.max_retries), "Retries on network errors, 429 and 5xx responses (at most 10)"),
+            ("max_history_messages", toml_value(&d.max_history_messages), "Trim oldest messages beyond this"),
+            ("max_context_tokens", toml_value(&d.max_context_tokens), "Also trim once history exceeds this many estimated tokens (0 = no limit)"),
+            ("compact_refine", toml_value(&d.compact_refine), "/refine sends only the current code and the request, not the whole chat"),
+            ("max_prompt_chars", toml_value(&d.max_prompt_chars), "Offer to truncate longer prompts before sending (0 = no limit)"),
+            ("offline", toml_value(&d.offline), "No network beyond localhost: Ollama only, no PyPI lookups (same as --offline)"),
+        ]);
+        section("Execution", &[
+            ("language", toml_value(&d.language), "python, bash or javascript (lint/security/deps/venv/Docker are Python-only)"),
+            ("execution_timeout_secs", toml_value(&d.execution_timeout_secs), "Kill scripts after this many seconds"),
+            ("interactive_timeout_secs", toml_value(&d.interactive_timeout_secs), "Kill interactive scripts after this many seconds (0 = never)"),
+            ("output_limit_bytes", toml_value(&d.output_limit_bytes), "Max stdout/stderr kept per stream (0 = no cap)"),
+            ("auto_install_deps", toml_value(&d.auto_install_deps), "Install detected dependencies without prompting"),
+            ("pip_max_retries", toml_value(&d.pip_max_retries), "Retries of pip installs after network errors (at most 10)"),
+            ("check_pypi", toml_value(&d.check_pypi), "Warn when a detected dependency isn't on PyPI"),
+            ("use_docker", toml_value(&d.use_docker), "Run scripts in the Docker sandbox (pymakebot build-sandbox)"),
+            ("docker_extra_mounts", toml_value(&d.docker_extra_mounts), "Extra sandbox mounts, e.g. [\"/data/in:/data:ro\"] (rw lets scripts write)"),
+            ("docker_network", toml_value(&d.docker_network), "auto (offline unless installing deps), none, bridge, host or a network name"),
+            ("use_venv", toml_value(&d.use_venv), "Isolate each run in a temporary virtual environment"),
+            ("venv_cache", toml_value(&d.venv_cache), "Reuse venvs per dependency set from ~/.cache/pymakebot/venvs instead of rebuilding"),
+            ("python_executable", toml_value(&d.python_executable), "Interpreter used for host runs and venvs"),
+            ("interactive_keywords", toml_value(&d.interactive_keywords), "Extra patterns forcing interactive mode, e.g. [\"gradio\"]"),
+            ("import_denylist", toml_value(&d.import_denylist), "Modules scripts may not import, e.g. [\"socket\", \"ctypes\"]"),
+            ("dry_run", toml_value(&d.dry_run), "Generate and check code but never execute it (same as --dry-run)"),
+            ("auto_confirm", toml_value(&d.auto_confirm), "Execute and install dependencies without prompting (same as --yes)"),
+            ("auto_confirm_refine", toml_value(&d.auto_confirm_refine), "Also auto-refine after syntax, lint and runtime errors"),
+            ("max_auto_refine_attempts", toml_value(&d.max_auto_refine_attempts), "Auto-refines per generation before giving up (0 = never auto-refine)"),
+            ("spinner", toml_value(&d.spinner), "Show a progress spinner on stderr (same as --no-spinner when false)"),
+            ("verbose", toml_value(&d.verbose), "Print the raw model reply before code extraction (same as --verbose)"),
+            ("pager_lines", toml_value(&d.pager_lines), "Page script output longer than this through $PAGER or less (0 = never)"),
+            ("color", toml_value(&d.color), "auto (off when NO_COLOR is set or not a terminal), always or never (same as --no-color)"),
+            ("theme", toml_value(&d.theme), "dark or light, for the terminal background"),
+        ]);
+        section("Code quality", &[
+            ("use_linting", toml_value(&d.use_linting), "Run ruff on generated code before execution"),
+            ("ruff_select", toml_value(&d.ruff_select), "Rule codes passed as --select, e.g. [\"E\", \"F\"]"),
+            ("ruff_ignore", toml_value(&d.ruff_ignore), "Rule codes passed as --ignore, e.g. [\"E501\"]"),
+            ("# ruff_config", toml_value(&"ruff.toml"), "Optional ruff config file (passed as --config)"),
+            ("use_security_check", toml_value(&d.use_security_check), "Run bandit on generated code before execution"),
+            ("security_block_level", toml_value(&d.security_block_level), "Halt on findings at/above: none, high, medium or low"),
+            ("bandit_skip", toml_value(&d.bandit_skip), "Bandit test IDs passed as --skip, e.g. [\"B101\"] (assert used)"),
+            ("bandit_min_confidence", toml_value(&d.bandit_min_confidence), "Ignore findings below this confidence: low (report all), medium or high"),
+            ("format_generated_code", toml_value(&d.format_generated_code), "Format generated code with ruff format (or black)"),
+        ]);
+        section("Web dashboard", &[
+            ("enable_dashboard", toml_value(&d.enable_dashboard), "Start the web dashboard alongside the REPL"),
+            ("dashboard_host", toml_value(&d.dashboard_host), "\"0.0.0.0\" exposes it to your network (set dashboard_token!)"),
+            ("dashboard_port", toml_value(&d.dashboard_port), "Port for the dashboard HTTP server"),
+            ("# dashboard_token", toml_value(&"change-me"), "Require this token on every dashboard request"),
+            ("dashboard_max_requests_per_min", toml_value(&d.dashboard_max_requests_per_min), "Generate/execute rate limit (0 = unlimited)"),
+            ("dashboard_auto_refine", toml_value(&d.dashboard_auto_refine), "Ask the model once to fix generations with syntax errors"),
+        ]);
+        section("Files and environment", &[
+            ("log_dir", toml_value(&d.log_dir), "Session logs"),
+            ("write_execution_report", toml_value(&d.write_execution_report), "Save <script>.result.json with prompt, model, lint/security results and output after each run"),
+            ("log_format", toml_value(&d.log_format), "text, or json for one {ts, level, kind, content} object per line"),
+            ("log_redact_secrets", toml_value(&d.log_redact_secrets), "Mask API keys and tokens before they reach the log"),
+            ("max_log_files", toml_value(&d.max_log_files), "Oldest session logs beyond this are deleted at startup (0 = keep all)"),
+            ("max_log_file_bytes", toml_value(&d.max_log_file_bytes), "Roll over to session_<ts>.1.log past this size (0 = no cap)"),
+            ("generated_dir", toml_value(&d.generated_dir), "Generated scripts"),
+            ("generated_max_files", toml_value(&d.generated_max_files), "Delete the oldest scripts beyond this, at startup and after each write (0 = keep all)"),
+            ("generated_max_age_days", toml_value(&d.generated_max_age_days), "Delete scripts older than this many days (0 = keep all)"),
+            ("script_name_template", toml_value(&d.script_name_template), "Script file names: {ts} (required), {seq} and {slug} (from the prompt)"),
+            ("inherit_env", toml_value(&d.inherit_env), "Host runs inherit the bot's environment (API keys are always stripped)"),
+        ]);
+
+        out.push_str("\n# Extra environment variables for executed scripts\n[script_env]\n# API_KEY = \"...\"\n");
+        out
+    }
+
+    /// Write [`AppConfig::default_toml`] to `path`, refusing to replace an
+    /// existing file unless `force` is set.
+    pub fn write_default_toml(path: &Path, force: bool) -> Result<()> {
+        if path.exists() && !force {
+            bail!("{} already exists; use --force to overwrite it", path.display());
+        }
+        fs::write(path, Self::default_toml())
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Load configuration with the chain: `PYMAKEBOT_*` environment variables
+    /// -> `./pymakebot.toml` -> `~/.pymakebot.toml` -> defaults.
     pub fn load() -> Self {
+        let mut config = Self::load_files();
+        for error in config.apply_env_overrides(std::env::vars()) {
+            eprintln!("Warning: ignoring {}", error);
+        }
+        config
+    }
+
+    fn load_files() -> Self {
         let candidates = Self::config_paths();
         for path in &candidates {
             if let Ok(contents) = fs::read_to_string(path) {
@@ -68,6 +501,72 @@ impl AppConfig {
         Self::default()
     }
 
+    /// Override fields from `PYMAKEBOT_<FIELD>` variables (e.g. `PYMAKEBOT_MAX_TOKENS=4096`).
+    ///
+    /// Values are parsed according to the field's type: booleans accept
+    /// true/false/1/0/yes/no, lists are comma-separated. Invalid variables are
+    /// skipped and returned as error messages; the rest still apply.
+    pub fn apply_env_overrides(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Vec<String> {
+        let mut errors = Vec::new();
+        let Ok(mut table) = toml::Table::try_from(&*self) else {
+            return errors;
+        };
+
+        for (name, raw) in vars {
+            let Some(mut key) = name.strip_prefix(ENV_PREFIX).map(str::to_lowercase) else {
+                continue;
+            };
+            // PYMAKEBOT_PYTHON is the short form, like --python
+            if key == "python" {
+                key = "python_executable".to_string();
+            }
+            let value = match table.get(&key) {
+                Some(toml::Value::Boolean(_)) => parse_env_bool(&raw).map(toml::Value::Boolean),
+                Some(toml::Value::Integer(_)) => raw
+                    .trim()
+                    .parse()
+                    .map(toml::Value::Integer)
+                    .map_err(|_| "expected a whole number".to_string()),
+                Some(toml::Value::Float(_)) => raw
+                    .trim()
+                    .parse()
+                    .map(toml::Value::Float)
+                    .map_err(|_| "expected a number".to_string()),
+                Some(toml::Value::Array(_)) => Ok(toml::Value::Array(
+                    raw.split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(|s| toml::Value::String(s.to_string()))
+                        .collect(),
+                )),
+                Some(toml::Value::Table(_)) => {
+                    Err("tables can't be set from the environment; use pymakebot.toml".to_string())
+                }
+                Some(_) => Ok(toml::Value::String(raw.clone())),
+                None if OPTIONAL_STRING_FIELDS.contains(&key.as_str()) => Ok(toml::Value::String(raw.clone())),
+                None => Err("no such config field".to_string()),
+            };
+
+            // Re-deserialize so enum fields like `language` are checked too
+            let applied = value.and_then(|value| {
+                let mut candidate = table.clone();
+                candidate.insert(key, value);
+                toml::Value::Table(candidate.clone())
+                    .try_into::<AppConfig>()
+                    .map(|cfg| (candidate, cfg))
+                    .map_err(|e| e.to_string().trim().to_string())
+            });
+            match applied {
+                Ok((candidate, cfg)) => {
+                    table = candidate;
+                    *self = cfg;
+                }
+                Err(e) => errors.push(format!("{}={:?}: {}", name, raw, e)),
+            }
+        }
+        errors
+    }
+
     fn config_paths() -> Vec<PathBuf> {
         let mut paths = vec![PathBuf::from("pymakebot.toml")];
         if let Some(home) = dirs::home_dir() {
@@ -77,6 +576,20 @@ impl AppConfig {
     }
 }
 
+/// Parse a boolean environment value.
+fn parse_env_bool(raw: &str) -> Result<bool, String> {
+    match raw.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+/// Render a value as a TOML literal for [`AppConfig::default_toml`].
+fn toml_value<T: Serialize + ?Sized>(value: &T) -> String {
+    toml::Value::try_from(value).map(|v| v.to_string()).unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,21 +599,62 @@ mod tests {
         let cfg = AppConfig::default();
         assert_eq!(cfg.provider, "huggingface");
         assert_eq!(cfg.model, "Qwen/Qwen2.5-Coder-32B-Instruct");
+        assert!(!cfg.ollama_native_api);
+        assert_eq!(cfg.ollama_keep_alive, "10m");
+        assert!(cfg.ollama_preload);
         assert_eq!(cfg.max_tokens, 16384);
         assert_eq!(cfg.temperature, 0.2);
         assert_eq!(cfg.execution_timeout_secs, 30);
+        assert_eq!(cfg.interactive_timeout_secs, 0);
+        assert_eq!(cfg.output_limit_bytes, 1024 * 1024);
+        assert!(cfg.script_env.is_empty());
+        assert!(cfg.inherit_env);
         assert!(!cfg.auto_install_deps);
+        assert_eq!(cfg.pip_max_retries, 2);
+        assert!(cfg.check_pypi);
+        assert!(!cfg.offline);
         assert_eq!(cfg.max_history_messages, 20);
+        assert_eq!(cfg.max_context_tokens, 24_000);
+        assert!(!cfg.compact_refine);
+        assert_eq!(cfg.max_prompt_chars, 32_000);
+        assert_eq!(cfg.pager_lines, 200);
         assert_eq!(cfg.max_retries, 3);
         assert!(!cfg.use_docker);
         assert!(cfg.use_venv);
+        assert!(!cfg.venv_cache);
         assert!(cfg.use_linting);
+        assert!(cfg.ruff_config.is_none());
+        assert!(cfg.ruff_select.is_empty());
+        assert!(cfg.ruff_ignore.is_empty());
+        assert!(cfg.bandit_skip.is_empty());
+        assert_eq!(cfg.bandit_min_confidence, SecuritySeverity::Low);
         assert!(cfg.use_security_check);
+        assert_eq!(cfg.security_block_level, SecurityBlockLevel::High);
+        assert!(!cfg.format_generated_code);
+        assert!(cfg.interactive_keywords.is_empty());
+        assert!(cfg.import_denylist.is_empty());
+        assert_eq!(cfg.language, Language::Python);
+        assert!(!cfg.dry_run);
+        assert!(!cfg.auto_confirm);
+        assert!(!cfg.auto_confirm_refine);
+        assert_eq!(cfg.max_auto_refine_attempts, 3);
         assert_eq!(cfg.log_dir, "logs");
+        assert!(!cfg.write_execution_report);
+        assert_eq!(cfg.log_format, LogFormat::Text);
+        assert!(cfg.log_redact_secrets);
+        assert_eq!(cfg.max_log_files, 20);
+        assert_eq!(cfg.max_log_file_bytes, 10 * 1024 * 1024);
         assert_eq!(cfg.python_executable, "python3");
         assert_eq!(cfg.generated_dir, "generated");
+        assert_eq!(cfg.generated_max_files, 0);
+        assert_eq!(cfg.generated_max_age_days, 0);
+        assert_eq!(cfg.script_name_template, "script_{ts}_{slug}");
         assert!(!cfg.enable_dashboard);
+        assert_eq!(cfg.dashboard_host, "127.0.0.1");
         assert_eq!(cfg.dashboard_port, 3000);
+        assert!(cfg.dashboard_token.is_none());
+        assert_eq!(cfg.dashboard_max_requests_per_min, 30);
+        assert!(cfg.dashboard_auto_refine);
     }
 
     #[test]
@@ -151,6 +705,249 @@ mod tests {
         assert_eq!(cfg.generated_dir, "my_scripts");
     }
 
+    #[test]
+    fn test_ruff_options_deserialize() {
+        let toml_str = r#"
+            ruff_config = "ruff.toml"
+            ruff_select = ["E", "F"]
+            ruff_ignore = ["E501"]
+        "#;
+        let cfg: AppConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(cfg.ruff_config.as_deref(), Some("ruff.toml"));
+        assert_eq!(cfg.ruff_select, vec!["E", "F"]);
+        assert_eq!(cfg.ruff_ignore, vec!["E501"]);
+    }
+
+    #[test]
+    fn test_security_block_level_deserialize() {
+        for (value, expected) in [
+            ("none", SecurityBlockLevel::Never),
+            ("high", SecurityBlockLevel::High),
+            ("medium", SecurityBlockLevel::Medium),
+            ("low", SecurityBlockLevel::Low),
+        ] {
+            let cfg: AppConfig = toml::from_str(&format!("security_block_level = \"{}\"", value)).unwrap();
+            assert_eq!(cfg.security_block_level, expected);
+        }
+        assert!(toml::from_str::<AppConfig>("security_block_level = \"critical\"").is_err());
+    }
+
+    #[test]
+    fn test_apply_args_dry_run() {
+        let mut cfg = AppConfig::default();
+        cfg.apply_args(vec!["--verbose".to_string()]);
+        assert!(!cfg.dry_run);
+        cfg.apply_args(vec!["--dry-run".to_string()]);
+        assert!(cfg.dry_run);
+    }
+
+    #[test]
+    fn test_apply_args_offline() {
+        let mut cfg = AppConfig::default();
+        cfg.apply_args(vec!["--offline".to_string()]);
+        assert!(cfg.offline);
+    }
+
+    #[test]
+    fn test_apply_args_no_spinner() {
+        let mut cfg = AppConfig::default();
+        assert!(cfg.spinner);
+        cfg.apply_args(vec!["--no-spinner".to_string()]);
+        assert!(!cfg.spinner);
+    }
+
+    #[test]
+    fn test_apply_args_no_color() {
+        let mut cfg = AppConfig::default();
+        assert_eq!(cfg.color, ColorMode::Auto);
+        cfg.apply_args(vec!["--no-color".to_string()]);
+        assert_eq!(cfg.color, ColorMode::Never);
+
+        let cfg: AppConfig = toml::from_str("color = \"always\"\ntheme = \"light\"").unwrap();
+        assert_eq!((cfg.color, cfg.theme), (ColorMode::Always, Theme::Light));
+    }
+
+    #[test]
+    fn test_apply_args_python() {
+        let mut cfg = AppConfig::default();
+        cfg.apply_args(vec!["--python".to_string(), "/opt/py312/bin/python3".to_string(), "-v".to_string()]);
+        assert_eq!(cfg.python_executable, "/opt/py312/bin/python3");
+        assert!(cfg.verbose);
+        cfg.apply_args(vec!["--python=python3.11".to_string()]);
+        assert_eq!(cfg.python_executable, "python3.11");
+
+        let args: Vec<String> = ["--python", "python3.11", "make", "--dry-run", "a", "game"].iter().map(|a| a.to_string()).collect();
+        assert_eq!(positional_args(&args), vec!["make", "a", "game"]);
+
+        let errors = cfg.apply_env_overrides(vec![("PYMAKEBOT_PYTHON".to_string(), "pypy3".to_string())]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(cfg.python_executable, "pypy3");
+    }
+
+    #[test]
+    fn test_apply_args_verbose() {
+        let mut cfg = AppConfig::default();
+        assert!(!cfg.verbose);
+        cfg.apply_args(vec!["-v".to_string()]);
+        assert!(cfg.verbose);
+    }
+
+    #[test]
+    fn test_apply_args_yes() {
+        for flag in ["--yes", "-y"] {
+            let mut cfg = AppConfig::default();
+            cfg.apply_args(vec![flag.to_string()]);
+            assert!(cfg.auto_confirm);
+            // Auto-refine stays opt-in through its own setting
+            assert!(!cfg.auto_confirm_refine);
+        }
+    }
+
+    #[test]
+    fn test_default_toml_round_trips() {
+        let text = AppConfig::default_toml();
+        let table: toml::Table = toml::from_str(&text).unwrap();
+        // Every field except the two optional ones is written out
+        assert_eq!(table.len(), 63);
+        assert!(text.contains("# ruff_config = \"ruff.toml\""));
+
+        let cfg: AppConfig = toml::from_str(&text).unwrap();
+        let d = AppConfig::default();
+        assert_eq!(cfg.model, d.model);
+        assert_eq!(cfg.temperature, d.temperature);
+        assert_eq!(cfg.security_block_level, d.security_block_level);
+        assert_eq!(cfg.language, d.language);
+        assert_eq!(cfg.dashboard_max_requests_per_min, d.dashboard_max_requests_per_min);
+        assert!(cfg.script_env.is_empty());
+    }
+
+    #[test]
+    fn test_write_default_toml_refuses_overwrite() {
+        let dir = std::env::temp_dir().join(format!("pymakebot_init_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pymakebot.toml");
+
+        AppConfig::write_default_toml(&path, false).unwrap();
+        fs::write(&path, "model = \"mine\"").unwrap();
+        let err = AppConfig::write_default_toml(&path, false).unwrap_err();
+        assert!(err.to_string().contains("--force"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "model = \"mine\"");
+
+        AppConfig::write_default_toml(&path, true).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("provider = \"huggingface\""));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_apply_env_overrides() {
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<Vec<_>>()
+        };
+
+        let mut cfg = AppConfig::default();
+        let errors = cfg.apply_env_overrides(vars(&[
+            ("PYMAKEBOT_MODEL", "codellama"),
+            ("PYMAKEBOT_PROVIDER", "ollama"),
+            ("PYMAKEBOT_MAX_TOKENS", "4096"),
+            ("PYMAKEBOT_USE_DOCKER", "yes"),
+            ("PYMAKEBOT_TEMPERATURE", "0.5"),
+            ("PYMAKEBOT_RUFF_SELECT", "E, F"),
+            ("PYMAKEBOT_LANGUAGE", "bash"),
+            ("PYMAKEBOT_DASHBOARD_TOKEN", "s3cret"),
+            ("HOME", "/root"),
+        ]));
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(cfg.model, "codellama");
+        assert_eq!(cfg.provider, "ollama");
+        assert_eq!(cfg.max_tokens, 4096);
+        assert!(cfg.use_docker);
+        assert_eq!(cfg.temperature, 0.5);
+        assert_eq!(cfg.ruff_select, vec!["E", "F"]);
+        assert_eq!(cfg.language, Language::Bash);
+        assert_eq!(cfg.dashboard_token(), Some("s3cret"));
+
+        // Bad values are reported and skipped; valid ones still apply
+        let mut cfg = AppConfig::default();
+        let errors = cfg.apply_env_overrides(vars(&[
+            ("PYMAKEBOT_USE_VENV", "maybe"),
+            ("PYMAKEBOT_MAX_TOKENS", "lots"),
+            ("PYMAKEBOT_DASHBOARD_PORT", "70000"),
+            ("PYMAKEBOT_LANGUAGE", "ruby"),
+            ("PYMAKEBOT_COLOUR", "blue"),
+            ("PYMAKEBOT_SCRIPT_ENV", "A=1"),
+            ("PYMAKEBOT_MAX_RETRIES", "5"),
+        ]));
+        assert_eq!(errors.len(), 6, "{:?}", errors);
+        assert!(errors[0].contains("PYMAKEBOT_USE_VENV") && errors[0].contains("true or false"));
+        assert!(errors[1].contains("whole number"));
+        assert!(errors[4].contains("no such config field"));
+        assert!(cfg.use_venv);
+        assert_eq!(cfg.max_tokens, AppConfig::default().max_tokens);
+        assert_eq!(cfg.max_retries, 5);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(AppConfig::default().validate().is_ok());
+        assert!(AppConfig::default().warnings().is_empty());
+
+        let bad = AppConfig {
+            temperature: 5.0,
+            max_tokens: 0,
+            provider: "gpt-cloud".to_string(),
+            ..AppConfig::default()
+        };
+        let msg = bad.validate().unwrap_err().to_string();
+        assert!(msg.contains("temperature = 5"));
+        assert!(msg.contains("max_tokens = 0"));
+        assert!(msg.contains("Unknown provider 'gpt-cloud'"));
+
+        let no_url = AppConfig {
+            provider: "openai-compatible".to_string(),
+            api_url: String::new(),
+            ..AppConfig::default()
+        };
+        assert!(no_url.validate().unwrap_err().to_string().contains("requires api_url"));
+
+        let no_ts = AppConfig { script_name_template: "{slug}".to_string(), ..AppConfig::default() };
+        assert!(no_ts.validate().unwrap_err().to_string().contains("needs {ts}"));
+
+        let hot = AppConfig { temperature: 1.5, ..AppConfig::default() };
+        assert!(hot.validate().is_ok());
+        assert_eq!(hot.warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_language_deserialize() {
+        for (value, expected) in [
+            ("python", Language::Python),
+            ("bash", Language::Bash),
+            ("sh", Language::Bash),
+            ("javascript", Language::JavaScript),
+            ("js", Language::JavaScript),
+        ] {
+            let cfg: AppConfig = toml::from_str(&format!("language = \"{}\"", value)).unwrap();
+            assert_eq!(cfg.language, expected);
+        }
+        assert!(toml::from_str::<AppConfig>("language = \"cobol\"").is_err());
+    }
+
+    #[test]
+    fn test_script_env_table_deserialize() {
+        let toml_str = r#"
+            inherit_env = false
+
+            [script_env]
+            API_KEY = "abc123"
+            MODE = "test"
+        "#;
+        let cfg: AppConfig = toml::from_str(toml_str).unwrap();
+        assert!(!cfg.inherit_env);
+        assert_eq!(cfg.script_env.len(), 2);
+        assert_eq!(cfg.script_env["API_KEY"], "abc123");
+        assert_eq!(cfg.script_env["MODE"], "test");
+    }
+
     #[test]
     fn test_load_falls_back_to_defaults() {
         // When no config file exists, load() returns defaults
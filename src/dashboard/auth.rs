@@ -0,0 +1,95 @@
+use axum::{
+    extract::{Query, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    token: Option<String>,
+}
+
+/// Middleware that rejects requests without the configured dashboard token.
+///
+/// The token is accepted as `Authorization: Bearer <token>` or as a `?token=`
+/// query parameter, since browsers can't set headers when opening the page
+/// or a WebSocket.
+pub async fn require_token(
+    State(expected): State<Arc<str>>,
+    Query(query): Query<TokenQuery>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    let authorized = [bearer, query.token.as_deref()]
+        .into_iter()
+        .flatten()
+        .any(|given| constant_time_eq(given.as_bytes(), expected.as_bytes()));
+
+    if authorized {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            Json(serde_json::json!({ "status": "error", "message": "Unauthorized" })),
+        )
+            .into_response()
+    }
+}
+
+/// Compare without short-circuiting so response timing doesn't leak the token.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+
+    async fn serve_protected(token: &str) -> String {
+        let app = Router::new()
+            .route("/api/ping", get(|| async { "pong" }))
+            .route_layer(middleware::from_fn_with_state(Arc::<str>::from(token), require_token));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/api/ping", addr)
+    }
+
+    #[tokio::test]
+    async fn test_require_token() {
+        let url = serve_protected("s3cret").await;
+        let client = reqwest::Client::new();
+
+        let missing = client.get(&url).send().await.unwrap();
+        assert_eq!(missing.status(), 401);
+
+        let wrong = client.get(&url).bearer_auth("nope").send().await.unwrap();
+        assert_eq!(wrong.status(), 401);
+
+        let header = client.get(&url).bearer_auth("s3cret").send().await.unwrap();
+        assert_eq!(header.status(), 200);
+        assert_eq!(header.text().await.unwrap(), "pong");
+
+        let query = client.get(format!("{}?token=s3cret", url)).send().await.unwrap();
+        assert_eq!(query.status(), 200);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::python_exec::Language;
+use crate::utils::{extract_imports, extract_project, is_stdlib, requirements_txt, ProjectFile};
+
+/// Files of a standalone project built from a session's generated code: the
+/// script as `main.<ext>` (or every file of a multi-file project), plus a
+/// `requirements.txt` for Python and a short `README.md`, unless the model
+/// already wrote them. Files whose `title=` path would escape the project
+/// folder are dropped.
+pub fn project_files(session_name: &str, code: &str, language: Language) -> Vec<ProjectFile> {
+    let entry = format!("main.{}", language.extension());
+    let mut files = extract_project(code)
+        .map(|files| files.into_iter().filter(ProjectFile::has_safe_path).collect::<Vec<_>>())
+        .filter(|files| !files.is_empty())
+        .unwrap_or_else(|| {
+            vec![ProjectFile {
+                path: entry.clone(),
+                content: code.trim_end().to_string() + "\n",
+            }]
+        });
+    let has = |files: &[ProjectFile], path: &str| files.iter().any(|f| f.path == path);
+
+    if language == Language::Python && !has(&files, "requirements.txt") {
+        let content = requirements(&files);
+        files.push(ProjectFile { path: "requirements.txt".to_string(), content });
+    }
+
+    if !has(&files, "README.md") {
+        let run = match language.interpreter() {
+            Some(interpreter) => format!("{} {}", interpreter, entry),
+            None => format!("pip install -r requirements.txt\npython {}", entry),
+        };
+        files.push(ProjectFile {
+            path: "README.md".to_string(),
+            content: format!(
+                "# {}\n\nGenerated with Python Maker Bot.\n\n## Run\n\n```bash\n{}\n```\n",
+                session_name, run
+            ),
+        });
+    }
+
+    files
+}
+
+/// `requirements.txt` listing the PyPI packages of third-party imports
+/// across the project's `.py` files, excluding the project's own modules.
+fn requirements(files: &[ProjectFile]) -> String {
+    let local_modules: Vec<&str> = files
+        .iter()
+        .filter(|f| f.path.ends_with(".py"))
+        .filter_map(|f| f.path.split('/').next())
+        .map(|top| top.trim_end_matches(".py"))
+        .collect();
+
+    let deps: Vec<String> = files
+        .iter()
+        .filter(|f| f.path.ends_with(".py"))
+        .flat_map(|f| extract_imports(&f.content))
+        .filter(|pkg| !is_stdlib(pkg) && !local_modules.contains(&pkg.as_str()))
+        .collect();
+
+    if deps.is_empty() {
+        "# No third-party dependencies\n".to_string()
+    } else {
+        requirements_txt(&deps)
+    }
+}
+
+/// Zip `files` under a top-level `folder/` directory. Paths that would
+/// escape it (absolute or with `..`) are rejected.
+pub fn zip_project(folder: &str, files: &[ProjectFile]) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for file in files {
+        if !file.has_safe_path() {
+            return Err(anyhow::anyhow!("Refusing to add file outside the project to archive: {}", file.path));
+        }
+        // Keep shell scripts executable after unzipping
+        let mode = if file.path.ends_with(".sh") { 0o755 } else { 0o644 };
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .unix_permissions(mode);
+        zip.start_file(format!("{}/{}", folder, file.path), options)
+            .with_context(|| format!("Failed to add {} to archive", file.path))?;
+        zip.write_all(file.content.as_bytes())?;
+    }
+    Ok(zip.finish().context("Failed to finish archive")?.into_inner())
+}
+
+/// File-system friendly version of a session name, e.g. "Snake game!" -> "snake-game".
+pub fn slug(name: &str) -> String {
+    let slug = name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "project".to_string()
+    } else {
+        slug.chars().take(50).collect::<String>().trim_end_matches('-').to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn paths(files: &[ProjectFile]) -> Vec<&str> {
+        files.iter().map(|f| f.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_single_script_project() {
+        let code = "import requests\nimport os\nimport cv2\n\nprint(requests.__version__)";
+        let files = project_files("Fetch stuff", code, Language::Python);
+        assert_eq!(paths(&files), vec!["main.py", "requirements.txt", "README.md"]);
+        assert_eq!(files[0].content, format!("{}\n", code));
+        // Import names map to their PyPI packages
+        assert_eq!(files[1].content, "opencv-python\nrequests\n");
+        assert!(files[2].content.starts_with("# Fetch stuff"));
+        assert!(files[2].content.contains("pip install -r requirements.txt\npython main.py"));
+    }
+
+    #[test]
+    fn test_multi_file_project_excludes_local_modules() {
+        let code = "```python title=main.py\nimport helpers\nimport numpy\n```\n\n\
+                    ```python title=helpers.py\nimport pandas as pd\n```";
+        let files = project_files("Data", code, Language::Python);
+        assert_eq!(paths(&files), vec!["main.py", "helpers.py", "requirements.txt", "README.md"]);
+        assert_eq!(files[2].content, "numpy\npandas\n");
+    }
+
+    #[test]
+    fn test_bash_project_has_no_requirements() {
+        let files = project_files("Backup", "#!/bin/bash\necho hi", Language::Bash);
+        assert_eq!(paths(&files), vec!["main.sh", "README.md"]);
+        assert!(files[1].content.contains("bash main.sh"));
+    }
+
+    #[test]
+    fn test_zip_project_round_trip() {
+        let files = project_files("Hello", "print('hi')", Language::Python);
+        let bytes = zip_project("hello", &files).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 3);
+        let mut main = String::new();
+        archive.by_name("hello/main.py").unwrap().read_to_string(&mut main).unwrap();
+        assert_eq!(main, "print('hi')\n");
+        assert!(archive.by_name("hello/requirements.txt").is_ok());
+    }
+
+    #[test]
+    fn test_unsafe_paths_are_dropped_and_rejected() {
+        let code = "```python title=main.py\nprint('hi')\n```\n\n\
+                    ```python title=../../.bashrc\nimport os\n```";
+        let files = project_files("Escape", code, Language::Python);
+        assert_eq!(paths(&files), vec!["main.py", "requirements.txt", "README.md"]);
+        assert_eq!(files[0].content, "print('hi')\n");
+
+        let evil = [ProjectFile { path: "/etc/passwd".to_string(), content: String::new() }];
+        assert!(zip_project("escape", &evil).is_err());
+    }
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("Snake game!"), "snake-game");
+        assert_eq!(slug("  Été / 2024 "), "t-2024");
+        assert_eq!(slug("???"), "project");
+    }
+}
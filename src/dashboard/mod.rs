@@ -4,6 +4,9 @@
 //! code generation, multi-turn chat, code execution, model switching,
 //! lint/security tools, and session statistics.
 
+pub mod auth;
+pub mod export;
+pub mod rate_limit;
 pub mod routes;
 pub mod server;
 pub mod state;
@@ -0,0 +1,130 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::state::DashboardState;
+
+/// Maximum number of rate-limited requests being handled at the same time.
+const MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// Per-process limiter for the expensive dashboard endpoints (LLM calls and
+/// script execution): a sliding one-minute window plus a concurrency cap.
+pub struct RateLimiter {
+    max_per_window: usize,
+    window: Duration,
+    recent: Mutex<VecDeque<Instant>>,
+    concurrency: Arc<Semaphore>,
+}
+
+/// The concurrency slot of an admitted request, added to its extensions by
+/// [`rate_limit`]. Handlers that return before their work is done (202
+/// Accepted) move a clone into the background task; the slot frees up once
+/// the last clone is dropped.
+#[derive(Clone, Debug, Default)]
+pub struct RequestPermit {
+    _slot: Option<Arc<OwnedSemaphorePermit>>,
+}
+
+impl RateLimiter {
+    /// Allow `max_per_min` requests per minute (0 = unlimited).
+    pub fn new(max_per_min: u32) -> Self {
+        Self::with_limits(max_per_min as usize, Duration::from_secs(60), MAX_CONCURRENT_REQUESTS)
+    }
+
+    pub(crate) fn with_limits(max_per_window: usize, window: Duration, max_concurrent: usize) -> Self {
+        Self {
+            max_per_window,
+            window,
+            recent: Mutex::new(VecDeque::new()),
+            concurrency: Arc::new(Semaphore::new(max_concurrent)),
+        }
+    }
+
+    /// Admit one request, returning a permit to hold while it is handled,
+    /// or the reason it was rejected.
+    pub fn try_acquire(&self) -> Result<RequestPermit, String> {
+        if self.max_per_window == 0 {
+            return Ok(RequestPermit::default());
+        }
+
+        let permit = Arc::clone(&self.concurrency).try_acquire_owned().map_err(|_| {
+            "Too many requests in progress. Wait for the current ones to finish.".to_string()
+        })?;
+
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap();
+        while recent.front().is_some_and(|t| now.duration_since(*t) >= self.window) {
+            recent.pop_front();
+        }
+        if recent.len() >= self.max_per_window {
+            return Err(format!(
+                "Rate limit exceeded: at most {} requests per minute.",
+                self.max_per_window
+            ));
+        }
+        recent.push_back(now);
+        Ok(RequestPermit { _slot: Some(Arc::new(permit)) })
+    }
+}
+
+/// Middleware that answers 429 when the dashboard's rate limiter rejects a request.
+pub async fn rate_limit(
+    State(state): State<Arc<DashboardState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match state.rate_limiter.try_acquire() {
+        Ok(permit) => {
+            request.extensions_mut().insert(permit);
+            next.run(request).await
+        }
+        Err(message) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(serde_json::json!({ "status": "error", "message": message })),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_limit() {
+        let limiter = RateLimiter::with_limits(2, Duration::from_millis(50), 10);
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+        let err = limiter.try_acquire().unwrap_err();
+        assert!(err.contains("at most 2"));
+
+        // Old requests slide out of the window
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn test_concurrency_limit() {
+        let limiter = RateLimiter::with_limits(100, Duration::from_secs(60), 1);
+        let held = limiter.try_acquire().unwrap();
+        assert!(limiter.try_acquire().unwrap_err().contains("in progress"));
+        drop(held);
+        assert!(limiter.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn test_zero_disables_limit() {
+        let limiter = RateLimiter::new(0);
+        for _ in 0..100 {
+            assert!(limiter.try_acquire().is_ok());
+        }
+    }
+}
@@ -1,18 +1,28 @@
 use axum::{
-    extract::State,
+    extract::{Query, State},
     response::{Html, IntoResponse, Json},
-    Form,
+    Extension, Form,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use super::state::{ChatSession, DashboardState, ExecutionEvent, RuntimeSettings, ScriptEntry};
+use super::export;
+use super::rate_limit::RequestPermit;
+use super::state::{ChatSession, DashboardState, ExecutionEvent, RunningProcess, RuntimeSettings, ScriptEntry};
 use super::templates;
 use crate::api::{self, Message};
+use crate::config::AppConfig;
 use crate::interface::trim_history;
-use crate::utils::extract_python_code;
+use crate::logger::ModelStats;
+use crate::python_exec::{analyze_hang_risk, timed, CodeExecutionResult, ExecutionRecord, ExecutionTiming, Language};
+use crate::utils::{
+    extract_python_code, find_in_file, format_relative_time, is_generated_script_name, list_generated_scripts, resolve_within,
+    format_snippet, truncate_prompt, TempScript, SNIPPET_CONTEXT_LINES,
+};
 
 use std::io::{BufRead, BufReader, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use wait_timeout::ChildExt;
 
 // ── GET / — main dashboard page ──────────────────────────────────────
@@ -22,7 +32,10 @@ pub async fn index(State(state): State<Arc<DashboardState>>) -> impl IntoRespons
     let metrics = state.metrics.read().await;
     let sessions = state.sessions.read().await;
     let active_id = state.active_session_id.read().await;
-    let settings = state.runtime_settings.read().await;
+    let settings = sessions
+        .get(&*active_id)
+        .map(|s| s.settings.clone())
+        .unwrap_or_else(|| RuntimeSettings::from_config(&state.config));
 
     // Collect session list for the sidebar
     let mut session_list: Vec<SessionListEntry> = sessions
@@ -69,18 +82,86 @@ pub async fn index(State(state): State<Arc<DashboardState>>) -> impl IntoRespons
 
 // ── GET /api/history — JSON list of generated scripts ────────────────
 
-pub async fn get_history(State(state): State<Arc<DashboardState>>) -> impl IntoResponse {
-    let scripts = list_scripts_from_dir(&state.config.generated_dir).await;
-    Json(scripts)
+// With `?q=<term>`, only scripts whose name or content contains the term
+// (case-insensitive) are returned, each with the first matching line.
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    #[serde(default)]
+    pub q: String,
+}
+
+#[derive(Serialize)]
+pub struct ScriptSearchResult {
+    #[serde(flatten)]
+    pub script: ScriptEntry,
+    /// 1-based line of the first content match, if the content matched.
+    pub line: Option<usize>,
+    pub snippet: Option<String>,
+}
+
+pub async fn get_history(
+    State(state): State<Arc<DashboardState>>,
+    Query(query): Query<HistoryQuery>,
+) -> axum::response::Response {
+    let term = query.q.trim();
+    if term.is_empty() {
+        return Json(list_scripts_from_dir(&state.config.generated_dir).await).into_response();
+    }
+    Json(search_scripts(&state.config.generated_dir, term).await).into_response()
 }
 
 // ── GET /api/history/html — HTML partial for HTMX swap ──────────────
 
-pub async fn get_history_html(State(state): State<Arc<DashboardState>>) -> impl IntoResponse {
-    let scripts = list_scripts_from_dir(&state.config.generated_dir).await;
+pub async fn get_history_html(
+    State(state): State<Arc<DashboardState>>,
+    Query(query): Query<HistoryQuery>,
+) -> impl IntoResponse {
+    let term = query.q.trim();
+    let scripts = if term.is_empty() {
+        list_scripts_from_dir(&state.config.generated_dir).await
+    } else {
+        search_scripts(&state.config.generated_dir, term).await.into_iter().map(|r| r.script).collect()
+    };
     Html(templates::render_history(&scripts))
 }
 
+// ── DELETE /api/history/:filename — remove a generated script ───────
+
+pub async fn delete_script(
+    State(state): State<Arc<DashboardState>>,
+    axum::extract::Path(filename): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    if !is_generated_script_name(&filename) {
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "status": "error", "message": "Invalid script filename" })),
+        );
+    }
+
+    let path = std::path::Path::new(&state.config.generated_dir).join(&filename);
+    match tokio::fs::remove_file(&path).await {
+        Ok(()) => {
+            let _ = tokio::fs::remove_file(ExecutionRecord::path_for(&path)).await;
+            state.broadcast(ExecutionEvent::ScriptDeleted {
+                filename: filename.clone(),
+            });
+            (
+                axum::http::StatusCode::OK,
+                Json(serde_json::json!({ "status": "deleted", "filename": filename })),
+            )
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (
+            axum::http::StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "status": "error", "message": "Script not found" })),
+        ),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "status": "error", "message": e.to_string() })),
+        ),
+    }
+}
+
 // ── GET /api/stats — session metrics as JSON ─────────────────────────
 
 #[derive(Serialize)]
@@ -90,6 +171,11 @@ pub struct StatsResponse {
     pub failed_executions: usize,
     pub api_errors: usize,
     pub success_rate: f64,
+    pub api_call_count: usize,
+    pub avg_api_latency_ms: Option<u64>,
+    pub min_api_latency_ms: Option<u64>,
+    pub max_api_latency_ms: Option<u64>,
+    pub per_model: HashMap<String, ModelStats>,
 }
 
 pub async fn get_stats(State(state): State<Arc<DashboardState>>) -> impl IntoResponse {
@@ -100,9 +186,70 @@ pub async fn get_stats(State(state): State<Arc<DashboardState>>) -> impl IntoRes
         failed_executions: m.failed_executions,
         api_errors: m.api_errors,
         success_rate: m.success_rate(),
+        api_call_count: m.api_call_count,
+        avg_api_latency_ms: m.average_api_latency_ms(),
+        min_api_latency_ms: m.min_api_latency_ms,
+        max_api_latency_ms: m.max_api_latency_ms,
+        per_model: m.per_model.clone(),
     })
 }
 
+// ── GET /api/health — liveness probe ─────────────────────────────────
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
+    pub uptime_secs: u64,
+    pub active_sessions: usize,
+    pub provider: String,
+    pub model: String,
+}
+
+/// Report that the server is up, without contacting the LLM provider.
+pub async fn health(State(state): State<Arc<DashboardState>>) -> impl IntoResponse {
+    let settings = state.active_settings().await;
+    Json(HealthResponse {
+        status: "ok",
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        active_sessions: state.sessions.read().await.len(),
+        provider: settings.provider,
+        model: settings.model,
+    })
+}
+
+// ── GET /api/ready — readiness probe ─────────────────────────────────
+
+/// Check that the active session's provider answers (a cheap GET, no
+/// generation). Returns 503 when it is unreachable or rejects the credentials.
+///
+/// The route needs no token, so the outcome is cached for
+/// [`READY_PROBE_TTL`](super::state::READY_PROBE_TTL) and the body doesn't
+/// reveal the provider's URL.
+pub async fn ready(State(state): State<Arc<DashboardState>>) -> impl IntoResponse {
+    // Held across the probe so concurrent requests share one
+    let mut last_probe = state.last_ready_probe.lock().await;
+    let reachable = match *last_probe {
+        Some((at, reachable)) if at.elapsed() < super::state::READY_PROBE_TTL => reachable,
+        _ => {
+            let config = state.active_settings().await.to_app_config(&state.config);
+            let reachable = api::probe_provider(&config).await.is_ok();
+            *last_probe = Some((std::time::Instant::now(), reachable));
+            reachable
+        }
+    };
+    if reachable {
+        (
+            axum::http::StatusCode::OK,
+            Json(serde_json::json!({ "status": "ready", "message": "LLM provider reachable" })),
+        )
+    } else {
+        (
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "status": "unavailable", "message": "LLM provider unreachable or rejected the credentials" })),
+        )
+    }
+}
+
 // ── GET /api/stats/html — HTML partial for HTMX ─────────────────────
 
 pub async fn get_stats_html(State(state): State<Arc<DashboardState>>) -> impl IntoResponse {
@@ -113,38 +260,64 @@ pub async fn get_stats_html(State(state): State<Arc<DashboardState>>) -> impl In
         m.failed_executions,
         m.api_errors,
         m.success_rate(),
+        m.latency_summary(),
     ))
 }
 
-// ── POST /api/generate — accept prompt, call LLM, return JSON ────────
+// ── POST /api/generate — accept prompt, stream the reply over WebSocket ─
 
 #[derive(Deserialize)]
 pub struct GenerateRequest {
     pub prompt: String,
     #[serde(default)]
     pub session_id: String,
+    /// Cut a prompt longer than `max_prompt_chars` instead of rejecting it.
+    #[serde(default)]
+    pub truncate: bool,
 }
 
-/// Response returned to the chat UI after code generation.
+/// Response to `/api/generate`. The reply itself arrives over the WebSocket
+/// as `CodeToken` events, then `CodeGenerated` or `GenerationFailed`.
 #[derive(Serialize)]
-pub struct GenerateResponse {
-    pub success: bool,
-    pub code: String,
-    pub script_path: String,
-    pub error: String,
+pub struct GenerateAccepted {
+    pub status: String,
+    pub session_id: String,
+    pub message: String,
 }
 
+/// Accept a prompt and stream the LLM reply in the background.
+/// Returns 202 Accepted immediately.
 pub async fn generate_code(
     State(state): State<Arc<DashboardState>>,
-    Form(req): Form<GenerateRequest>,
+    Extension(permit): Extension<RequestPermit>,
+    Form(mut req): Form<GenerateRequest>,
 ) -> impl IntoResponse {
+    let reject = |status: axum::http::StatusCode, message: &str| {
+        (
+            status,
+            Json(GenerateAccepted {
+                status: "error".to_string(),
+                session_id: String::new(),
+                message: message.to_string(),
+            }),
+        )
+    };
+
     if req.prompt.trim().is_empty() {
-        return Json(GenerateResponse {
-            success: false,
-            code: String::new(),
-            script_path: String::new(),
-            error: "Please enter a prompt.".to_string(),
-        });
+        return reject(axum::http::StatusCode::BAD_REQUEST, "Please enter a prompt.");
+    }
+
+    // Oversized prompts fail deep in the provider call; let the page ask
+    // whether to resend with `truncate` set instead
+    let max_chars = state.config.max_prompt_chars;
+    if let Some(truncated) = truncate_prompt(&req.prompt, max_chars) {
+        if !req.truncate {
+            return reject(
+                axum::http::StatusCode::PAYLOAD_TOO_LARGE,
+                &format!("The prompt is {} characters; the limit is {} (max_prompt_chars).", req.prompt.chars().count(), max_chars),
+            );
+        }
+        req.prompt = truncated.to_string();
     }
 
     // Resolve session ID — fall back to active session if not provided
@@ -154,8 +327,8 @@ pub async fn generate_code(
         req.session_id.clone()
     };
 
-    // Add user message to session and snapshot history for the LLM call
-    let messages = {
+    // Add user message to session and snapshot history and settings for the LLM call
+    let (messages, effective_config) = {
         let mut sessions = state.sessions.write().await;
         if let Some(session) = sessions.get_mut(&session_id) {
             session.messages.push(Message {
@@ -171,106 +344,194 @@ pub async fn generate_code(
                     name
                 };
             }
-            session.messages.clone()
+            let effective_config = session.settings.to_app_config(&state.config);
+            let mut messages = session.messages.clone();
+            trim_history(&mut messages, effective_config.max_history_messages, effective_config.max_context_tokens);
+            (messages, effective_config)
         } else {
-            return Json(GenerateResponse {
-                success: false,
-                code: String::new(),
-                script_path: String::new(),
-                error: "Session not found.".to_string(),
-            });
+            return reject(axum::http::StatusCode::NOT_FOUND, "Session not found.");
         }
     };
+    state.sessions_changed();
 
-    // Build ephemeral config from runtime settings
-    let effective_config = {
-        let settings = state.runtime_settings.read().await;
-        settings.to_app_config(&state.config)
-    };
+    tokio::spawn(stream_generation(
+        Arc::clone(&state),
+        session_id.clone(),
+        req.prompt,
+        messages,
+        effective_config,
+        permit,
+    ));
 
-    // Call the LLM
-    let result = api::generate_code_with_history(&messages, &effective_config).await;
+    (
+        axum::http::StatusCode::ACCEPTED,
+        Json(GenerateAccepted {
+            status: "accepted".to_string(),
+            session_id,
+            message: String::new(),
+        }),
+    )
+}
 
-    match result {
-        Ok(raw_response) => {
-            let code = extract_python_code(&raw_response);
-
-            // Write the script to disk
-            let script_path = match state.executor.write_script(&code) {
-                Ok(p) => p.display().to_string(),
-                Err(e) => {
-                    return Json(GenerateResponse {
-                        success: false,
-                        code: String::new(),
-                        script_path: String::new(),
-                        error: format!("Error writing script: {}", e),
-                    });
-                }
-            };
+/// Background half of `generate_code`: broadcast the reply as it streams in,
+/// then save the script and update the session. Holds the request's
+/// rate-limit slot until it is done.
+async fn stream_generation(
+    state: Arc<DashboardState>,
+    session_id: String,
+    prompt: String,
+    messages: Vec<Message>,
+    effective_config: AppConfig,
+    _permit: RequestPermit,
+) {
+    let token_state = Arc::clone(&state);
+    let token_session_id = session_id.clone();
+    let (generation_id, cancel) = state.start_generation(&session_id).await;
+    let api_started = std::time::Instant::now();
+    let result = api::generate_code_streaming(&messages, &effective_config, |content| {
+        token_state.broadcast(ExecutionEvent::CodeToken {
+            session_id: token_session_id.clone(),
+            content: content.to_string(),
+        });
+    }, &cancel)
+    .await;
+    state.finish_generation(&generation_id).await;
+    state.metrics.write().await.record_api_latency(api_started.elapsed());
 
-            // Update session state
-            {
-                let mut sessions = state.sessions.write().await;
-                if let Some(session) = sessions.get_mut(&session_id) {
-                    session.messages.push(Message {
-                        role: "assistant".to_string(),
-                        content: code.clone(),
-                    });
-                    session.last_generated_code = code.clone();
-                    // Enforce history limit
-                    trim_history(&mut session.messages, effective_config.max_history_messages);
-                }
-            }
+    let fail = |error: String| {
+        state.broadcast(ExecutionEvent::GenerationFailed {
+            session_id: session_id.clone(),
+            error,
+        });
+    };
 
-            // Also update legacy flat state for REPL sync
-            {
-                let mut last = state.last_generated_code.write().await;
-                *last = code.clone();
-            }
-            {
-                let mut history = state.conversation_history.write().await;
-                history.push(Message {
-                    role: "user".to_string(),
-                    content: req.prompt.clone(),
-                });
-                history.push(Message {
-                    role: "assistant".to_string(),
-                    content: code.clone(),
-                });
-                trim_history(&mut history, effective_config.max_history_messages);
-            }
+    let raw_response = match result {
+        Ok(raw_response) => raw_response,
+        Err(e) => {
             {
                 let mut m = state.metrics.write().await;
-                m.total_requests += 1;
+                m.record_request(&effective_config.model);
+                if !matches!(e, api::ApiError::Cancelled) {
+                    m.api_errors += 1;
+                }
             }
-
-            // Broadcast event
-            state.broadcast(ExecutionEvent::CodeGenerated {
-                code: code.clone(),
-                script_path: script_path.clone(),
+            fail(match e.hint() {
+                Some(hint) => format!("{} ({})", e, hint),
+                None => e.to_string(),
             });
-
-            Json(GenerateResponse {
-                success: true,
-                code,
-                script_path,
-                error: String::new(),
-            })
+            return;
         }
+    };
+
+    let mut code = extract_python_code(&raw_response);
+    let raw_response = effective_config.verbose.then_some(raw_response);
+
+    // Write the script to disk
+    let script_path = match state.executor.write_script_for_prompt(&code, Some(&prompt)) {
+        Ok(p) => p.display().to_string(),
         Err(e) => {
-            {
-                let mut m = state.metrics.write().await;
-                m.total_requests += 1;
-                m.api_errors += 1;
-            }
-            Json(GenerateResponse {
-                success: false,
-                code: String::new(),
-                script_path: String::new(),
-                error: e.to_string(),
-            })
+            fail(format!("Error writing script: {}", e));
+            return;
+        }
+    };
+
+    // Code that fails the syntax check gets one refinement round, like the
+    // REPL's auto-refine. The exchange is kept in the chat.
+    let mut replies = vec![Message { role: "assistant".to_string(), content: code.clone() }];
+    if effective_config.dashboard_auto_refine {
+        if let Some(fix) = refine_syntax_error(&state, &session_id, &messages, &code, &script_path, &effective_config).await {
+            code = fix.last().map(|m| m.content.clone()).unwrap_or(code);
+            replies.extend(fix);
+        }
+    }
+
+    // Update session state
+    {
+        let mut sessions = state.sessions.write().await;
+        if let Some(session) = sessions.get_mut(&session_id) {
+            session.messages.extend(replies);
+            session.last_generated_code = code.clone();
+            // Enforce history limit
+            trim_history(&mut session.messages, effective_config.max_history_messages, effective_config.max_context_tokens);
+        }
+    }
+    state.sessions_changed();
+
+    // Also update legacy flat state for REPL sync
+    {
+        let mut last = state.last_generated_code.write().await;
+        *last = code.clone();
+    }
+    {
+        let mut history = state.conversation_history.write().await;
+        history.push(Message {
+            role: "user".to_string(),
+            content: prompt,
+        });
+        history.push(Message {
+            role: "assistant".to_string(),
+            content: code.clone(),
+        });
+        trim_history(&mut history, effective_config.max_history_messages, effective_config.max_context_tokens);
+    }
+    {
+        let mut m = state.metrics.write().await;
+        m.record_request(&effective_config.model);
+    }
+
+    // Broadcast event
+    state.broadcast(ExecutionEvent::CodeGenerated {
+        code,
+        script_path,
+        session_id,
+        raw_response,
+    });
+}
+
+/// Syntax-check the script at `script_path` and, if it fails, ask the model
+/// once for a fix and overwrite the script with it. Returns the fix request
+/// and the model's reply to append to the chat, or `None` when the code
+/// passed or the refinement failed (the original code is kept then).
+async fn refine_syntax_error(
+    state: &Arc<DashboardState>,
+    session_id: &str,
+    messages: &[Message],
+    code: &str,
+    script_path: &str,
+    config: &AppConfig,
+) -> Option<Vec<Message>> {
+    let check_state = Arc::clone(state);
+    let check_path = std::path::PathBuf::from(script_path);
+    let syntax_err = tokio::task::spawn_blocking(move || check_state.executor.syntax_check(&check_path))
+        .await
+        .ok()?
+        .err()?;
+
+    let fix_request = Message {
+        role: "user".to_string(),
+        content: format!("The code has a syntax error. Please fix it:\n{}", syntax_err),
+    };
+    let mut history = messages.to_vec();
+    history.push(Message { role: "assistant".to_string(), content: code.to_string() });
+    history.push(fix_request.clone());
+
+    // Still cancellable through /api/generate/cancel
+    let (generation_id, cancel) = state.start_generation(session_id).await;
+    let api_started = std::time::Instant::now();
+    let result = api::generate_code_with_history(&history, config, &cancel).await;
+    state.finish_generation(&generation_id).await;
+    {
+        let mut m = state.metrics.write().await;
+        m.record_api_latency(api_started.elapsed());
+        m.record_request(&config.model);
+        if result.as_ref().is_err_and(|e| !matches!(e, api::ApiError::Cancelled)) {
+            m.api_errors += 1;
         }
     }
+
+    let fixed = extract_python_code(&result.ok()?);
+    std::fs::write(script_path, &fixed).ok()?;
+    Some(vec![fix_request, Message { role: "assistant".to_string(), content: fixed }])
 }
 
 // ══════════════════════════════════════════════════════════════════════
@@ -286,38 +547,51 @@ pub struct ExecuteRequest {
 pub struct ExecuteAccepted {
     pub status: String,
     pub script_path: String,
+    /// Tags this execution's `LogLine` events.
+    pub run_id: String,
 }
 
 /// Accept code, spawn execution in background, stream output via WebSocket.
 /// Returns 202 Accepted immediately.
 pub async fn execute_code(
     State(state): State<Arc<DashboardState>>,
+    Extension(permit): Extension<RequestPermit>,
     Json(req): Json<ExecuteRequest>,
 ) -> impl IntoResponse {
+    if state.config.dry_run {
+        return (
+            axum::http::StatusCode::FORBIDDEN,
+            Json(ExecuteAccepted {
+                status: "dry_run".to_string(),
+                script_path: String::new(),
+                run_id: String::new(),
+            }),
+        );
+    }
     if req.code.trim().is_empty() {
         return (
             axum::http::StatusCode::BAD_REQUEST,
             Json(ExecuteAccepted {
                 status: "error".to_string(),
                 script_path: String::new(),
+                run_id: String::new(),
             }),
         );
     }
 
+    let run_id = DashboardState::new_run_id();
+
     // Write script to disk
     let script_path = match state.executor.write_script(&req.code) {
         Ok(p) => p,
         Err(e) => {
-            state.broadcast(ExecutionEvent::LogLine {
-                timestamp: now_hms(),
-                stream: "stderr".to_string(),
-                content: format!("Error writing script: {}", e),
-            });
+            state.broadcast_log(&run_id, "stderr", format!("Error writing script: {}", e));
             return (
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ExecuteAccepted {
                     status: "error".to_string(),
                     script_path: String::new(),
+                    run_id,
                 }),
             );
         }
@@ -325,22 +599,25 @@ pub async fn execute_code(
 
     let script_path_str = script_path.display().to_string();
 
-    // Read runtime settings
-    let settings = state.runtime_settings.read().await.clone();
+    // Read the active session's runtime settings
+    let settings = state.active_settings().await;
 
     // Spawn background execution task
     let execution_state = Arc::clone(&state);
     let exec_script_path = script_path.clone();
     let exec_script_path_str = script_path_str.clone();
     let code_for_deps = req.code.clone();
+    let exec_run_id = run_id.clone();
 
     tokio::task::spawn_blocking(move || {
         execute_script_with_streaming(
             execution_state,
+            &exec_run_id,
             exec_script_path,
             &exec_script_path_str,
             &code_for_deps,
             &settings,
+            permit,
         );
     });
 
@@ -349,66 +626,66 @@ pub async fn execute_code(
         Json(ExecuteAccepted {
             status: "accepted".to_string(),
             script_path: script_path_str,
+            run_id,
         }),
     )
 }
 
 /// Synchronous function that runs the full execution pipeline with real-time
-/// output streaming via broadcast events.
+/// output streaming via broadcast events. Holds the request's rate-limit
+/// slot until the script exits.
 fn execute_script_with_streaming(
     state: Arc<DashboardState>,
+    run_id: &str,
     script_path: std::path::PathBuf,
     script_path_str: &str,
     code: &str,
     settings: &RuntimeSettings,
+    _permit: RequestPermit,
 ) {
+    let mut timing = ExecutionTiming::default();
+
     // 1. Broadcast execution started
     state.broadcast(ExecutionEvent::ExecutionStarted {
+        run_id: run_id.to_string(),
         script_path: script_path_str.to_string(),
     });
 
     // 2. Syntax check
-    state.broadcast(ExecutionEvent::LogLine {
-        timestamp: now_hms(),
-        stream: "info".to_string(),
-        content: "Running syntax check...".to_string(),
-    });
+    state.broadcast_log(run_id, "info", "Running syntax check...");
 
     if let Err(e) = state.executor.syntax_check(&script_path) {
-        state.broadcast(ExecutionEvent::LogLine {
-            timestamp: now_hms(),
-            stream: "stderr".to_string(),
-            content: format!("Syntax error: {}", e),
-        });
+        state.broadcast_log(run_id, "stderr", format!("Syntax error: {}", e));
         state.broadcast(ExecutionEvent::ExecutionCompleted {
+            run_id: run_id.to_string(),
             success: false,
             exit_code: None,
+            timing,
         });
         let mut m = state.metrics.blocking_write();
         m.failed_executions += 1;
         return;
     }
 
-    state.broadcast(ExecutionEvent::LogLine {
-        timestamp: now_hms(),
-        stream: "info".to_string(),
-        content: "Syntax check passed.".to_string(),
-    });
+    state.broadcast_log(run_id, "info", "Syntax check passed.");
+
+    // Kept for the execution report
+    let mut lint_report = None;
+    let mut security_report = None;
 
     // 3. Lint check (if enabled)
-    if settings.use_linting {
-        state.broadcast(ExecutionEvent::LogLine {
-            timestamp: now_hms(),
-            stream: "info".to_string(),
-            content: "Running lint check (ruff)...".to_string(),
-        });
+    let is_python = state.executor.language() == Language::Python;
+    if settings.use_linting && is_python {
+        state.broadcast_log(run_id, "info", "Running lint check (ruff)...");
 
-        match state.executor.lint_check(&script_path) {
+        let (lint_outcome, lint_ms) = timed(|| state.executor.lint_check(&script_path));
+        timing.lint_ms = lint_ms;
+        match lint_outcome {
             Ok(lint_result) => {
                 let diag_text = lint_result
                     .diagnostics
                     .iter()
-                    .map(|d| d.message.clone())
+                    .map(|d| d.to_string())
                     .collect::<Vec<_>>()
                     .join("\n");
                 let summary = if lint_result.passed {
@@ -416,36 +693,29 @@ fn execute_script_with_streaming(
                 } else {
                     format!("Lint: {}", lint_result.summary)
                 };
-                state.broadcast(ExecutionEvent::LogLine {
-                    timestamp: now_hms(),
-                    stream: if lint_result.has_errors { "stderr" } else { "info" }.to_string(),
-                    content: summary,
-                });
+                state.broadcast_log(run_id, if lint_result.has_errors { "stderr" } else { "info" }, summary);
                 state.broadcast(ExecutionEvent::LintCompleted {
                     passed: lint_result.passed,
                     diagnostics: diag_text,
                 });
+                lint_report = Some(lint_result);
             }
             Err(e) => {
-                state.broadcast(ExecutionEvent::LogLine {
-                    timestamp: now_hms(),
-                    stream: "stderr".to_string(),
-                    content: format!("Lint check error: {}", e),
-                });
+                state.broadcast_log(run_id, "stderr", format!("Lint check error: {}", e));
             }
         }
     }
 
     // 4. Security check (if enabled)
-    if settings.use_security_check {
-        state.broadcast(ExecutionEvent::LogLine {
-            timestamp: now_hms(),
-            stream: "info".to_string(),
-            content: "Running security scan (bandit)...".to_string(),
-        });
+    if settings.use_security_check && is_python {
+        state.broadcast_log(run_id, "info", "Running security scan (bandit)...");
 
-        match state.executor.security_check(&script_path) {
+        let (security_outcome, security_ms) = timed(|| state.executor.security_check(&script_path));
+        timing.security_ms = security_ms;
+        match security_outcome {
             Ok(sec_result) => {
+                let block_level = state.config.security_block_level;
+                let blocked = sec_result.blocks_at(block_level);
                 let diag_text = sec_result
                     .diagnostics
                     .iter()
@@ -457,43 +727,32 @@ fn execute_script_with_streaming(
                 } else {
                     format!("Security: {}", sec_result.summary)
                 };
-                state.broadcast(ExecutionEvent::LogLine {
-                    timestamp: now_hms(),
-                    stream: if sec_result.has_high_severity {
-                        "stderr"
-                    } else {
-                        "info"
-                    }
-                    .to_string(),
-                    content: summary,
-                });
+                state.broadcast_log(run_id, if blocked { "stderr" } else { "info" }, summary);
                 state.broadcast(ExecutionEvent::SecurityCompleted {
                     passed: sec_result.passed,
                     diagnostics: diag_text,
                 });
 
-                // Block on HIGH severity
-                if sec_result.has_high_severity {
-                    state.broadcast(ExecutionEvent::LogLine {
-                        timestamp: now_hms(),
-                        stream: "stderr".to_string(),
-                        content: "Execution blocked: HIGH severity security finding.".to_string(),
-                    });
+                // Block on findings at or above the configured severity
+                if blocked {
+                    state.broadcast_log(run_id, "stderr", format!(
+                        "Execution blocked: security finding at or above {} severity.",
+                        block_level.threshold().unwrap_or(crate::python_exec::SecuritySeverity::High)
+                    ));
                     state.broadcast(ExecutionEvent::ExecutionCompleted {
+                        run_id: run_id.to_string(),
                         success: false,
                         exit_code: None,
+                        timing,
                     });
                     let mut m = state.metrics.blocking_write();
                     m.failed_executions += 1;
                     return;
                 }
+                security_report = Some(sec_result);
             }
             Err(e) => {
-                state.broadcast(ExecutionEvent::LogLine {
-                    timestamp: now_hms(),
-                    stream: "stderr".to_string(),
-                    content: format!("Security scan error: {}", e),
-                });
+                state.broadcast_log(run_id, "stderr", format!("Security scan error: {}", e));
             }
         }
     }
@@ -501,171 +760,157 @@ fn execute_script_with_streaming(
     // 5. Detect and install dependencies
     let deps = state.executor.detect_dependencies(code);
     if !deps.is_empty() {
-        state.broadcast(ExecutionEvent::LogLine {
-            timestamp: now_hms(),
-            stream: "info".to_string(),
-            content: format!("Detected dependencies: {}", deps.join(", ")),
-        });
+        state.broadcast_log(run_id, "info", format!("Detected dependencies: {}", deps.join(", ")));
+        if state.config.check_pypi && !state.config.offline {
+            let missing = tokio::runtime::Handle::current().block_on(api::find_missing_pypi_packages(&deps));
+            for package in missing {
+                state.broadcast_log(run_id, "stderr", format!(
+                    "Package '{}' not found on PyPI — likely a typo or a local module", package
+                ));
+            }
+        }
     }
 
     // 6. Create venv if needed
-    let venv_path = match state.executor.create_venv() {
-        Ok(vp) => vp,
+    let (venv_outcome, venv_ms) = timed(|| state.executor.prepare_venv(&deps));
+    timing.venv_ms = venv_ms;
+    let (venv_path, deps_ready) = match venv_outcome {
+        Ok(venv) => venv.unzip(),
         Err(e) => {
-            state.broadcast(ExecutionEvent::LogLine {
-                timestamp: now_hms(),
-                stream: "stderr".to_string(),
-                content: format!("Venv creation failed: {}", e),
-            });
-            None
+            state.broadcast_log(run_id, "stderr", format!("Venv creation failed: {}", e));
+            (None, None)
         }
     };
 
-    if !deps.is_empty() {
-        if let Err(e) = state
-            .executor
-            .install_packages(&deps, venv_path.as_deref())
-        {
-            state.broadcast(ExecutionEvent::LogLine {
-                timestamp: now_hms(),
-                stream: "stderr".to_string(),
-                content: format!("Dependency install failed: {}", e),
-            });
+    if !deps.is_empty() && deps_ready != Some(true) {
+        let (installed, install_ms) = timed(|| {
+            state.executor.install_packages_streaming(&deps, venv_path.as_deref(), &mut |line| {
+                state.broadcast_log(run_id, "info", line);
+            })
+        });
+        timing.install_ms = install_ms;
+        if let Err(e) = installed {
+            state.broadcast_log(run_id, "stderr", format!("Dependency install failed: {}", e));
         }
     }
 
     // 7. Execute with real-time output streaming and interactive stdin support
-    state.broadcast(ExecutionEvent::LogLine {
-        timestamp: now_hms(),
-        stream: "info".to_string(),
-        content: "Executing script...".to_string(),
-    });
+    state.broadcast_log(run_id, "info", "Executing script...");
 
     let timeout_secs = settings.execution_timeout_secs;
+    if state.executor.language() == Language::Python {
+        if let Some(reason) = analyze_hang_risk(code) {
+            state.broadcast_log(run_id, "stderr", format!(
+                "Warning: this looks like it may run forever ({}); it will be stopped after {}s.",
+                reason, timeout_secs
+            ));
+        }
+    }
 
+    if let Some(network) = state.executor.docker_network_summary(&deps) {
+        state.broadcast_log(run_id, "info", format!("Docker network: {}", network));
+    }
+
+    let run_start = std::time::Instant::now();
     match state.executor.spawn_piped(&script_path, venv_path.as_deref(), &deps) {
         Ok(mut child) => {
-            // Store PID for kill support
-            let child_pid = child.id();
-            {
-                let mut pid_lock = state.running_pid.blocking_lock();
-                *pid_lock = Some(child_pid);
-            }
-
-            // Take stdin and store it in shared state for the web input endpoint
-            let child_stdin = child.stdin.take();
-            {
-                let mut stdin_lock = state.running_stdin.blocking_lock();
-                *stdin_lock = child_stdin;
-            }
+            // Register the PID and stdin under this run for the kill and
+            // input endpoints, so concurrent runs don't clobber each other
+            state.running.blocking_lock().insert(
+                run_id.to_string(),
+                RunningProcess { pid: child.id(), stdin: child.stdin.take() },
+            );
 
             // Take stdout and stderr for line-by-line streaming
             let child_stdout = child.stdout.take();
             let child_stderr = child.stderr.take();
+            let output_limit = state.executor.output_limit();
+            let limit_hit = Arc::new(AtomicBool::new(false));
+            // Output is only kept when it goes into an execution report
+            let keep_output = state.config.write_execution_report;
 
             // Stream stdout in a separate thread
             let stdout_state = Arc::clone(&state);
+            let stdout_limit_hit = Arc::clone(&limit_hit);
+            let stdout_run_id = run_id.to_string();
             let stdout_handle = std::thread::spawn(move || {
-                if let Some(stdout) = child_stdout {
-                    let reader = BufReader::new(stdout);
-                    for line in reader.lines() {
-                        match line {
-                            Ok(text) => {
-                                stdout_state.broadcast(ExecutionEvent::LogLine {
-                                    timestamp: now_hms(),
-                                    stream: "stdout".to_string(),
-                                    content: text,
-                                });
-                            }
-                            Err(_) => break,
-                        }
-                    }
-                }
+                child_stdout
+                    .map(|stdout| stream_capped_lines(&stdout_state, &stdout_run_id, stdout, "stdout", output_limit, &stdout_limit_hit, keep_output))
+                    .unwrap_or_default()
             });
 
             // Stream stderr in a separate thread
             let stderr_state = Arc::clone(&state);
+            let stderr_limit_hit = Arc::clone(&limit_hit);
+            let stderr_run_id = run_id.to_string();
             let stderr_handle = std::thread::spawn(move || {
-                if let Some(stderr) = child_stderr {
-                    let reader = BufReader::new(stderr);
-                    for line in reader.lines() {
-                        match line {
-                            Ok(text) => {
-                                stderr_state.broadcast(ExecutionEvent::LogLine {
-                                    timestamp: now_hms(),
-                                    stream: "stderr".to_string(),
-                                    content: text,
-                                });
-                            }
-                            Err(_) => break,
-                        }
-                    }
-                }
+                child_stderr
+                    .map(|stderr| stream_capped_lines(&stderr_state, &stderr_run_id, stderr, "stderr", output_limit, &stderr_limit_hit, keep_output))
+                    .unwrap_or_default()
             });
 
-            // Wait for the child process with optional timeout
-            let exit_code = if timeout_secs > 0 {
-                let timeout = std::time::Duration::from_secs(timeout_secs);
-                match child.wait_timeout(timeout) {
-                    Ok(Some(status)) => status.code(),
+            // Wait for the child process, killing it on timeout or once the
+            // output cap is exceeded
+            let deadline = (timeout_secs > 0)
+                .then(|| std::time::Instant::now() + std::time::Duration::from_secs(timeout_secs));
+            let exit_code = loop {
+                match child.wait_timeout(std::time::Duration::from_millis(100)) {
+                    Ok(Some(status)) => break status.code(),
                     Ok(None) => {
-                        // Timed out — kill the process
-                        let _ = child.kill();
-                        let _ = child.wait();
-                        state.broadcast(ExecutionEvent::LogLine {
-                            timestamp: now_hms(),
-                            stream: "stderr".to_string(),
-                            content: format!(
+                        if limit_hit.load(Ordering::Relaxed) {
+                            let _ = child.kill();
+                            let _ = child.wait();
+                            state.broadcast_log(run_id, "stderr", format!(
+                                "Process killed after exceeding the output limit of {} bytes.",
+                                output_limit
+                            ));
+                            break None;
+                        }
+                        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+                            // Timed out — kill the process
+                            let _ = child.kill();
+                            let _ = child.wait();
+                            state.broadcast_log(run_id, "stderr", format!(
                                 "Process timed out after {} seconds.",
                                 timeout_secs
-                            ),
-                        });
-                        None
-                    }
-                    Err(e) => {
-                        state.broadcast(ExecutionEvent::LogLine {
-                            timestamp: now_hms(),
-                            stream: "stderr".to_string(),
-                            content: format!("Error waiting for process: {}", e),
-                        });
-                        None
+                            ));
+                            break None;
+                        }
                     }
-                }
-            } else {
-                // No timeout — blocking wait
-                match child.wait() {
-                    Ok(status) => status.code(),
                     Err(e) => {
-                        state.broadcast(ExecutionEvent::LogLine {
-                            timestamp: now_hms(),
-                            stream: "stderr".to_string(),
-                            content: format!("Error waiting for process: {}", e),
-                        });
-                        None
+                        state.broadcast_log(run_id, "stderr", format!("Error waiting for process: {}", e));
+                        break None;
                     }
                 }
             };
 
             // Wait for reader threads to finish
-            let _ = stdout_handle.join();
-            let _ = stderr_handle.join();
+            let stdout = stdout_handle.join().unwrap_or_default();
+            let stderr = stderr_handle.join().unwrap_or_default();
 
-            // Clear PID and stdin from state
-            {
-                let mut pid_lock = state.running_pid.blocking_lock();
-                *pid_lock = None;
-            }
-            {
-                let mut stdin_lock = state.running_stdin.blocking_lock();
-                *stdin_lock = None;
-            }
+            // Unregister the run (already gone if it was killed)
+            state.running.blocking_lock().remove(run_id);
 
             let success = exit_code == Some(0);
+            timing.run_ms = run_start.elapsed().as_millis() as u64;
+            state.broadcast_log(run_id, "info", format!("Timing: {}", timing));
             state.broadcast(ExecutionEvent::ExecutionCompleted {
+                run_id: run_id.to_string(),
                 success,
                 exit_code,
+                timing,
             });
 
+            if keep_output {
+                let result = CodeExecutionResult { script_path: script_path.clone(), stdout, stderr, exit_code, timing };
+                let mut record = ExecutionRecord::new(&result, timing, &settings.model, &settings.provider);
+                record.lint = lint_report.as_ref();
+                record.security = security_report.as_ref();
+                if let Err(e) = record.write() {
+                    state.broadcast_log(run_id, "stderr", format!("Could not write the execution report: {:#}", e));
+                }
+            }
+
             let mut m = state.metrics.blocking_write();
             if success {
                 m.successful_executions += 1;
@@ -674,14 +919,12 @@ fn execute_script_with_streaming(
             }
         }
         Err(e) => {
-            state.broadcast(ExecutionEvent::LogLine {
-                timestamp: now_hms(),
-                stream: "stderr".to_string(),
-                content: format!("Execution error: {}", e),
-            });
+            state.broadcast_log(run_id, "stderr", format!("Execution error: {}", e));
             state.broadcast(ExecutionEvent::ExecutionCompleted {
+                run_id: run_id.to_string(),
                 success: false,
                 exit_code: None,
+                timing,
             });
             let mut m = state.metrics.blocking_write();
             m.failed_executions += 1;
@@ -696,18 +939,67 @@ fn execute_script_with_streaming(
 
 // ── POST /api/execute/kill — kill running script ─────────────────────
 
+#[derive(Deserialize, Default)]
+pub struct KillExecutionRequest {
+    /// Execution to kill; all running ones when omitted.
+    pub run_id: Option<String>,
+}
+
 pub async fn kill_execution(
     State(state): State<Arc<DashboardState>>,
+    req: Option<Json<KillExecutionRequest>>,
 ) -> impl IntoResponse {
-    let mut pid_lock = state.running_pid.lock().await;
-    if let Some(pid) = pid_lock.take() {
-        let _ = std::process::Command::new("kill")
-            .args(["-9", &pid.to_string()])
-            .output();
-        state.broadcast(ExecutionEvent::ExecutionKilled);
-        Json(serde_json::json!({ "status": "killed", "pid": pid }))
+    let req = req.map(|Json(r)| r).unwrap_or_default();
+    let mut running = state.running.lock().await;
+    let run_ids: Vec<String> = running
+        .keys()
+        .filter(|id| req.run_id.as_ref().is_none_or(|wanted| wanted == *id))
+        .cloned()
+        .collect();
+    if run_ids.is_empty() {
+        return Json(serde_json::json!({ "status": "no_process" }));
+    }
+    for run_id in &run_ids {
+        if let Some(process) = running.remove(run_id) {
+            let _ = std::process::Command::new("kill")
+                .args(["-9", &process.pid.to_string()])
+                .output();
+            state.broadcast(ExecutionEvent::ExecutionKilled { run_id: run_id.clone() });
+        }
+    }
+    Json(serde_json::json!({ "status": "killed", "run_ids": run_ids }))
+}
+
+// ── POST /api/generate/cancel — abort in-flight LLM requests ─────────
+
+#[derive(Deserialize, Default)]
+pub struct CancelGenerationRequest {
+    /// Chat whose generation to cancel; all of them when omitted.
+    pub session_id: Option<String>,
+}
+
+/// Cancel a streaming generation. The chat gets a `GenerationFailed` event
+/// with "Generation cancelled".
+pub async fn cancel_generation(
+    State(state): State<Arc<DashboardState>>,
+    req: Option<Json<CancelGenerationRequest>>,
+) -> impl IntoResponse {
+    let req = req.map(|Json(r)| r).unwrap_or_default();
+    let generations = state.generations.lock().await;
+    let mut cancelled: Vec<&String> = generations
+        .values()
+        .filter(|g| req.session_id.as_ref().is_none_or(|wanted| *wanted == g.session_id))
+        .map(|g| {
+            g.cancel.cancel();
+            &g.session_id
+        })
+        .collect();
+    cancelled.sort();
+    cancelled.dedup();
+    if cancelled.is_empty() {
+        Json(serde_json::json!({ "status": "no_generation" }))
     } else {
-        Json(serde_json::json!({ "status": "no_process" }))
+        Json(serde_json::json!({ "status": "cancelled", "session_ids": cancelled }))
     }
 }
 
@@ -715,26 +1007,24 @@ pub async fn kill_execution(
 
 #[derive(Deserialize)]
 pub struct SendInputRequest {
+    /// Execution whose stdin receives the input, as returned by `/api/execute`.
+    pub run_id: String,
     pub input: String,
 }
 
-/// Write a line of text to the stdin of the currently running script.
+/// Write a line of text to the stdin of a running script.
 pub async fn send_input(
     State(state): State<Arc<DashboardState>>,
     Json(req): Json<SendInputRequest>,
 ) -> impl IntoResponse {
-    let mut stdin_lock = state.running_stdin.lock().await;
-    if let Some(ref mut stdin) = *stdin_lock {
+    let mut running = state.running.lock().await;
+    if let Some(stdin) = running.get_mut(&req.run_id).and_then(|p| p.stdin.as_mut()) {
         let line = format!("{}\n", req.input);
         match stdin.write_all(line.as_bytes()) {
             Ok(()) => {
                 let _ = stdin.flush();
                 // Echo the input in the output panel so the user sees it
-                state.broadcast(ExecutionEvent::LogLine {
-                    timestamp: now_hms(),
-                    stream: "stdin".to_string(),
-                    content: req.input.clone(),
-                });
+                state.broadcast_log(&req.run_id, "stdin", req.input.clone());
                 Json(serde_json::json!({ "status": "sent" }))
             }
             Err(e) => {
@@ -767,6 +1057,11 @@ pub struct LintApiResponse {
 pub struct LintDiagnosticView {
     pub message: String,
     pub severity: String,
+    pub line: u32,
+    pub column: u32,
+    pub code: String,
+    /// The code around `line`, the reported line marked with `>`.
+    pub snippet: Vec<String>,
 }
 
 pub async fn lint_code(
@@ -774,15 +1069,12 @@ pub async fn lint_code(
     Json(req): Json<CodePayload>,
 ) -> impl IntoResponse {
     let code = req.code.clone();
-    let base_dir = state.executor.base_dir().to_path_buf();
+    let lint_options = state.executor.lint_options().clone();
 
     let result = tokio::task::spawn_blocking(move || {
-        let tmp_name = format!("_lint_check_{}.py", std::process::id());
-        let tmp_path = base_dir.join(tmp_name);
-        std::fs::write(&tmp_path, &code).map_err(|e| e.to_string())?;
-        let r = crate::python_exec::CodeExecutor::lint_check_static(&tmp_path);
-        let _ = std::fs::remove_file(&tmp_path);
-        r.map_err(|e| e.to_string())
+        let tmp = TempScript::new("_lint_check", &code).map_err(|e| e.to_string())?;
+        crate::python_exec::CodeExecutor::lint_check_static(tmp.path(), &lint_options)
+            .map_err(|e| e.to_string())
     })
     .await;
 
@@ -799,6 +1091,10 @@ pub async fn lint_code(
                         crate::python_exec::LintSeverity::Error => "error".to_string(),
                         crate::python_exec::LintSeverity::Warning => "warning".to_string(),
                     },
+                    line: d.line,
+                    column: d.column,
+                    code: d.code.clone(),
+                    snippet: format_snippet(&req.code, d.line as usize, SNIPPET_CONTEXT_LINES),
                 })
                 .collect(),
             summary: lint_result.summary,
@@ -809,6 +1105,10 @@ pub async fn lint_code(
             diagnostics: vec![LintDiagnosticView {
                 message: "Lint check failed to run".to_string(),
                 severity: "error".to_string(),
+                line: 0,
+                column: 0,
+                code: String::new(),
+                snippet: Vec::new(),
             }],
             summary: "Lint check failed".to_string(),
         }),
@@ -828,6 +1128,13 @@ pub struct SecurityDiagnosticView {
     pub message: String,
     pub severity: String,
     pub confidence: String,
+    /// e.g. "CWE-78"
+    pub cwe: Option<String>,
+    pub cwe_link: Option<String>,
+    pub more_info: Option<String>,
+    pub line: u32,
+    /// The code around `line`, the reported line marked with `>`.
+    pub snippet: Vec<String>,
 }
 
 pub async fn security_check_code(
@@ -835,15 +1142,12 @@ pub async fn security_check_code(
     Json(req): Json<CodePayload>,
 ) -> impl IntoResponse {
     let code = req.code.clone();
-    let base_dir = state.executor.base_dir().to_path_buf();
+    let security_options = state.executor.security_options().clone();
 
     let result = tokio::task::spawn_blocking(move || {
-        let tmp_name = format!("_security_check_{}.py", std::process::id());
-        let tmp_path = base_dir.join(tmp_name);
-        std::fs::write(&tmp_path, &code).map_err(|e| e.to_string())?;
-        let r = crate::python_exec::CodeExecutor::security_check_static(&tmp_path);
-        let _ = std::fs::remove_file(&tmp_path);
-        r.map_err(|e| e.to_string())
+        let tmp = TempScript::new("_security_check", &code).map_err(|e| e.to_string())?;
+        crate::python_exec::CodeExecutor::security_check_static(tmp.path(), &security_options)
+            .map_err(|e| e.to_string())
     })
     .await;
 
@@ -858,6 +1162,11 @@ pub async fn security_check_code(
                     message: d.message.clone(),
                     severity: d.severity.to_string(),
                     confidence: d.confidence.to_string(),
+                    cwe: d.cwe.map(|id| format!("CWE-{}", id)),
+                    cwe_link: d.cwe_link(),
+                    more_info: d.more_info.clone(),
+                    line: d.line_number,
+                    snippet: format_snippet(&req.code, d.line_number as usize, SNIPPET_CONTEXT_LINES),
                 })
                 .collect(),
             summary: sec_result.summary,
@@ -869,12 +1178,143 @@ pub async fn security_check_code(
                 message: "Security check failed to run".to_string(),
                 severity: "error".to_string(),
                 confidence: "N/A".to_string(),
+                cwe: None,
+                cwe_link: None,
+                more_info: None,
+                line: 0,
+                snippet: Vec::new(),
             }],
             summary: "Security check failed".to_string(),
         }),
     }
 }
 
+// ══════════════════════════════════════════════════════════════════════
+//  Tests (pytest)
+// ══════════════════════════════════════════════════════════════════════
+
+#[derive(Deserialize)]
+pub struct TestRequest {
+    /// Code under test. When `test_code` is omitted it must contain the tests itself.
+    pub code: String,
+    /// Optional pytest module; it can import the code under test as `solution`.
+    #[serde(default)]
+    pub test_code: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TestApiResponse {
+    pub success: bool,
+    pub passed: u32,
+    pub failed: u32,
+    pub errors: u32,
+    pub output: String,
+}
+
+/// Scan every test file with bandit like an execution would, refusing to
+/// run them when a finding reaches `security_block_level`.
+fn security_gate_tests(state: &DashboardState, settings: &RuntimeSettings, test_dir: &std::path::Path) -> Result<(), String> {
+    if !settings.use_security_check || state.executor.language() != Language::Python {
+        return Ok(());
+    }
+    let block_level = state.config.security_block_level;
+    for entry in std::fs::read_dir(test_dir).map_err(|e| e.to_string())?.filter_map(|e| e.ok()) {
+        let Ok(result) = state.executor.security_check(&entry.path()) else {
+            continue;
+        };
+        if result.blocks_at(block_level) {
+            return Err(format!(
+                "Execution blocked: security finding at or above {} severity.\n{}",
+                block_level.threshold().unwrap_or(crate::python_exec::SecuritySeverity::High),
+                result.summary
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub async fn run_tests(
+    State(state): State<Arc<DashboardState>>,
+    Json(req): Json<TestRequest>,
+) -> impl IntoResponse {
+    let failure = |output: String| TestApiResponse {
+        success: false,
+        passed: 0,
+        failed: 0,
+        errors: 0,
+        output,
+    };
+
+    if state.config.dry_run {
+        return (
+            axum::http::StatusCode::FORBIDDEN,
+            Json(failure("Dry run mode: code execution is disabled".to_string())),
+        );
+    }
+    if req.code.trim().is_empty() {
+        return (axum::http::StatusCode::BAD_REQUEST, Json(failure("No code provided".to_string())));
+    }
+    // With Docker, pytest runs inside the sandbox image instead
+    if !state.executor.uses_docker() && !crate::python_exec::CodeExecutor::check_pytest_available() {
+        return (
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            Json(failure("pytest is not installed (pip install pytest)".to_string())),
+        );
+    }
+
+    let run_id = DashboardState::new_run_id();
+    state.broadcast_log(&run_id, "info", "Running tests (pytest)...");
+
+    let settings = state.active_settings().await;
+    let timeout_secs = settings.execution_timeout_secs;
+    let worker_state = state.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        // Each run gets its own directory so the test module can import `solution`
+        let test_dir = worker_state
+            .executor
+            .base_dir()
+            .join(format!("_pytest_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&test_dir).map_err(|e| e.to_string())?;
+        let written = match req.test_code {
+            Some(ref tests) => std::fs::write(test_dir.join("solution.py"), &req.code)
+                .and_then(|_| std::fs::write(test_dir.join("test_solution.py"), tests)),
+            None => std::fs::write(test_dir.join("test_generated.py"), &req.code),
+        };
+        let r = written
+            .map_err(|e| e.to_string())
+            .and_then(|_| security_gate_tests(&worker_state, &settings, &test_dir))
+            .and_then(|_| worker_state.executor.run_tests(&test_dir, timeout_secs).map_err(|e| e.to_string()));
+        let _ = std::fs::remove_dir_all(&test_dir);
+        r
+    })
+    .await;
+
+    match result {
+        Ok(Ok(test_result)) => {
+            for line in test_result.output.lines() {
+                state.broadcast_log(&run_id, "stdout", line.to_string());
+            }
+            state.broadcast(ExecutionEvent::TestCompleted {
+                passed: test_result.passed,
+                failed: test_result.failed,
+                errors: test_result.errors,
+            });
+            (
+                axum::http::StatusCode::OK,
+                Json(TestApiResponse {
+                    success: test_result.is_success(),
+                    passed: test_result.passed,
+                    failed: test_result.failed,
+                    errors: test_result.errors,
+                    output: test_result.output,
+                }),
+            )
+        }
+        Ok(Err(e)) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, Json(failure(format!("Test run failed: {}", e)))),
+        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, Json(failure(format!("Test run failed: {}", e)))),
+    }
+}
+
 // ══════════════════════════════════════════════════════════════════════
 //  Session Management
 // ══════════════════════════════════════════════════════════════════════
@@ -916,20 +1356,12 @@ pub async fn list_sessions(
     Json(list)
 }
 
-/// POST /api/sessions — create a new session
+/// POST /api/sessions — create a new session, starting from the active session's settings
 pub async fn create_session(
     State(state): State<Arc<DashboardState>>,
 ) -> impl IntoResponse {
-    let new_id = uuid::Uuid::new_v4().to_string();
-    let session = ChatSession {
-        id: new_id.clone(),
-        name: "New Chat".to_string(),
-        messages: Vec::new(),
-        last_generated_code: String::new(),
-        created_at: chrono::Local::now()
-            .format("%Y-%m-%d %H:%M:%S")
-            .to_string(),
-    };
+    let session = ChatSession::new("New Chat", state.active_settings().await);
+    let new_id = session.id.clone();
 
     {
         let mut sessions = state.sessions.write().await;
@@ -939,6 +1371,7 @@ pub async fn create_session(
         let mut active = state.active_session_id.write().await;
         *active = new_id.clone();
     }
+    state.sessions_changed();
 
     Json(serde_json::json!({ "id": new_id, "status": "created" }))
 }
@@ -965,6 +1398,7 @@ pub async fn delete_session(
             *active = next_id.clone();
         }
     }
+    state.sessions_changed();
 
     Json(serde_json::json!({ "status": "deleted" }))
 }
@@ -982,12 +1416,153 @@ pub async fn get_session(
             "messages": session.messages,
             "last_generated_code": session.last_generated_code,
             "created_at": session.created_at,
+            "settings": session.settings,
         }))
     } else {
         Json(serde_json::json!({ "error": "Session not found" }))
     }
 }
 
+/// GET /api/sessions/:id/export — download the session's code as a project zip
+pub async fn export_session(
+    State(state): State<Arc<DashboardState>>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> axum::response::Response {
+    let error = |status: axum::http::StatusCode, message: String| {
+        (status, Json(serde_json::json!({ "status": "error", "message": message }))).into_response()
+    };
+
+    let (name, code) = {
+        let sessions = state.sessions.read().await;
+        match sessions.get(&id) {
+            Some(s) => (s.name.clone(), s.last_generated_code.clone()),
+            None => return error(axum::http::StatusCode::NOT_FOUND, "Session not found".to_string()),
+        }
+    };
+    if code.trim().is_empty() {
+        return error(
+            axum::http::StatusCode::BAD_REQUEST,
+            "This session has no generated code to export yet".to_string(),
+        );
+    }
+
+    let folder = export::slug(&name);
+    let files = export::project_files(&name, &code, state.config.language);
+    match export::zip_project(&folder, &files) {
+        Ok(bytes) => (
+            [
+                (axum::http::header::CONTENT_TYPE, "application/zip".to_string()),
+                (
+                    axum::http::header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.zip\"", folder),
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => error(axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Export failed: {}", e)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RenameSessionRequest {
+    pub name: String,
+}
+
+/// PATCH /api/sessions/:id — rename a session
+pub async fn rename_session(
+    State(state): State<Arc<DashboardState>>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    Json(req): Json<RenameSessionRequest>,
+) -> impl IntoResponse {
+    let name = match ChatSession::validate_name(&req.name) {
+        Ok(name) => name,
+        Err(message) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "status": "error", "message": message })),
+            );
+        }
+    };
+
+    {
+        let mut sessions = state.sessions.write().await;
+        match sessions.get_mut(&id) {
+            Some(session) => session.name = name.clone(),
+            None => {
+                return (
+                    axum::http::StatusCode::NOT_FOUND,
+                    Json(serde_json::json!({ "status": "error", "message": "Session not found" })),
+                );
+            }
+        }
+    }
+    state.sessions_changed();
+
+    state.broadcast(ExecutionEvent::SessionRenamed {
+        session_id: id,
+        name: name.clone(),
+    });
+    (
+        axum::http::StatusCode::OK,
+        Json(serde_json::json!({ "status": "renamed", "name": name })),
+    )
+}
+
+/// POST /api/sessions/:id/load/:filename — load a generated script into a
+/// chat as the assistant's latest reply, so it can be refined from there
+pub async fn load_script_into_session(
+    State(state): State<Arc<DashboardState>>,
+    axum::extract::Path((id, filename)): axum::extract::Path<(String, String)>,
+) -> impl IntoResponse {
+    // Only bare generated-script names, resolved inside the generated directory
+    let path = is_generated_script_name(&filename)
+        .then(|| resolve_within(std::path::Path::new(&state.config.generated_dir), &filename))
+        .flatten();
+    let code = match path {
+        Some(path) => tokio::fs::read_to_string(path).await.ok(),
+        None => None,
+    };
+    let Some(code) = code else {
+        return (
+            axum::http::StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "status": "error", "message": "Script not found" })),
+        );
+    };
+
+    {
+        let mut sessions = state.sessions.write().await;
+        let Some(session) = sessions.get_mut(&id) else {
+            return (
+                axum::http::StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "status": "error", "message": "Session not found" })),
+            );
+        };
+        // Keep user/assistant turns alternating for the next refine
+        session.messages.push(Message {
+            role: "user".to_string(),
+            content: format!("Loaded {}", filename),
+        });
+        session.messages.push(Message {
+            role: "assistant".to_string(),
+            content: code.clone(),
+        });
+        trim_history(&mut session.messages, state.config.max_history_messages, state.config.max_context_tokens);
+        session.last_generated_code = code.clone();
+    }
+    state.sessions_changed();
+
+    state.broadcast(ExecutionEvent::ScriptLoaded {
+        session_id: id,
+        filename: filename.clone(),
+        code,
+    });
+    (
+        axum::http::StatusCode::OK,
+        Json(serde_json::json!({ "status": "loaded", "filename": filename })),
+    )
+}
+
 /// PUT /api/sessions/:id/active — set session as active
 pub async fn set_active_session(
     State(state): State<Arc<DashboardState>>,
@@ -996,8 +1571,8 @@ pub async fn set_active_session(
     let sessions = state.sessions.read().await;
     if sessions.contains_key(&id) {
         drop(sessions);
-        let mut active = state.active_session_id.write().await;
-        *active = id.clone();
+        *state.active_session_id.write().await = id.clone();
+        state.sessions_changed();
         Json(serde_json::json!({ "status": "ok", "active_session": id }))
     } else {
         Json(serde_json::json!({ "status": "error", "message": "Session not found" }))
@@ -1027,25 +1602,18 @@ pub struct ProviderModels {
 pub async fn get_models(
     State(state): State<Arc<DashboardState>>,
 ) -> impl IntoResponse {
-    let settings = state.runtime_settings.read().await;
-    let current_provider = settings.provider.clone();
-    let current_model = settings.model.clone();
-    drop(settings);
-
-    // Fetch live model lists from HF and Ollama in parallel
-    let (hf_models, ollama_models) =
-        tokio::join!(fetch_hf_models(), fetch_ollama_models());
-
-    let openai_models = vec![
-        "gpt-4o".to_string(),
-        "gpt-4o-mini".to_string(),
-        "gpt-4-turbo".to_string(),
-        "gpt-3.5-turbo".to_string(),
-        "o3-mini".to_string(),
-        "claude-3-5-sonnet-20241022".to_string(),
-        "deepseek-chat".to_string(),
-        "deepseek-coder".to_string(),
-    ];
+    let settings = state.active_settings().await;
+    let current_provider = settings.provider;
+    let current_model = settings.model;
+
+    // Fetch live model lists from HF and Ollama in parallel (offline, the
+    // HuggingFace list is the curated one)
+    let offline = state.config.offline;
+    let (hf_models, ollama_models, openai_models) = tokio::join!(
+        api::list_models(api::Provider::HuggingFace, offline),
+        api::list_models(api::Provider::Ollama, offline),
+        api::list_models(api::Provider::OpenAiCompatible, offline),
+    );
 
     Json(ModelsResponse {
         providers: vec![
@@ -1070,131 +1638,32 @@ pub async fn get_models(
     })
 }
 
-async fn fetch_ollama_models() -> Vec<String> {
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(3))
-        .build()
-        .unwrap_or_default();
-
-    match client
-        .get("http://localhost:11434/api/tags")
-        .send()
-        .await
-    {
-        Ok(resp) if resp.status().is_success() => {
-            if let Ok(body) = resp.json::<serde_json::Value>().await {
-                if let Some(models) = body["models"].as_array() {
-                    let mut names: Vec<String> = models
-                        .iter()
-                        .filter_map(|m| m["name"].as_str().map(|s| s.to_string()))
-                        .collect();
-                    if !names.is_empty() {
-                        names.sort();
-                        return names;
-                    }
-                }
-            }
-            curated_ollama_models()
-        }
-        _ => curated_ollama_models(),
-    }
-}
-
-fn curated_ollama_models() -> Vec<String> {
-    vec![
-        "qwen2.5-coder:32b".to_string(),
-        "qwen2.5-coder:14b".to_string(),
-        "qwen2.5-coder:7b".to_string(),
-        "codellama:13b".to_string(),
-        "codellama:7b".to_string(),
-        "deepseek-coder-v2:16b".to_string(),
-        "deepseek-coder:6.7b".to_string(),
-        "llama3.3:70b".to_string(),
-        "mistral:7b".to_string(),
-    ]
-}
-
-/// Fetch the live model list from HuggingFace's /v1/models endpoint.
-/// Falls back to a small curated list if the request fails.
-async fn fetch_hf_models() -> Vec<String> {
-    let token = std::env::var("HF_TOKEN").unwrap_or_default();
-    if token.is_empty() {
-        return curated_hf_models();
-    }
-
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .unwrap_or_default();
-
-    match client
-        .get("https://router.huggingface.co/v1/models")
-        .header("Authorization", format!("Bearer {}", token))
-        .send()
-        .await
-    {
-        Ok(resp) if resp.status().is_success() => {
-            if let Ok(body) = resp.json::<serde_json::Value>().await {
-                if let Some(models) = body["data"].as_array() {
-                    let mut names: Vec<String> = models
-                        .iter()
-                        .filter_map(|m| m["id"].as_str().map(|s| s.to_string()))
-                        .collect();
-                    if !names.is_empty() {
-                        // Sort: put coding-oriented models first, then alphabetical
-                        names.sort_by(|a, b| {
-                            let a_code = a.to_lowercase().contains("coder")
-                                || a.to_lowercase().contains("code");
-                            let b_code = b.to_lowercase().contains("coder")
-                                || b.to_lowercase().contains("code");
-                            match (a_code, b_code) {
-                                (true, false) => std::cmp::Ordering::Less,
-                                (false, true) => std::cmp::Ordering::Greater,
-                                _ => a.cmp(b),
-                            }
-                        });
-                        return names;
-                    }
-                }
-            }
-            curated_hf_models()
-        }
-        _ => curated_hf_models(),
-    }
-}
-
-/// Fallback HF model list when the API is unreachable or token is missing.
-fn curated_hf_models() -> Vec<String> {
-    vec![
-        "Qwen/Qwen2.5-Coder-32B-Instruct".to_string(),
-        "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
-        "meta-llama/Llama-3.3-70B-Instruct".to_string(),
-        "meta-llama/Llama-3.1-8B-Instruct".to_string(),
-        "deepseek-ai/DeepSeek-R1".to_string(),
-        "Qwen/Qwen3-32B".to_string(),
-    ]
-}
-
 // ══════════════════════════════════════════════════════════════════════
 //  Runtime Settings
 // ══════════════════════════════════════════════════════════════════════
 
-/// GET /api/settings — return current runtime settings
+/// GET /api/settings — return the active session's runtime settings
 pub async fn get_settings(
     State(state): State<Arc<DashboardState>>,
 ) -> impl IntoResponse {
-    let settings = state.runtime_settings.read().await;
-    Json(settings.clone())
+    Json(state.active_settings().await)
 }
 
-/// POST /api/settings — update runtime settings
+/// POST /api/settings — update the active session's runtime settings
 pub async fn update_settings(
     State(state): State<Arc<DashboardState>>,
     Json(new_settings): Json<RuntimeSettings>,
 ) -> impl IntoResponse {
-    let mut settings = state.runtime_settings.write().await;
-    *settings = new_settings;
-    Json(serde_json::json!({ "status": "ok" }))
+    let active_id = state.active_session_id.read().await.clone();
+    let mut sessions = state.sessions.write().await;
+    match sessions.get_mut(&active_id) {
+        Some(session) => {
+            session.settings = new_settings;
+            state.sessions_changed();
+            Json(serde_json::json!({ "status": "ok" }))
+        }
+        None => Json(serde_json::json!({ "status": "error", "message": "Session not found" })),
+    }
 }
 
 // ══════════════════════════════════════════════════════════════════════
@@ -1225,13 +1694,64 @@ pub async fn view_code(
     State(state): State<Arc<DashboardState>>,
     axum::extract::Path(filename): axum::extract::Path<String>,
 ) -> impl IntoResponse {
-    let path = std::path::Path::new(&state.config.generated_dir).join(&filename);
-    match std::fs::read_to_string(&path) {
-        Ok(code) => Html(templates::render_code_block(&code)),
-        Err(_) => Html(format!(
-            "<p class=\"text-red-400\">File not found: {}</p>",
-            html_escape(&filename)
-        )),
+    // Reject anything that resolves outside the generated directory
+    let code = resolve_within(std::path::Path::new(&state.config.generated_dir), &filename)
+        .and_then(|path| std::fs::read_to_string(path).ok());
+    match code {
+        Some(code) => (axum::http::StatusCode::OK, Html(templates::render_code_block(&code))),
+        None => (
+            axum::http::StatusCode::NOT_FOUND,
+            Html(format!(
+                "<p class=\"text-red-400\">File not found: {}</p>",
+                html_escape(&filename)
+            )),
+        ),
+    }
+}
+
+/// A generated script's source and metadata, for `GET /api/scripts/:filename`.
+#[derive(Serialize)]
+pub struct ScriptSource {
+    pub filename: String,
+    pub content: String,
+    pub size: u64,
+    /// Last modification time, RFC 3339
+    pub modified: String,
+    /// Non-standard imports, as the executor would install them
+    pub dependencies: Vec<String>,
+}
+
+/// GET /api/scripts/:filename — a generated script's source and metadata as JSON
+pub async fn get_script(
+    State(state): State<Arc<DashboardState>>,
+    axum::extract::Path(filename): axum::extract::Path<String>,
+) -> axum::response::Response {
+    // Only bare generated-script names, resolved inside the generated directory
+    let path = is_generated_script_name(&filename)
+        .then(|| resolve_within(std::path::Path::new(&state.config.generated_dir), &filename))
+        .flatten();
+    let Some(path) = path else {
+        return (
+            axum::http::StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "status": "error", "message": "Script not found" })),
+        )
+            .into_response();
+    };
+    let read = async { Ok::<_, std::io::Error>((tokio::fs::read_to_string(&path).await?, tokio::fs::metadata(&path).await?)) };
+    match read.await {
+        Ok((content, metadata)) => {
+            let modified = metadata
+                .modified()
+                .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+                .unwrap_or_default();
+            let dependencies = state.executor.detect_dependencies(&content);
+            Json(ScriptSource { filename, content, size: metadata.len(), modified, dependencies }).into_response()
+        }
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "status": "error", "message": e.to_string() })),
+        )
+            .into_response(),
     }
 }
 
@@ -1259,31 +1779,49 @@ async fn list_scripts_from_dir(dir: &str) -> Vec<ScriptEntry> {
 }
 
 fn list_scripts_from_dir_sync(dir: &str) -> Vec<ScriptEntry> {
-    let Ok(entries) = std::fs::read_dir(dir) else {
-        return Vec::new();
-    };
-
-    let mut scripts: Vec<ScriptEntry> = entries
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().is_some_and(|ext| ext == "py"))
-        .map(|e| {
-            let filename = e.file_name().to_string_lossy().to_string();
-            let path = e.path().display().to_string();
-            let timestamp = filename
-                .strip_prefix("script_")
-                .and_then(|s| s.strip_suffix(".py"))
-                .unwrap_or(&filename)
-                .to_string();
-            ScriptEntry {
+    let dir = std::path::Path::new(dir);
+    let now = chrono::Utc::now();
+    let mut scripts: Vec<(chrono::DateTime<chrono::Utc>, ScriptEntry)> = list_generated_scripts(dir)
+        .into_iter()
+        .filter_map(|(filename, created)| {
+            let path = dir.join(&filename);
+            let metadata = std::fs::metadata(&path).ok()?;
+            let modified = metadata.modified().map(chrono::DateTime::from).unwrap_or(created);
+            Some((modified, ScriptEntry {
+                path: path.display().to_string(),
+                timestamp: created.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string(),
+                size_bytes: metadata.len(),
+                modified: format_relative_time(modified, now),
                 filename,
-                path,
-                timestamp,
-            }
+            }))
         })
         .collect();
 
-    scripts.sort_by(|a, b| b.filename.cmp(&a.filename));
-    scripts
+    // Most recently modified first, so a refined script moves back to the top
+    scripts.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    scripts.into_iter().map(|(_, entry)| entry).collect()
+}
+
+async fn search_scripts(dir: &str, term: &str) -> Vec<ScriptSearchResult> {
+    let (dir, term) = (dir.to_string(), term.to_string());
+    tokio::task::spawn_blocking(move || search_scripts_sync(&dir, &term))
+        .await
+        .unwrap_or_default()
+}
+
+fn search_scripts_sync(dir: &str, term: &str) -> Vec<ScriptSearchResult> {
+    let needle = term.to_lowercase();
+    list_scripts_from_dir_sync(dir)
+        .into_iter()
+        .filter_map(|script| {
+            let hit = find_in_file(std::path::Path::new(&script.path), term);
+            if hit.is_none() && !script.filename.to_lowercase().contains(&needle) {
+                return None;
+            }
+            let (line, snippet) = hit.unzip();
+            Some(ScriptSearchResult { script, line, snippet })
+        })
+        .collect()
 }
 
 async fn list_docker_containers() -> Vec<ContainerInfo> {
@@ -1329,6 +1867,43 @@ fn list_docker_containers_sync() -> Vec<ContainerInfo> {
         .collect()
 }
 
+/// Broadcast each line read from `pipe` as a `LogLine` on `stream`, keeping
+/// at most `limit` bytes (`0` = unlimited). Once the cap is exceeded the rest
+/// is drained silently, `limit_hit` is raised, and a truncation marker is sent.
+/// With `keep`, the lines sent are also returned.
+fn stream_capped_lines<R: std::io::Read>(
+    state: &DashboardState,
+    run_id: &str,
+    pipe: R,
+    stream: &str,
+    limit: usize,
+    limit_hit: &AtomicBool,
+    keep: bool,
+) -> String {
+    let mut kept = String::new();
+    let mut sent = 0usize;
+    let mut dropped = 0usize;
+    for line in BufReader::new(pipe).lines() {
+        let Ok(text) = line else { break };
+        let size = text.len() + 1;
+        if limit > 0 && sent + size > limit {
+            dropped += size;
+            limit_hit.store(true, Ordering::Relaxed);
+            continue;
+        }
+        sent += size;
+        if keep {
+            kept.push_str(&text);
+            kept.push('\n');
+        }
+        state.broadcast_log(run_id, stream, text);
+    }
+    if dropped > 0 {
+        state.broadcast_log(run_id, stream, format!("... [output truncated, {} bytes dropped]", dropped));
+    }
+    kept
+}
+
 fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -1336,6 +1911,136 @@ fn html_escape(s: &str) -> String {
         .replace('"', "&quot;")
 }
 
-fn now_hms() -> String {
-    chrono::Local::now().format("%H:%M:%S").to_string()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+
+    fn test_state() -> (Arc<DashboardState>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("pymakebot_routes_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = AppConfig { generated_dir: dir.display().to_string(), ..AppConfig::default() };
+        let executor = crate::python_exec::CodeExecutor::new(dir.to_str().unwrap(), false, false, "python3").unwrap();
+        (DashboardState::new(config, executor), dir)
+    }
+
+    #[tokio::test]
+    async fn test_syntax_error_is_refined_once() {
+        let (state, dir) = test_state();
+        let mut server = mockito::Server::new_async().await;
+        let reply = |code: &str| {
+            serde_json::json!({ "choices": [{ "message": { "role": "assistant", "content": format!("```python\n{}\n```", code) } }] })
+                .to_string()
+        };
+        // Served in order: the broken generation, then exactly one fix
+        let broken = server.mock("POST", "/v1/chat/completions").with_body(reply("def main(:\n    print('hi')")).expect(1).create_async().await;
+        let fixed = server
+            .mock("POST", "/v1/chat/completions")
+            .match_body(mockito::Matcher::Regex("Please fix it".to_string()))
+            .with_body(reply("def main():\n    print('hi')"))
+            .expect(1)
+            .create_async()
+            .await;
+        let config = AppConfig {
+            provider: "openai-compatible".to_string(),
+            api_url: format!("{}/v1/chat/completions", server.url()),
+            max_retries: 0,
+            ..state.config.clone()
+        };
+        let id = state.active_session_id.read().await.clone();
+        let prompt = "say hi".to_string();
+        let messages = vec![Message { role: "user".to_string(), content: prompt.clone() }];
+
+        stream_generation(state.clone(), id.clone(), prompt, messages, config, RequestPermit::default()).await;
+        broken.assert_async().await;
+        fixed.assert_async().await;
+
+        let sessions = state.sessions.read().await;
+        let session = &sessions[&id];
+        assert_eq!(session.last_generated_code, "def main():\n    print('hi')");
+        // The broken reply, the fix request and the fix stay in the chat
+        assert_eq!(session.messages.len(), 3);
+        assert_eq!(session.messages[0].content, "def main(:\n    print('hi')");
+        drop(sessions);
+        assert_eq!(*state.last_generated_code.read().await, "def main():\n    print('hi')");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_load_script_into_session() {
+        let (state, dir) = test_state();
+        std::fs::write(dir.join("script_20251209_152023.py"), "print('hi')\n").unwrap();
+        std::fs::write(dir.join("notes.py"), "print('not generated')\n").unwrap();
+        let id = state.active_session_id.read().await.clone();
+
+        let load = |filename: &str| {
+            load_script_into_session(State(state.clone()), Path((id.clone(), filename.to_string())))
+        };
+        let resp = load("script_20251209_152023.py").await.into_response();
+        assert_eq!(resp.status(), axum::http::StatusCode::OK);
+        assert_eq!(load("notes.py").await.into_response().status(), axum::http::StatusCode::NOT_FOUND);
+
+        let sessions = state.sessions.read().await;
+        let session = &sessions[&id];
+        let roles: Vec<&str> = session.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant"]);
+        assert_eq!(session.messages[0].content, "Loaded script_20251209_152023.py");
+        assert_eq!(session.last_generated_code, "print('hi')\n");
+        drop(sessions);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_get_script_only_serves_generated_scripts() {
+        let (state, dir) = test_state();
+        std::fs::write(dir.join("script_20251209_152023.py"), "import requests\n").unwrap();
+        std::fs::write(dir.join("notes.py"), "print('not generated')\n").unwrap();
+
+        let get = |filename: &str| get_script(State(state.clone()), Path(filename.to_string()));
+        let resp = get("script_20251209_152023.py").await;
+        assert_eq!(resp.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["content"], "import requests\n");
+        assert_eq!(json["dependencies"][0], "requests");
+
+        assert_eq!(get("notes.py").await.status(), axum::http::StatusCode::NOT_FOUND);
+        assert_eq!(get("../script_20251209_152023.py").await.status(), axum::http::StatusCode::NOT_FOUND);
+        assert_eq!(get("script_20251209_152024.py").await.status(), axum::http::StatusCode::NOT_FOUND);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_execute_holds_rate_limit_slot_until_the_script_exits() {
+        use crate::dashboard::rate_limit::{rate_limit, RateLimiter};
+
+        let (mut state, dir) = test_state();
+        Arc::get_mut(&mut state).unwrap().rate_limiter = RateLimiter::with_limits(100, std::time::Duration::from_secs(60), 1);
+        let app = axum::Router::new()
+            .route("/api/execute", axum::routing::post(execute_code))
+            .route_layer(axum::middleware::from_fn_with_state(state.clone(), rate_limit))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/execute", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = reqwest::Client::new();
+        let execute = |code: &str| client.post(&url).json(&serde_json::json!({ "code": code })).send();
+        let first = execute("import time\ntime.sleep(1)\n").await.unwrap();
+        assert_eq!(first.status(), 202);
+        // The first run is still going, so its slot is taken
+        assert_eq!(execute("print('second')\n").await.unwrap().status(), 429);
+
+        // Once it exits the slot is free again
+        let mut freed = false;
+        for _ in 0..100 {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            if execute("print('third')\n").await.unwrap().status() == 202 {
+                freed = true;
+                break;
+            }
+        }
+        assert!(freed, "the rate-limit slot was never released");
+        let _ = std::fs::remove_dir_all(dir);
+    }
 }
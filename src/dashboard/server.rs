@@ -1,28 +1,46 @@
 use axum::{
-    routing::{delete, get, post, put},
+    middleware,
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use std::sync::Arc;
 
+use super::auth;
+use super::rate_limit;
 use super::routes;
 use super::state::DashboardState;
 use super::websocket;
 
-/// Start the Axum web dashboard server on the given port.
+/// Start the Axum web dashboard server on the given host and port.
 ///
-/// This runs as a background tokio task alongside the REPL.
-pub async fn start_dashboard(state: Arc<DashboardState>, port: u16) -> anyhow::Result<()> {
+/// This runs as a background tokio task alongside the REPL. If
+/// `dashboard_token` is configured, every route requires it.
+pub async fn start_dashboard(state: Arc<DashboardState>, host: &str, port: u16) -> anyhow::Result<()> {
+    // Saves chat sessions after changes (no-op for in-memory sessions)
+    tokio::spawn(state.clone().persist_sessions());
+
+    // Apply the generated_dir retention policy before serving the history
+    state.executor.prune_generated();
+
+    // Endpoints that call the LLM or run code share a rate limiter
+    let limited = Router::new()
+        .route("/api/generate", post(routes::generate_code))
+        .route("/api/execute", post(routes::execute_code))
+        .route("/api/test", post(routes::run_tests))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit::rate_limit));
+
     let app = Router::new()
         // HTML pages
         .route("/", get(routes::index))
         .route("/code/:filename", get(routes::view_code))
         // JSON API endpoints
         .route("/api/history", get(routes::get_history))
+        .route("/api/history/:filename", delete(routes::delete_script))
+        .route("/api/scripts/:filename", get(routes::get_script))
         .route("/api/stats", get(routes::get_stats))
         .route("/api/containers", get(routes::get_containers))
-        .route("/api/generate", post(routes::generate_code))
         // Execution
-        .route("/api/execute", post(routes::execute_code))
+        .route("/api/generate/cancel", post(routes::cancel_generation))
         .route("/api/execute/kill", post(routes::kill_execution))
         .route("/api/execute/input", post(routes::send_input))
         // Lint & Security
@@ -33,7 +51,10 @@ pub async fn start_dashboard(state: Arc<DashboardState>, port: u16) -> anyhow::R
         .route("/api/sessions", post(routes::create_session))
         .route("/api/sessions/:id", get(routes::get_session))
         .route("/api/sessions/:id", delete(routes::delete_session))
+        .route("/api/sessions/:id", patch(routes::rename_session))
         .route("/api/sessions/:id/active", put(routes::set_active_session))
+        .route("/api/sessions/:id/export", get(routes::export_session))
+        .route("/api/sessions/:id/load/:filename", post(routes::load_script_into_session))
         // Model selection & settings
         .route("/api/models", get(routes::get_models))
         .route("/api/settings", get(routes::get_settings))
@@ -44,10 +65,23 @@ pub async fn start_dashboard(state: Arc<DashboardState>, port: u16) -> anyhow::R
         .route("/api/containers/html", get(routes::get_containers_html))
         // WebSocket for real-time logs
         .route("/api/logs", get(websocket::ws_handler))
+        .merge(limited);
+
+    let app = match state.config.dashboard_token() {
+        Some(token) => app.route_layer(middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            auth::require_token,
+        )),
+        None => app,
+    };
+    // Probes for supervisors and container orchestrators stay reachable
+    // without the token; /api/ready caches its provider check
+    let app = app
+        .route("/api/health", get(routes::health))
+        .route("/api/ready", get(routes::ready))
         .with_state(state);
 
-    let addr = format!("127.0.0.1:{}", port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    let listener = tokio::net::TcpListener::bind((host, port)).await?;
 
     axum::serve(listener, app).await?;
     Ok(())
@@ -1,11 +1,17 @@
 use crate::api::Message;
 use crate::config::AppConfig;
 use crate::logger::SessionMetrics;
-use crate::python_exec::CodeExecutor;
+use crate::python_exec::{CodeExecutor, ExecutionTiming};
+use super::rate_limit::RateLimiter;
 use serde::{Deserialize, Serialize};
+use anyhow::Context;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, Notify, RwLock};
+use tokio_util::sync::CancellationToken;
 
 // ── Events streamed to WebSocket clients ─────────────────────────────
 
@@ -15,19 +21,40 @@ use tokio::sync::{broadcast, Mutex, RwLock};
 pub enum ExecutionEvent {
     /// A line of stdout or stderr output from a running script.
     LogLine {
+        /// Process-wide, monotonically increasing; clients sort and dedupe by it.
+        seq: u64,
+        /// The execution (or tool run) this line belongs to.
+        run_id: String,
         timestamp: String,
         stream: String,
         content: String,
     },
     /// A script execution has started.
-    ExecutionStarted { script_path: String },
+    ExecutionStarted { run_id: String, script_path: String },
     /// A script execution has completed.
     ExecutionCompleted {
+        run_id: String,
         success: bool,
         exit_code: Option<i32>,
+        /// Time spent in each pipeline phase up to completion.
+        timing: ExecutionTiming,
+    },
+    /// A chunk of an LLM reply that is still streaming in.
+    CodeToken { session_id: String, content: String },
+    /// A chat session was renamed; sidebars should refresh.
+    SessionRenamed { session_id: String, name: String },
+    /// A dashboard generation failed; no `CodeGenerated` will follow.
+    GenerationFailed { session_id: String, error: String },
+    /// New code was generated by the LLM (in the web UI or the REPL).
+    CodeGenerated {
+        code: String,
+        script_path: String,
+        /// Chat session the code belongs to.
+        session_id: String,
+        /// The model's reply before code extraction, only with `verbose`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        raw_response: Option<String>,
     },
-    /// New code was generated by the LLM.
-    CodeGenerated { code: String, script_path: String },
     /// Lint check result.
     LintCompleted {
         passed: bool,
@@ -38,8 +65,18 @@ pub enum ExecutionEvent {
         passed: bool,
         diagnostics: String,
     },
+    /// A pytest run finished.
+    TestCompleted {
+        passed: u32,
+        failed: u32,
+        errors: u32,
+    },
+    /// A generated script was loaded back into a chat session as its latest reply.
+    ScriptLoaded { session_id: String, filename: String, code: String },
+    /// A generated script was deleted; history views should refresh.
+    ScriptDeleted { filename: String },
     /// A running script was killed by the user.
-    ExecutionKilled,
+    ExecutionKilled { run_id: String },
     /// A running script is waiting for user input (stdin).
     WaitingForInput {
         prompt: String,
@@ -54,6 +91,16 @@ pub struct ScriptEntry {
     pub filename: String,
     pub path: String,
     pub timestamp: String,
+    pub size_bytes: u64,
+    /// Time since the file was last modified, e.g. `5 minutes ago`.
+    pub modified: String,
+}
+
+impl ScriptEntry {
+    /// File size for display, e.g. `2.3 KB`.
+    pub fn size_display(&self) -> String {
+        crate::utils::format_size(self.size_bytes)
+    }
 }
 
 // ── Chat sessions ────────────────────────────────────────────────────
@@ -66,12 +113,76 @@ pub struct ChatSession {
     pub messages: Vec<Message>,
     pub last_generated_code: String,
     pub created_at: String,
+    /// Provider, model and toggles used for this chat's generations and runs.
+    pub settings: RuntimeSettings,
+}
+
+/// Longest session name accepted by the rename endpoint, in characters.
+pub const MAX_SESSION_NAME_CHARS: usize = 80;
+
+impl ChatSession {
+    /// An empty session named `name`, created now.
+    pub fn new(name: &str, settings: RuntimeSettings) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            messages: Vec::new(),
+            last_generated_code: String::new(),
+            created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            settings,
+        }
+    }
+
+    /// Trim a user-supplied session name and check it is non-empty and
+    /// within [`MAX_SESSION_NAME_CHARS`].
+    pub fn validate_name(name: &str) -> Result<String, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Session name cannot be empty.".to_string());
+        }
+        if name.chars().count() > MAX_SESSION_NAME_CHARS {
+            return Err(format!("Session name is too long (max {} characters).", MAX_SESSION_NAME_CHARS));
+        }
+        Ok(name.to_string())
+    }
+}
+
+/// Chat sessions as written to the sessions file.
+#[derive(Serialize, Deserialize)]
+struct SavedSessions {
+    active_session_id: String,
+    sessions: Vec<ChatSession>,
+}
+
+/// Location of the saved dashboard chats: `~/.pymakebot/dashboard_sessions.json`.
+pub fn default_sessions_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".pymakebot").join("dashboard_sessions.json"))
+}
+
+/// How long to wait after a session change before writing the sessions
+/// file, so a burst of changes (a generation, a rename) costs one write.
+const SESSIONS_SAVE_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// How long `/api/ready` reuses its last provider probe. The probe is
+/// unauthenticated, so this bounds how often it reaches the provider.
+pub const READY_PROBE_TTL: Duration = Duration::from_secs(10);
+
+/// Load saved chat sessions from `path`. A missing file yields `None`.
+fn load_sessions(path: &Path) -> anyhow::Result<Option<SavedSessions>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read sessions file {:?}", path))?;
+    let saved = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse sessions file {:?}", path))?;
+    Ok(Some(saved))
 }
 
 // ── Runtime settings (mutable subset of AppConfig) ───────────────────
 
-/// Settings that can be changed at runtime from the dashboard UI.
-/// Initialized from `AppConfig` at startup.
+/// Settings that can be changed at runtime from the dashboard UI, per chat
+/// session. Initialized from `AppConfig`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RuntimeSettings {
     pub provider: String,
@@ -85,6 +196,9 @@ pub struct RuntimeSettings {
     pub execution_timeout_secs: u64,
     pub auto_install_deps: bool,
     pub max_tokens: u32,
+    /// Refine once server-side when generated code fails the syntax check.
+    #[serde(default)]
+    pub auto_refine: bool,
 }
 
 impl RuntimeSettings {
@@ -102,9 +216,24 @@ impl RuntimeSettings {
             execution_timeout_secs: config.execution_timeout_secs,
             auto_install_deps: config.auto_install_deps,
             max_tokens: config.max_tokens,
+            auto_refine: config.dashboard_auto_refine,
         }
     }
 
+    /// Point these settings at `config`'s provider, model and API URL,
+    /// returning whether any of them changed. Applied to sessions reloaded
+    /// from disk so edits to the config file reach existing chats.
+    fn refresh_connection(&mut self, config: &AppConfig) -> bool {
+        let current = (&config.provider, &config.model, &config.api_url);
+        if (&self.provider, &self.model, &self.api_url) == current {
+            return false;
+        }
+        self.provider = config.provider.clone();
+        self.model = config.model.clone();
+        self.api_url = config.api_url.clone();
+        true
+    }
+
     /// Build an ephemeral `AppConfig` by overlaying these runtime settings
     /// on top of the base config. Used for LLM API calls.
     pub fn to_app_config(&self, base: &AppConfig) -> AppConfig {
@@ -120,6 +249,7 @@ impl RuntimeSettings {
             execution_timeout_secs: self.execution_timeout_secs,
             auto_install_deps: self.auto_install_deps,
             max_tokens: self.max_tokens,
+            dashboard_auto_refine: self.auto_refine,
             ..base.clone()
         }
     }
@@ -127,6 +257,20 @@ impl RuntimeSettings {
 
 // ── Shared dashboard state ───────────────────────────────────────────
 
+/// A dashboard execution's process, for the kill and input endpoints.
+pub struct RunningProcess {
+    pub pid: u32,
+    /// Stdin handle, for interactive input.
+    pub stdin: Option<std::process::ChildStdin>,
+}
+
+/// An in-flight LLM generation, for the cancel endpoint.
+pub struct Generation {
+    /// Chat the reply belongs to.
+    pub session_id: String,
+    pub cancel: CancellationToken,
+}
+
 /// Shared application state accessible by both the REPL and the web dashboard.
 ///
 /// Wrapped in `Arc` and passed to both the Axum server and the REPL loop.
@@ -143,34 +287,96 @@ pub struct DashboardState {
     pub sessions: RwLock<HashMap<String, ChatSession>>,
     /// ID of the currently active chat session.
     pub active_session_id: RwLock<String>,
-    /// Runtime-mutable settings (provider, model, toggles, etc.).
-    pub runtime_settings: RwLock<RuntimeSettings>,
-    /// PID of the currently running script process (for kill support).
-    pub running_pid: Mutex<Option<u32>>,
-    /// Stdin handle of the currently running script process (for interactive input).
-    pub running_stdin: Mutex<Option<std::process::ChildStdin>>,
+    /// ID of the "Terminal" session mirroring the REPL conversation, once created.
+    pub repl_session_id: Mutex<Option<String>>,
+    /// Scripts started from the dashboard that are still running, keyed by run ID.
+    pub running: Mutex<HashMap<String, RunningProcess>>,
+    /// In-flight generations, keyed by a per-generation ID so overlapping
+    /// generations in one chat don't drop each other's token.
+    pub generations: Mutex<HashMap<String, Generation>>,
+    /// Next `LogLine` sequence number.
+    log_seq: AtomicU64,
+    /// Limits LLM generation and execution requests from the web UI.
+    pub rate_limiter: RateLimiter,
+    /// When the dashboard state was created (for `/api/health` uptime).
+    pub started_at: std::time::Instant,
+    /// Last `/api/ready` probe: when it ran and whether the provider answered.
+    pub last_ready_probe: Mutex<Option<(std::time::Instant, bool)>>,
+    /// Where chat sessions are saved, if they persist across restarts.
+    sessions_path: Option<PathBuf>,
+    /// Signalled by [`DashboardState::sessions_changed`] to schedule a save.
+    sessions_dirty: Notify,
 }
 
 impl DashboardState {
     /// Create a new shared state with a broadcast channel for execution events.
+    /// Chat sessions live only in memory.
     pub fn new(
         config: AppConfig,
         executor: CodeExecutor,
+    ) -> Arc<Self> {
+        Self::build(config, executor, None, None)
+    }
+
+    /// Like [`DashboardState::new`], but chat sessions are reloaded from
+    /// `path` and saved back to it after changes (see
+    /// [`DashboardState::persist_sessions`]). An unreadable file is reported
+    /// and replaced by a fresh session; one that doesn't parse is first moved
+    /// aside to `<path>.bak` so the next save doesn't destroy it.
+    pub fn with_sessions_file(
+        config: AppConfig,
+        executor: CodeExecutor,
+        path: PathBuf,
+    ) -> Arc<Self> {
+        let saved = load_sessions(&path).unwrap_or_else(|e| {
+            let backup = PathBuf::from(format!("{}.bak", path.display()));
+            match std::fs::rename(&path, &backup) {
+                Ok(()) => eprintln!("Warning: {:#}; moved it to {:?} and starting with a new chat", e, backup),
+                Err(_) => eprintln!("Warning: {:#}; starting with a new chat", e),
+            }
+            None
+        });
+        Self::build(config, executor, saved, Some(path))
+    }
+
+    fn build(
+        config: AppConfig,
+        executor: CodeExecutor,
+        saved: Option<SavedSessions>,
+        sessions_path: Option<PathBuf>,
     ) -> Arc<Self> {
         let (event_tx, _) = broadcast::channel(256);
-        let runtime_settings = RuntimeSettings::from_config(&config);
+        let rate_limiter = RateLimiter::new(config.dashboard_max_requests_per_min);
 
-        // Create the default session
-        let default_session_id = uuid::Uuid::new_v4().to_string();
-        let default_session = ChatSession {
-            id: default_session_id.clone(),
-            name: "New Chat".to_string(),
-            messages: Vec::new(),
-            last_generated_code: String::new(),
-            created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-        };
-        let mut sessions = HashMap::new();
-        sessions.insert(default_session_id.clone(), default_session);
+        let mut sessions: HashMap<String, ChatSession> = HashMap::new();
+        let mut default_session_id = String::new();
+        if let Some(saved) = saved {
+            default_session_id = saved.active_session_id;
+            let mut refreshed = 0;
+            for mut session in saved.sessions {
+                refreshed += usize::from(session.settings.refresh_connection(&config));
+                sessions.insert(session.id.clone(), session);
+            }
+            if refreshed > 0 {
+                eprintln!(
+                    "Note: {} saved chat(s) switched to the configured provider ({}, model {})",
+                    refreshed, config.provider, config.model
+                );
+            }
+        }
+        if sessions.is_empty() {
+            // Create the default session
+            let default_session = ChatSession::new("New Chat", RuntimeSettings::from_config(&config));
+            default_session_id = default_session.id.clone();
+            sessions.insert(default_session_id.clone(), default_session);
+        } else if !sessions.contains_key(&default_session_id) {
+            // Fall back to the most recently created chat
+            default_session_id = sessions
+                .values()
+                .max_by(|a, b| a.created_at.cmp(&b.created_at))
+                .map(|s| s.id.clone())
+                .unwrap_or_default();
+        }
 
         Arc::new(Self {
             config,
@@ -181,15 +387,289 @@ impl DashboardState {
             executor,
             sessions: RwLock::new(sessions),
             active_session_id: RwLock::new(default_session_id),
-            runtime_settings: RwLock::new(runtime_settings),
-            running_pid: Mutex::new(None),
-            running_stdin: Mutex::new(None),
+            repl_session_id: Mutex::new(None),
+            running: Mutex::new(HashMap::new()),
+            generations: Mutex::new(HashMap::new()),
+            log_seq: AtomicU64::new(0),
+            rate_limiter,
+            started_at: std::time::Instant::now(),
+            last_ready_probe: Mutex::new(None),
+            sessions_path,
+            sessions_dirty: Notify::new(),
         })
     }
 
+    /// Register a generation for `session_id`, returning its ID (for
+    /// [`finish_generation`](Self::finish_generation)) and cancellation token.
+    pub async fn start_generation(&self, session_id: &str) -> (String, CancellationToken) {
+        let id = uuid::Uuid::new_v4().to_string();
+        let cancel = CancellationToken::new();
+        self.generations.lock().await.insert(
+            id.clone(),
+            Generation { session_id: session_id.to_string(), cancel: cancel.clone() },
+        );
+        (id, cancel)
+    }
+
+    /// Forget a finished generation.
+    pub async fn finish_generation(&self, generation_id: &str) {
+        self.generations.lock().await.remove(generation_id);
+    }
+
+    /// Record that chat sessions changed, scheduling a debounced save when
+    /// they persist to a file.
+    pub fn sessions_changed(&self) {
+        if self.sessions_path.is_some() {
+            self.sessions_dirty.notify_one();
+        }
+    }
+
+    /// Write the chat sessions to the sessions file now (oldest first).
+    /// Does nothing for in-memory sessions.
+    pub async fn save_sessions(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.sessions_path else {
+            return Ok(());
+        };
+        let mut sessions: Vec<ChatSession> = self.sessions.read().await.values().cloned().collect();
+        sessions.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        let saved = SavedSessions {
+            active_session_id: self.active_session_id.read().await.clone(),
+            sessions,
+        };
+
+        if let Some(parent) = path.parent() {
+            crate::utils::ensure_dir(parent)?;
+        }
+        let json = serde_json::to_string_pretty(&saved).context("Failed to serialize sessions")?;
+        // Write then rename, so a crash mid-write can't truncate the file
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).with_context(|| format!("Failed to write sessions file {:?}", tmp))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("Failed to write sessions file {:?}", path))
+    }
+
+    /// Save the chat sessions whenever they change, at most once per
+    /// [`SESSIONS_SAVE_DEBOUNCE`]. Runs until the process exits.
+    pub async fn persist_sessions(self: Arc<Self>) {
+        loop {
+            self.sessions_dirty.notified().await;
+            tokio::time::sleep(SESSIONS_SAVE_DEBOUNCE).await;
+            if let Err(e) = self.save_sessions().await {
+                eprintln!("Warning: {:#}", e);
+            }
+        }
+    }
+
+    /// Settings of the active chat session (config defaults if it is gone).
+    pub async fn active_settings(&self) -> RuntimeSettings {
+        let active_id = self.active_session_id.read().await.clone();
+        self.sessions
+            .read()
+            .await
+            .get(&active_id)
+            .map(|s| s.settings.clone())
+            .unwrap_or_else(|| RuntimeSettings::from_config(&self.config))
+    }
+
+    /// Mirror the REPL conversation into its own "Terminal" chat session so
+    /// CLI generations show up in the web UI. The session is created on first
+    /// use (and again if it was deleted from the dashboard). Returns its ID.
+    pub async fn sync_repl_session(&self, history: &[Message], last_code: &str) -> String {
+        let mut repl_id = self.repl_session_id.lock().await;
+        let id = repl_id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone();
+
+        let mut sessions = self.sessions.write().await;
+        let session = sessions.entry(id.clone()).or_insert_with(|| ChatSession {
+            id: id.clone(),
+            ..ChatSession::new("Terminal", RuntimeSettings::from_config(&self.config))
+        });
+        session.messages = history.to_vec();
+        session.last_generated_code = last_code.to_string();
+        drop(sessions);
+        self.sessions_changed();
+        id
+    }
+
     /// Broadcast an execution event to all connected WebSocket clients.
     /// Silently ignores errors if there are no active receivers.
     pub fn broadcast(&self, event: ExecutionEvent) {
         let _ = self.event_tx.send(event);
     }
+
+    /// Broadcast one output line for `run_id`, stamped with the next sequence number.
+    pub fn broadcast_log(&self, run_id: &str, stream: &str, content: impl Into<String>) {
+        self.broadcast(ExecutionEvent::LogLine {
+            seq: self.log_seq.fetch_add(1, Ordering::Relaxed),
+            run_id: run_id.to_string(),
+            timestamp: chrono::Local::now().format("%H:%M:%S").to_string(),
+            stream: stream.to_string(),
+            content: content.into(),
+        });
+    }
+
+    /// Generate an ID tying log lines to one execution or tool run.
+    pub fn new_run_id() -> String {
+        uuid::Uuid::new_v4().simple().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_state() -> (Arc<DashboardState>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("pymakebot_state_{}", uuid::Uuid::new_v4()));
+        let executor = CodeExecutor::new(dir.to_str().unwrap(), false, false, "python3").unwrap();
+        (DashboardState::new(AppConfig::default(), executor), dir)
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_generations_keep_their_tokens() {
+        let (state, dir) = test_state();
+        let (first, _) = state.start_generation("chat").await;
+        let (second, second_cancel) = state.start_generation("chat").await;
+        assert_ne!(first, second);
+
+        // The first reply finishing must not forget the second one
+        state.finish_generation(&first).await;
+        let generations = state.generations.lock().await;
+        assert_eq!(generations.len(), 1);
+        generations[&second].cancel.cancel();
+        assert!(second_cancel.is_cancelled());
+        drop(generations);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_broadcast_log_sequence_and_run_id() {
+        let (state, dir) = test_state();
+        let mut rx = state.event_tx.subscribe();
+        state.broadcast_log("run-a", "stdout", "one");
+        state.broadcast_log("run-b", "stderr", "two");
+
+        let mut seen = Vec::new();
+        while let Ok(ExecutionEvent::LogLine { seq, run_id, stream, content, .. }) = rx.try_recv() {
+            seen.push((seq, run_id, stream, content));
+        }
+        assert_eq!(seen.len(), 2);
+        assert!(seen[0].0 < seen[1].0);
+        assert_eq!(seen[0].1, "run-a");
+        assert_eq!((seen[1].2.as_str(), seen[1].3.as_str()), ("stderr", "two"));
+
+        let json = serde_json::to_value(ExecutionEvent::ExecutionStarted {
+            run_id: "run-a".to_string(),
+            script_path: "x.py".to_string(),
+        })
+        .unwrap();
+        assert_eq!(json["type"], "ExecutionStarted");
+        assert_eq!(json["run_id"], "run-a");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("pymakebot_state_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("dashboard_sessions.json");
+        let executor = || CodeExecutor::new(dir.to_str().unwrap(), false, false, "python3").unwrap();
+
+        let state = DashboardState::with_sessions_file(AppConfig::default(), executor(), path.clone());
+        let chat = ChatSession::new("Snake game", RuntimeSettings::from_config(&state.config));
+        let chat_id = chat.id.clone();
+        state.sessions.write().await.insert(chat_id.clone(), chat);
+        *state.active_session_id.write().await = chat_id.clone();
+        state.save_sessions().await.unwrap();
+
+        let restarted = DashboardState::with_sessions_file(AppConfig::default(), executor(), path.clone());
+        assert_eq!(restarted.sessions.read().await.len(), 2);
+        assert_eq!(*restarted.active_session_id.read().await, chat_id);
+        assert_eq!(restarted.sessions.read().await[&chat_id].name, "Snake game");
+
+        // Provider, model and URL follow the config the dashboard restarts with
+        let changed = AppConfig {
+            model: "other-model".to_string(),
+            api_url: "http://localhost:9/v1".to_string(),
+            ..AppConfig::default()
+        };
+        let restarted = DashboardState::with_sessions_file(changed, executor(), path.clone());
+        let settings = restarted.sessions.read().await[&chat_id].settings.clone();
+        assert_eq!(settings.model, "other-model");
+        assert_eq!(settings.api_url, "http://localhost:9/v1");
+
+        // A corrupt file starts a fresh chat instead of failing
+        std::fs::write(&path, "{ not json").unwrap();
+        let fresh = DashboardState::with_sessions_file(AppConfig::default(), executor(), path.clone());
+        assert_eq!(fresh.sessions.read().await.len(), 1);
+        // ...after keeping the corrupt file for recovery
+        assert_eq!(std::fs::read_to_string(format!("{}.bak", path.display())).unwrap(), "{ not json");
+        assert!(!path.exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_validate_session_name() {
+        assert_eq!(ChatSession::validate_name("  Snake game  ").unwrap(), "Snake game");
+        assert!(ChatSession::validate_name("   ").is_err());
+        assert!(ChatSession::validate_name(&"é".repeat(MAX_SESSION_NAME_CHARS)).is_ok());
+        assert!(ChatSession::validate_name(&"x".repeat(MAX_SESSION_NAME_CHARS + 1))
+            .unwrap_err()
+            .contains("too long"));
+    }
+
+    #[tokio::test]
+    async fn test_settings_are_per_session() {
+        let (state, dir) = test_state();
+        let first_id = state.active_session_id.read().await.clone();
+
+        // Switch the first chat to a local model
+        let local = RuntimeSettings {
+            provider: "ollama".to_string(),
+            model: "codellama".to_string(),
+            ..state.active_settings().await
+        };
+        state.sessions.write().await.get_mut(&first_id).unwrap().settings = local;
+
+        let second = ChatSession::new("Cloud", RuntimeSettings::from_config(&state.config));
+        let second_id = second.id.clone();
+        state.sessions.write().await.insert(second_id.clone(), second);
+
+        assert_eq!(state.active_settings().await.model, "codellama");
+        *state.active_session_id.write().await = second_id;
+        assert_eq!(state.active_settings().await.model, AppConfig::default().model);
+
+        let config = state.sessions.read().await[&first_id].settings.to_app_config(&state.config);
+        assert_eq!((config.provider.as_str(), config.model.as_str()), ("ollama", "codellama"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_sync_repl_session_reuses_terminal_session() {
+        let (state, dir) = test_state();
+
+        let history = vec![
+            Message { role: "user".to_string(), content: "say hi".to_string() },
+            Message { role: "assistant".to_string(), content: "print('hi')".to_string() },
+        ];
+        let id = state.sync_repl_session(&history[..1], "").await;
+        assert_eq!(state.sync_repl_session(&history, "print('hi')").await, id);
+
+        {
+            let sessions = state.sessions.read().await;
+            assert_eq!(sessions.len(), 2);
+            let terminal = &sessions[&id];
+            assert_eq!(terminal.name, "Terminal");
+            assert_eq!(terminal.messages.len(), 2);
+            assert_eq!(terminal.last_generated_code, "print('hi')");
+        }
+
+        // Deleted from the dashboard: recreated under the same ID
+        state.sessions.write().await.remove(&id);
+        assert_eq!(state.sync_repl_session(&history, "print('hi')").await, id);
+        assert!(state.sessions.read().await.contains_key(&id));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -45,6 +45,7 @@ pub struct StatsTemplate {
     pub failed_executions: usize,
     pub api_errors: usize,
     pub success_rate: f64,
+    pub api_latency: String,
 }
 
 #[derive(Template)]
@@ -106,6 +107,7 @@ pub fn render_stats(
     failed_executions: usize,
     api_errors: usize,
     success_rate: f64,
+    api_latency: String,
 ) -> String {
     let template = StatsTemplate {
         total_requests,
@@ -113,6 +115,7 @@ pub fn render_stats(
         failed_executions,
         api_errors,
         success_rate,
+        api_latency,
     };
     template.render().unwrap_or_default()
 }
@@ -6,10 +6,18 @@ use axum::{
     response::IntoResponse,
 };
 use futures::{SinkExt, StreamExt};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
 
 use super::state::DashboardState;
 
+/// How often a ping is sent to keep proxies from closing idle connections.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A client that hasn't answered for this long is considered dead.
+const PONG_TIMEOUT: Duration = Duration::from_secs(75);
+
 /// Axum handler that upgrades an HTTP request to a WebSocket connection.
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
@@ -19,28 +27,52 @@ pub async fn ws_handler(
 }
 
 /// Manages a single WebSocket connection: subscribes to the broadcast channel
-/// and forwards `ExecutionEvent`s as JSON to the client.
+/// and forwards `ExecutionEvent`s as JSON to the client. Pings the client
+/// every `PING_INTERVAL` and drops the connection if it stops answering.
 async fn handle_socket(socket: WebSocket, state: Arc<DashboardState>) {
     let (mut sender, mut receiver) = socket.split();
     let mut event_rx = state.event_tx.subscribe();
+    let last_seen = Arc::new(Mutex::new(Instant::now()));
 
-    // Task: forward broadcast events → WebSocket client
+    // Task: forward broadcast events → WebSocket client, plus keepalive pings
+    let send_last_seen = last_seen.clone();
     let mut send_task = tokio::spawn(async move {
-        while let Ok(event) = event_rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&event) {
-                if sender.send(Message::Text(json)).await.is_err() {
-                    break; // client disconnected
+        let mut ping = tokio::time::interval(PING_INTERVAL);
+        ping.tick().await; // the first tick completes immediately
+        loop {
+            tokio::select! {
+                event = event_rx.recv() => {
+                    let event = match event {
+                        Ok(event) => event,
+                        // Slow client missed some events; keep streaming
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    };
+                    if let Ok(json) = serde_json::to_string(&event) {
+                        if sender.send(Message::Text(json)).await.is_err() {
+                            break; // client disconnected
+                        }
+                    }
+                }
+                _ = ping.tick() => {
+                    if send_last_seen.lock().unwrap().elapsed() > PONG_TIMEOUT {
+                        break; // no pong: dead client
+                    }
+                    if sender.send(Message::Ping(Vec::new())).await.is_err() {
+                        break;
+                    }
                 }
             }
         }
     });
 
-    // Task: read from WebSocket (handle close / ping-pong)
+    // Task: read from WebSocket (handle close, record pongs and other traffic)
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             if matches!(msg, Message::Close(_)) {
                 break;
             }
+            *last_seen.lock().unwrap() = Instant::now();
         }
     });
 
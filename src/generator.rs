@@ -0,0 +1,180 @@
+use crate::api::{self, Message};
+use crate::config::AppConfig;
+use crate::interface::trim_history;
+use crate::utils::extract_python_code;
+use anyhow::Result;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Library entry point for code generation without the REPL.
+///
+/// Wraps [`api::generate_code_with_history`] and keeps the conversation
+/// history between calls, trimmed to `max_history_messages` like the REPL.
+///
+/// ```no_run
+/// # async fn demo() -> anyhow::Result<()> {
+/// use python_maker_bot::{AppConfig, CodeGenerator};
+///
+/// let generator = CodeGenerator::new(AppConfig::load());
+/// let code = generator.generate("a click CLI with two subcommands").await?;
+/// let better = generator.refine(&code, "add a --verbose flag").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CodeGenerator {
+    config: AppConfig,
+    history: Mutex<Vec<Message>>,
+}
+
+impl CodeGenerator {
+    /// Create a generator with an empty conversation history.
+    pub fn new(config: AppConfig) -> Self {
+        Self {
+            config,
+            history: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Seed the conversation history (e.g. to resume a saved session).
+    pub fn with_history(self, history: Vec<Message>) -> Self {
+        *self.history.lock().unwrap() = history;
+        self
+    }
+
+    /// Return the configuration used for API calls.
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// Return a snapshot of the conversation history.
+    pub fn history(&self) -> Vec<Message> {
+        self.history.lock().unwrap().clone()
+    }
+
+    /// Forget all previous turns.
+    pub fn clear_history(&self) {
+        self.history.lock().unwrap().clear();
+    }
+
+    /// Generate Python code for `prompt`, continuing the current conversation.
+    /// Returns the extracted code (markdown fences removed).
+    pub async fn generate(&self, prompt: &str) -> Result<String> {
+        self.send(Vec::new(), prompt.to_string()).await
+    }
+
+    /// Ask for changes to `prior` code. If `prior` isn't already the last
+    /// assistant turn, it is added to the history first so the model sees it.
+    pub async fn refine(&self, prior: &str, instruction: &str) -> Result<String> {
+        let is_last_reply = self
+            .history
+            .lock()
+            .unwrap()
+            .last()
+            .is_some_and(|m| m.role == "assistant" && m.content == prior);
+        let context = if is_last_reply {
+            Vec::new()
+        } else {
+            vec![Message {
+                role: "assistant".to_string(),
+                content: prior.to_string(),
+            }]
+        };
+        self.send(context, format!("Please refine the previous code: {}", instruction)).await
+    }
+
+    /// Send one user turn (after any `context` messages) and record the
+    /// exchange. History is left untouched if the API call fails.
+    async fn send(&self, context: Vec<Message>, user_content: String) -> Result<String> {
+        let mut turn = context;
+        turn.push(Message {
+            role: "user".to_string(),
+            content: user_content,
+        });
+
+        // Don't hold the lock across the API call
+        let mut messages = self.history();
+        messages.extend(turn.iter().cloned());
+        trim_history(&mut messages, self.config.max_history_messages, self.config.max_context_tokens);
+
+        let raw = api::generate_code_with_history(&messages, &self.config, &CancellationToken::new()).await?;
+        let code = extract_python_code(&raw);
+
+        let mut history = self.history.lock().unwrap();
+        history.extend(turn);
+        history.push(Message {
+            role: "assistant".to_string(),
+            content: code.clone(),
+        });
+        trim_history(&mut history, self.config.max_history_messages, self.config.max_context_tokens);
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(api_url: String) -> AppConfig {
+        AppConfig {
+            provider: "openai-compatible".to_string(),
+            api_url,
+            max_retries: 0,
+            ..AppConfig::default()
+        }
+    }
+
+    fn completion(content: &str) -> String {
+        serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": content } }]
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_generate_and_refine_track_history() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("POST", "/v1/chat/completions")
+            .with_body(completion("```python\nprint('hi')\n```"))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let generator = CodeGenerator::new(test_config(format!("{}/v1/chat/completions", server.url())));
+        let code = generator.generate("say hi").await.unwrap();
+        assert_eq!(code, "print('hi')");
+        first.assert_async().await;
+
+        let second = server
+            .mock("POST", "/v1/chat/completions")
+            .match_body(mockito::Matcher::Regex("refine the previous code: shout".to_string()))
+            .with_body(completion("print('HI')"))
+            .expect(1)
+            .create_async()
+            .await;
+        let refined = generator.refine(&code, "shout").await.unwrap();
+        assert_eq!(refined, "print('HI')");
+        second.assert_async().await;
+
+        // The prior code was already the last reply, so it isn't duplicated
+        let history = generator.history();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[1].content, "print('hi')");
+        assert_eq!(history[3].content, "print('HI')");
+    }
+
+    #[tokio::test]
+    async fn test_failed_call_leaves_history_untouched() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("POST", "/v1/chat/completions")
+            .with_status(400)
+            .with_body("bad request")
+            .create_async()
+            .await;
+
+        let generator = CodeGenerator::new(test_config(format!("{}/v1/chat/completions", server.url())));
+        assert!(generator.refine("print(1)", "make it 2").await.is_err());
+        assert!(generator.history().is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// When to color terminal output (`color` in config).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Color unless `NO_COLOR` is set or output isn't a terminal (default).
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Terminal background the palette is chosen for (`theme` in config).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Bright colors for dark backgrounds (default).
+    #[default]
+    Dark,
+    /// Darker colors in place of the white and yellow that vanish on a light background.
+    Light,
+}
+
+static LIGHT_THEME: AtomicBool = AtomicBool::new(false);
+
+/// Apply the color settings to all terminal output. Call once at startup.
+pub fn apply_color_settings(mode: ColorMode, theme: Theme) {
+    match mode {
+        ColorMode::Always => colored::control::set_override(true),
+        ColorMode::Never => colored::control::set_override(false),
+        ColorMode::Auto if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) => {
+            colored::control::set_override(false)
+        }
+        // `colored` already checks for a terminal and CLICOLOR
+        ColorMode::Auto => colored::control::unset_override(),
+    }
+    LIGHT_THEME.store(theme == Theme::Light, Ordering::Relaxed);
+}
+
+fn light_theme() -> bool {
+    LIGHT_THEME.load(Ordering::Relaxed)
+}
+
+/// Theme-aware colors for text that must stay readable on either background.
+pub trait Themed {
+    /// Replaces `bright_white()`: names, paths and other highlighted values.
+    fn emphasis(self) -> ColoredString;
+    /// Replaces `yellow()`: warnings, notices and their values.
+    fn warning(self) -> ColoredString;
+    /// Replaces `bright_cyan()`: the `━━━` frames around report sections.
+    fn frame(self) -> ColoredString;
+}
+
+impl<T: Colorize> Themed for T {
+    fn emphasis(self) -> ColoredString {
+        if light_theme() {
+            self.black()
+        } else {
+            self.bright_white()
+        }
+    }
+
+    fn warning(self) -> ColoredString {
+        if light_theme() {
+            self.magenta()
+        } else {
+            self.yellow()
+        }
+    }
+
+    fn frame(self) -> ColoredString {
+        if light_theme() {
+            self.blue()
+        } else {
+            self.bright_cyan()
+        }
+    }
+}
+
+/// Python keywords (including soft keywords used by `match`).
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "case", "class",
+    "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if",
+    "import", "in", "is", "lambda", "match", "nonlocal", "not", "or", "pass", "raise", "return",
+    "try", "while", "with", "yield",
+];
+
+/// Commonly used builtin functions, types and exceptions.
+const BUILTINS: &[&str] = &[
+    "abs", "all", "any", "bool", "bytes", "callable", "chr", "dict", "dir", "divmod", "enumerate",
+    "filter", "float", "format", "frozenset", "getattr", "hasattr", "hash", "hex", "id", "input",
+    "int", "isinstance", "issubclass", "iter", "len", "list", "map", "max", "min", "next",
+    "object", "open", "ord", "pow", "print", "property", "range", "repr", "reversed", "round",
+    "set", "setattr", "slice", "sorted", "staticmethod", "classmethod", "str", "sum", "super",
+    "tuple", "type", "vars", "zip", "self", "cls", "Exception", "ValueError", "TypeError",
+    "KeyError", "IndexError", "RuntimeError", "OSError", "FileNotFoundError", "StopIteration",
+    "KeyboardInterrupt", "NotImplementedError", "AttributeError", "ImportError", "ZeroDivisionError",
+];
+
+/// String prefixes that may precede a quote (`r"..."`, `f'...'`, `rb"""..."""`).
+const STRING_PREFIXES: &[&str] = &["r", "u", "b", "f", "br", "rb", "fr", "rf"];
+
+/// Lexical category of a highlighted span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    Builtin,
+    String,
+    Number,
+    Comment,
+    Decorator,
+    /// Name introduced by `def` or `class`.
+    Definition,
+    /// Identifiers, operators and whitespace.
+    Text,
+}
+
+/// Split Python source into highlighted spans. Concatenating the spans
+/// reproduces the input exactly; strings (including triple-quoted ones that
+/// span several lines) are a single span.
+pub fn tokenize(code: &str) -> Vec<(TokenKind, &str)> {
+    let bytes = code.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    let mut text_start = 0;
+    let mut at_line_start = true;
+    let mut expect_definition = false;
+
+    macro_rules! emit {
+        ($kind:expr, $end:expr) => {{
+            if text_start < pos {
+                tokens.push((TokenKind::Text, &code[text_start..pos]));
+            }
+            tokens.push(($kind, &code[pos..$end]));
+            pos = $end;
+            text_start = pos;
+        }};
+    }
+
+    while pos < bytes.len() {
+        let c = code[pos..].chars().next().unwrap_or(' ');
+        match c {
+            '#' => {
+                let end = code[pos..].find('\n').map_or(code.len(), |i| pos + i);
+                emit!(TokenKind::Comment, end);
+            }
+            '"' | '\'' => {
+                let end = scan_string(code, pos);
+                emit!(TokenKind::String, end);
+            }
+            '@' if at_line_start => {
+                let end = pos + 1 + take_while(&code[pos + 1..], |ch| is_ident_char(ch) || ch == '.');
+                emit!(TokenKind::Decorator, end);
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && bytes.get(pos + 1).is_some_and(|b| b.is_ascii_digit())) =>
+            {
+                let end = scan_number(code, pos);
+                emit!(TokenKind::Number, end);
+            }
+            c if is_ident_start(c) => {
+                let end = pos + take_while(&code[pos..], is_ident_char);
+                let word = &code[pos..end];
+                let next = bytes.get(end).copied();
+                if matches!(next, Some(b'"') | Some(b'\''))
+                    && STRING_PREFIXES.contains(&word.to_ascii_lowercase().as_str())
+                {
+                    let end = scan_string(code, end);
+                    emit!(TokenKind::String, end);
+                } else if expect_definition {
+                    expect_definition = false;
+                    emit!(TokenKind::Definition, end);
+                } else if KEYWORDS.contains(&word) {
+                    expect_definition = word == "def" || word == "class";
+                    emit!(TokenKind::Keyword, end);
+                } else if BUILTINS.contains(&word) {
+                    emit!(TokenKind::Builtin, end);
+                } else {
+                    // Plain identifier: leave it in the pending text run
+                    pos = end;
+                }
+            }
+            _ => pos += c.len_utf8(),
+        }
+
+        // Track whether only whitespace precedes `pos` on the current line
+        let last = code[..pos].chars().next_back();
+        at_line_start = match last {
+            Some('\n') => true,
+            Some(' ') | Some('\t') => at_line_start,
+            _ => false,
+        };
+    }
+
+    if text_start < code.len() {
+        tokens.push((TokenKind::Text, &code[text_start..]));
+    }
+    tokens
+}
+
+/// Highlight Python source for the terminal, returning one colored string
+/// per source line (multi-line strings are colored on every line they span).
+pub fn highlight_lines(code: &str) -> Vec<String> {
+    if code.is_empty() {
+        return Vec::new();
+    }
+    let mut lines = vec![String::new()];
+    for (kind, text) in tokenize(code) {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            if !part.is_empty() {
+                let current = lines.last_mut().expect("lines is never empty");
+                current.push_str(&paint(kind, part).to_string());
+            }
+        }
+    }
+    // `str::lines` semantics: a trailing newline doesn't start a new line
+    if code.ends_with('\n') {
+        lines.pop();
+    }
+    lines
+}
+
+fn paint(kind: TokenKind, text: &str) -> ColoredString {
+    if light_theme() {
+        return match kind {
+            TokenKind::Keyword => text.magenta().bold(),
+            TokenKind::Builtin => text.blue(),
+            TokenKind::String => text.green(),
+            TokenKind::Number => text.red(),
+            TokenKind::Comment => text.bright_black().italic(),
+            TokenKind::Decorator => text.cyan(),
+            TokenKind::Definition => text.blue().bold(),
+            TokenKind::Text => text.black(),
+        };
+    }
+    match kind {
+        TokenKind::Keyword => text.bright_magenta().bold(),
+        TokenKind::Builtin => text.cyan(),
+        TokenKind::String => text.green(),
+        TokenKind::Number => text.bright_red(),
+        TokenKind::Comment => text.bright_black().italic(),
+        TokenKind::Decorator => text.yellow(),
+        TokenKind::Definition => text.bright_yellow().bold(),
+        TokenKind::Text => text.white(),
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+fn is_ident_char(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+/// Length in bytes of the longest prefix of `s` whose chars satisfy `pred`.
+fn take_while(s: &str, pred: impl Fn(char) -> bool) -> usize {
+    s.char_indices().find(|&(_, c)| !pred(c)).map_or(s.len(), |(i, _)| i)
+}
+
+/// Return the end offset of the string literal whose opening quote is at
+/// `start`. Unterminated single-line strings stop at the newline;
+/// unterminated triple-quoted strings run to the end of the input.
+fn scan_string(code: &str, start: usize) -> usize {
+    let bytes = code.as_bytes();
+    let quote = bytes[start];
+    let triple = bytes.get(start + 1) == Some(&quote) && bytes.get(start + 2) == Some(&quote);
+    let mut pos = start + if triple { 3 } else { 1 };
+
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'\n' if !triple => return pos,
+            b if b == quote => {
+                if !triple {
+                    return pos + 1;
+                }
+                if bytes.get(pos + 1) == Some(&quote) && bytes.get(pos + 2) == Some(&quote) {
+                    return pos + 3;
+                }
+                pos += 1;
+            }
+            _ => pos += 1,
+        }
+    }
+    code.len()
+}
+
+/// Return the end offset of a numeric literal starting at `start`
+/// (ints, floats, hex/octal/binary, underscores, exponents, `j` suffix).
+fn scan_number(code: &str, start: usize) -> usize {
+    let bytes = code.as_bytes();
+    let mut pos = start;
+    while pos < bytes.len() {
+        let b = bytes[pos];
+        let is_exponent_sign = (b == b'+' || b == b'-')
+            && matches!(bytes[pos - 1], b'e' | b'E')
+            && !code[start..pos].starts_with("0x")
+            && !code[start..pos].starts_with("0X");
+        if b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || is_exponent_sign {
+            pos += 1;
+        } else {
+            break;
+        }
+    }
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(code)
+            .into_iter()
+            .filter(|(kind, text)| *kind != TokenKind::Text || !text.trim().is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_roundtrips_source() {
+        let code = "@dataclass\nclass Point:\n    x: int = 0  # comment\n\ndef f(s='a\\'b'):\n    return f\"{s}\" * 2.5e-3\n";
+        let joined: String = tokenize(code).into_iter().map(|(_, t)| t).collect();
+        assert_eq!(joined, code);
+    }
+
+    #[test]
+    fn test_tokenize_keywords_definitions_and_builtins() {
+        let tokens = kinds("def greet(name):\n    print(len(name))");
+        assert_eq!(tokens[0], (TokenKind::Keyword, "def"));
+        assert_eq!(tokens[1], (TokenKind::Definition, "greet"));
+        assert!(tokens.contains(&(TokenKind::Builtin, "print")));
+        assert!(tokens.contains(&(TokenKind::Builtin, "len")));
+    }
+
+    #[test]
+    fn test_tokenize_triple_quoted_string_spans_lines() {
+        let code = "doc = \"\"\"\nimport os  # not code\ndef nope():\n\"\"\"\nx = 1";
+        let tokens = kinds(code);
+        let string = tokens.iter().find(|(k, _)| *k == TokenKind::String).unwrap();
+        assert!(string.1.contains("def nope()"));
+        assert!(!tokens.contains(&(TokenKind::Keyword, "import")));
+        assert!(!tokens.iter().any(|(k, _)| *k == TokenKind::Comment));
+        assert!(tokens.contains(&(TokenKind::Number, "1")));
+    }
+
+    #[test]
+    fn test_tokenize_prefixed_strings_numbers_and_decorators() {
+        let tokens = kinds("@app.route('/')\nx = rb'\\x00' + 0xFF + 1_000 + 3.14j  # done");
+        assert_eq!(tokens[0], (TokenKind::Decorator, "@app.route"));
+        assert!(tokens.contains(&(TokenKind::String, "rb'\\x00'")));
+        assert!(tokens.contains(&(TokenKind::Number, "0xFF")));
+        assert!(tokens.contains(&(TokenKind::Number, "1_000")));
+        assert!(tokens.contains(&(TokenKind::Number, "3.14j")));
+        assert_eq!(tokens.last(), Some(&(TokenKind::Comment, "# done")));
+    }
+
+    #[test]
+    fn test_keyword_mid_line_and_matrix_operator() {
+        let tokens = kinds("y = a if ok else b\nz = m @ n");
+        assert!(tokens.contains(&(TokenKind::Keyword, "if")));
+        assert!(tokens.contains(&(TokenKind::Keyword, "else")));
+        // `@` mid-line is the matrix-multiply operator, not a decorator
+        assert!(!tokens.iter().any(|(k, _)| *k == TokenKind::Decorator));
+    }
+
+    #[test]
+    fn test_highlight_lines_count() {
+        let ansi = regex::Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+        let code = "s = '''a\nb'''\nprint(s)\n";
+        let lines: Vec<String> = highlight_lines(code)
+            .iter()
+            .map(|line| ansi.replace_all(line, "").into_owned())
+            .collect();
+        assert_eq!(lines, vec!["s = '''a", "b'''", "print(s)"]);
+        assert_eq!(highlight_lines("").len(), 0);
+    }
+}
@@ -1,14 +1,28 @@
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::fs;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use crate::api::{self, Message, Provider};
+use std::time::{Duration, Instant};
+use crate::api::{self, ApiError, Message, Provider};
 use crate::config::AppConfig;
 use crate::dashboard::state::{DashboardState, ExecutionEvent};
-use crate::python_exec::{CodeExecutor, ExecutionMode, LintSeverity, SecuritySeverity};
-use crate::utils::{extract_python_code, find_char_boundary};
+use crate::highlight::{highlight_lines, Themed};
+use crate::python_exec::{
+    analyze_hang_risk, format_ms, interactive_child_running, timed, CodeExecutionResult, CodeExecutor, ExecutionMode, ExecutionRecord, ExecutionTiming, Language, LintResult,
+    LintSeverity, SecurityResult, SecuritySeverity,
+};
+use crate::utils::{
+    ensure_dir, estimate_tokens, extract_project, extract_python_code, find_char_boundary, find_in_file, format_project,
+    is_generated_script_name, list_generated_scripts, parse_batch_prompts, parse_run_args, requirements_txt, resolve_within,
+    snippet_around, truncate_prompt, SNIPPET_CONTEXT_LINES,
+};
+use anyhow::Context as _;
 use crate::logger::{Logger, SessionMetrics};
 use colored::*;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
 use rustyline::completion::{Completer, Pair};
 use rustyline::error::ReadlineError;
 use rustyline::hint::Hinter;
@@ -17,7 +31,9 @@ use rustyline::{Config, CompletionType, Context, Editor, Helper, Highlighter, Va
 /// Available slash commands for tab-completion.
 const COMMANDS: &[&str] = &[
     "/help", "/quit", "/exit", "/clear", "/refine",
-    "/save", "/history", "/stats", "/list", "/run", "/provider", "/lint", "/security", "/dashboard",
+    "/save", "/history", "/tokens", "/stats", "/list", "/run", "/provider", "/lint", "/security", "/format",
+    "/dashboard", "/template", "/delete", "/model", "/retry", "/doctor", "/rerun", "/search",
+    "/batch", "/open", "/explain", "/docstring", "/clean",
 ];
 
 /// Rustyline helper providing slash-command tab-completion and inline hints.
@@ -82,11 +98,11 @@ pub fn print_banner() {
    ██║        ██║      ██║   ██║  ██║╚██████╔╝██║ ╚████║
    ╚═╝        ╚═╝      ╚═╝   ╚═╝  ╚═╝ ╚═════╝ ╚═╝  ╚═══╝
     "#;
-    println!("{}", art.bright_cyan().bold());
-    println!("    {}", "MAKER BOT v0.3.0 — AI Code Generator".bright_white());
+    println!("{}", art.frame().bold());
+    println!("    {}", "MAKER BOT v0.3.0 — AI Code Generator".emphasis());
     println!();
-    println!("    {} Type {} for command list", "ℹ".cyan(), "/help".bold().white());
-    println!("    {} Type {} to quit", "ℹ".cyan(), "/quit".bold().white());
+    println!("    {} Type {} for command list", "ℹ".cyan(), "/help".bold().emphasis());
+    println!("    {} Type {} to quit", "ℹ".cyan(), "/quit".bold().emphasis());
     println!();
 }
 
@@ -110,50 +126,133 @@ pub fn confirm(question: &str) -> bool {
     ans.to_lowercase().starts_with('y')
 }
 
+/// Like [`confirm`], but answers "yes" without asking when `auto` is set.
+pub fn confirm_or_auto(question: &str, auto: bool) -> bool {
+    if auto {
+        println!("{question} (y/n) : {}", "y (auto)".dimmed());
+        return true;
+    }
+    confirm(question)
+}
+
 // Display function for generated Python code
 pub fn display_code(code: &str) {
     let border = "────────────────────────────────────────────────────────".bright_black();
     println!("\n{}", border);
-    println!("  {}", "Generated Python Code".bright_cyan().bold());
+    println!("  {}", "Generated Code".frame().bold());
     println!("{}", border);
     
-    // Simple syntax highlighting for Python
-    for (i, line) in code.lines().enumerate() {
+    for (i, line) in highlight_lines(code).iter().enumerate() {
         let line_num = format!("{:3} │", i + 1).bright_black();
-        let trimmed = line.trim_start();
-        let highlighted = if trimmed.starts_with('#') {
-            line.bright_green() // Comments green
-        } else if trimmed.starts_with("def ") || trimmed.starts_with("class ") {
-            line.bright_yellow()
-        } else if trimmed.starts_with("import ") || trimmed.starts_with("from ") {
-            line.bright_magenta()
-        } else if trimmed.contains("print(") {
-            line.cyan()
-        } else {
-            line.white()
-        };
-        println!("{} {}", line_num, highlighted);
+        println!("{} {}", line_num, line);
     }
     println!("{}", border);
     println!();
 }
 
-/// Trim conversation history to at most `max` messages, dropping the oldest
-/// user/assistant pairs first.
-pub fn trim_history(history: &mut Vec<Message>, max: usize) {
-    while history.len() > max {
+/// Enforce `max_prompt_chars` before a prompt is sent: warn about an
+/// oversized prompt and offer to truncate it. Returns `None` if the user
+/// declines, so nothing is sent.
+fn check_prompt_length(config: &AppConfig, prompt: String) -> Option<String> {
+    let Some(truncated) = truncate_prompt(&prompt, config.max_prompt_chars) else {
+        return Some(prompt);
+    };
+    println!(
+        "{} {} characters exceeds max_prompt_chars ({}); the model may reject it.",
+        "⚠️  Prompt of".warning(),
+        prompt.chars().count(),
+        config.max_prompt_chars
+    );
+    if confirm_or_auto(&format!("Truncate it to {} characters and send?", config.max_prompt_chars), config.auto_confirm) {
+        Some(truncated.to_string())
+    } else {
+        println!("{}", "Prompt not sent.".warning());
+        None
+    }
+}
+
+/// Trim conversation history to at most `max_messages` messages and
+/// `max_tokens` estimated tokens (0 = no token budget), dropping the oldest
+/// user/assistant pairs first. Leading system messages and the most recent
+/// turn are always kept, even if they alone exceed either limit.
+pub fn trim_history(history: &mut Vec<Message>, max_messages: usize, max_tokens: usize) {
+    let start = history.iter().take_while(|m| m.role == "system").count();
+    let recent = if history.last().is_some_and(|m| m.role == "assistant") { 2 } else { 1 };
+    let max_messages = max_messages.max(start + recent);
+    let over_budget = |history: &[Message]| {
+        max_tokens > 0 && history.iter().map(|m| estimate_tokens(&m.content)).sum::<usize>() > max_tokens
+    };
+
+    while history.len() > max_messages || over_budget(history) {
         // Remove in pairs (user + assistant) from the front
-        if history.len() >= 2 {
-            history.drain(..2);
-        } else {
-            history.remove(0);
+        let removable = history.len().saturating_sub(start + recent);
+        if removable == 0 {
+            break;
         }
+        history.drain(start..start + removable.min(2));
+    }
+}
+
+/// The messages for a `compact_refine` request: the current code as the
+/// assistant's last reply, then the refinement `request`, without the rest
+/// of the conversation.
+pub fn compact_refine_messages(code: &str, request: &Message) -> Vec<Message> {
+    vec![
+        Message { role: "assistant".to_string(), content: code.to_string() },
+        request.clone(),
+    ]
+}
+
+/// Prepare `history` for `/retry`: drop the trailing assistant reply so the
+/// last user message can be sent again. Returns the removed reply (to restore
+/// if the retry fails), or `Err` if there is no user message to retry.
+pub fn prepare_retry(history: &mut Vec<Message>) -> Result<Option<Message>, &'static str> {
+    let popped = if history.last().is_some_and(|m| m.role == "assistant") {
+        history.pop()
+    } else {
+        None
+    };
+    if history.last().is_some_and(|m| m.role == "user") {
+        Ok(popped)
+    } else {
+        history.extend(popped);
+        Err("Nothing to retry. Generate some code first!")
+    }
+}
+
+/// Location of the saved prompt templates: `~/.pymakebot/templates.toml`.
+fn templates_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".pymakebot").join("templates.toml"))
+}
+
+/// Load prompt templates (`name = "prompt"`) from `path`.
+/// A missing file yields an empty set.
+pub fn load_templates(path: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read templates file {:?}", path))?;
+    toml::from_str(&content).with_context(|| format!("Failed to parse templates file {:?}", path))
+}
+
+/// Write prompt templates to `path`, creating its parent directory if needed.
+pub fn save_templates(path: &Path, templates: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        ensure_dir(parent)?;
     }
+    let content = toml::to_string(templates).context("Failed to serialize templates")?;
+    fs::write(path, content).with_context(|| format!("Failed to write templates file {:?}", path))
 }
 
-/// Start a spinner animation in a background thread.
+/// Start a spinner animation on stderr in a background thread.
 /// Returns an `Arc<AtomicBool>` — set it to `false` to stop the spinner.
-fn start_spinner(message: &str) -> Arc<AtomicBool> {
+/// Nothing is drawn when `enabled` is false (`spinner = false` / `--no-spinner`)
+/// or stderr isn't a terminal, so piped output stays clean.
+fn start_spinner(message: &str, enabled: bool) -> Arc<AtomicBool> {
+    if !enabled || !io::stderr().is_terminal() {
+        return Arc::new(AtomicBool::new(false));
+    }
     let running = Arc::new(AtomicBool::new(true));
     let running_clone = running.clone();
     let msg = message.to_string();
@@ -162,14 +261,14 @@ fn start_spinner(message: &str) -> Arc<AtomicBool> {
         let frames = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
         let mut i = 0;
         while running_clone.load(Ordering::Relaxed) {
-            print!("\r{} {} ", frames[i % frames.len()].to_string().cyan(), msg.dimmed());
-            let _ = io::stdout().flush();
+            eprint!("\r{} {} ", frames[i % frames.len()].to_string().cyan(), msg.dimmed());
+            let _ = io::stderr().flush();
             std::thread::sleep(std::time::Duration::from_millis(80));
             i += 1;
         }
         // Clear the spinner line
-        print!("\r{}\r", " ".repeat(msg.len() + 4));
-        let _ = io::stdout().flush();
+        eprint!("\r{}\r", " ".repeat(msg.len() + 4));
+        let _ = io::stderr().flush();
     });
 
     running
@@ -177,9 +276,31 @@ fn start_spinner(message: &str) -> Arc<AtomicBool> {
 
 /// Stop a running spinner.
 fn stop_spinner(handle: &Arc<AtomicBool>) {
-    handle.store(false, Ordering::Relaxed);
-    // Give the spinner thread time to clear the line
-    std::thread::sleep(std::time::Duration::from_millis(100));
+    if handle.swap(false, Ordering::Relaxed) {
+        // Give the spinner thread time to clear the line
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+
+/// Create an executor configured from `config`. `use_docker` is the
+/// resolved Docker availability, which may differ from `config.use_docker`.
+pub(crate) fn build_executor(config: &AppConfig, use_docker: bool) -> CodeExecutor {
+    CodeExecutor::new(&config.generated_dir, use_docker, config.use_venv, &config.python_executable)
+        .expect("Failed to create generated scripts directory")
+        .with_output_limit(config.output_limit_bytes)
+        .with_script_env(config.script_env.clone(), config.inherit_env)
+        .with_lint_options(config.lint_options())
+        .with_security_options(config.security_options())
+        .with_interactive_keywords(config.interactive_keywords.clone())
+        .with_docker_mounts(config.docker_mounts())
+        .with_docker_network(config.docker_network.clone())
+        .with_language(config.language)
+        .with_interactive_timeout(config.interactive_timeout_secs)
+        .with_import_denylist(config.import_denylist.clone())
+        .with_pip_retries(config.pip_max_retries)
+        .with_script_name_template(&config.script_name_template)
+        .with_retention(config.retention_policy())
+        .with_venv_cache(config.venv_cache_dir())
 }
 
 /// Shared initialization context for the REPL, used by both standalone
@@ -190,10 +311,55 @@ struct ReplContext {
     metrics: SessionMetrics,
     linter_available: bool,
     security_scanner_available: bool,
+    formatter_available: bool,
     /// Resolved Docker availability (may differ from config if Docker is unavailable).
     use_docker: bool,
 }
 
+/// Mutable state of one interactive session, shared by the REPL loop and
+/// [`process_prompt`].
+struct ReplSession {
+    config: AppConfig,
+    executor: CodeExecutor,
+    logger: Logger,
+    metrics: SessionMetrics,
+    linter_available: bool,
+    security_scanner_available: bool,
+    formatter_available: bool,
+    dashboard: Option<Arc<DashboardState>>,
+    /// Conversation history for multi-turn refinement
+    conversation_history: Vec<Message>,
+    last_generated_code: String,
+    /// Most recent generation prompt, as typed: names the scripts it
+    /// produces (refines and auto-fixes included) and backs `/template save`
+    last_user_prompt: String,
+    /// Last executed script, its stdin redirect and mode (for `/rerun`)
+    last_run: Option<(String, Option<String>, ExecutionMode)>,
+    /// Last synced metrics for delta-based dashboard updates
+    last_synced_metrics: SessionMetrics,
+}
+
+impl ReplSession {
+    fn new(config: &AppConfig, ctx: ReplContext, dashboard: Option<Arc<DashboardState>>) -> Self {
+        Self {
+            // Session-local copy so /model can switch models without a restart
+            config: config.clone(),
+            executor: ctx.executor,
+            logger: ctx.logger,
+            metrics: ctx.metrics,
+            linter_available: ctx.linter_available,
+            security_scanner_available: ctx.security_scanner_available,
+            formatter_available: ctx.formatter_available,
+            dashboard,
+            conversation_history: Vec::new(),
+            last_generated_code: String::new(),
+            last_user_prompt: String::new(),
+            last_run: None,
+            last_synced_metrics: SessionMetrics::new(),
+        }
+    }
+}
+
 /// Validate provider, check tool availability, create executor/logger.
 /// Returns `None` if provider configuration is invalid (errors are printed).
 fn init_repl_context(config: &AppConfig) -> Option<ReplContext> {
@@ -206,24 +372,43 @@ fn init_repl_context(config: &AppConfig) -> Option<ReplContext> {
         }
     };
     match provider.resolve_api_url(&config.api_url) {
-        Ok(url) => println!("{} {} → {}", "✔ Provider:".green(), provider.display_name().bright_white(), url.dimmed()),
+        Ok(url) => println!("{} {} → {}", "✔ Provider:".green(), provider.display_name().emphasis(), url.dimmed()),
         Err(e) => {
             println!("{} {}", "✖ Provider configuration error:".red().bold(), e);
             return None;
         }
     }
 
-    if config.use_venv {
-        println!("{} {}", "✔".green(), "Virtual environment isolation enabled.".white());
+    // Lint, security scanning, formatting and venvs only apply to Python
+    let is_python = config.language == Language::Python;
+    if !is_python {
+        println!("{} {} {}", "✔".green(), format!("Generating {} scripts.", config.language.name()).emphasis(),
+            "(Python-only checks are skipped)".dimmed());
+    }
+
+    if config.dry_run {
+        println!("{} {}", "✔".green(), "Dry run: code is generated and checked but never executed.".emphasis());
+    }
+
+    if config.offline {
+        println!("{} {}", "✔".green(), "Offline mode: no network connections beyond localhost.".emphasis());
+    }
+
+    if config.auto_confirm {
+        println!("{} {}", "✔".green(), "Auto-confirm: scripts run and dependencies install without asking.".emphasis());
+    }
+
+    if config.use_venv && is_python {
+        println!("{} {}", "✔".green(), "Virtual environment isolation enabled.".emphasis());
     }
 
     // Check linter availability
-    let linter_available = if config.use_linting {
+    let linter_available = if config.use_linting && is_python {
         if CodeExecutor::check_linter_available() {
-            println!("{} {}", "✔".green(), "Linting enabled (ruff).".white());
+            println!("{} {}", "✔".green(), "Linting enabled (ruff).".emphasis());
             true
         } else {
-            println!("{} Linting enabled but ruff not found. Install with: pip install ruff", "⚠".yellow());
+            println!("{} Linting enabled but ruff not found. Install with: pip install ruff", "⚠".warning());
             println!("  {} Linting will be skipped.", "ℹ".blue());
             false
         }
@@ -232,12 +417,12 @@ fn init_repl_context(config: &AppConfig) -> Option<ReplContext> {
     };
 
     // Check security scanner (bandit) availability
-    let security_scanner_available = if config.use_security_check {
+    let security_scanner_available = if config.use_security_check && is_python {
         if CodeExecutor::check_security_scanner_available() {
-            println!("{} {}", "✔".green(), "Security scanning enabled (bandit).".white());
+            println!("{} {}", "✔".green(), "Security scanning enabled (bandit).".emphasis());
             true
         } else {
-            println!("{} Security scanning enabled but bandit not found. Install with: pip install bandit", "⚠".yellow());
+            println!("{} Security scanning enabled but bandit not found. Install with: pip install bandit", "⚠".warning());
             println!("  {} Security scanning will be skipped.", "ℹ".blue());
             false
         }
@@ -245,21 +430,45 @@ fn init_repl_context(config: &AppConfig) -> Option<ReplContext> {
         false
     };
 
+    // Check formatter (ruff format / black) availability
+    let formatter_available = if !is_python {
+        false
+    } else if config.format_generated_code {
+        if CodeExecutor::check_formatter_available() {
+            println!("{} {}", "✔".green(), "Auto-formatting enabled (ruff format / black).".emphasis());
+            true
+        } else {
+            println!("{} Auto-formatting enabled but neither ruff nor black was found. Install with: pip install ruff", "⚠".warning());
+            println!("  {} Formatting will be skipped.", "ℹ".blue());
+            false
+        }
+    } else {
+        CodeExecutor::check_formatter_available()
+    };
+
     // If Docker mode is enabled, verify Docker is available; fall back to host execution if not
     let use_docker = if config.use_docker {
         print!("{} Checking Docker availability...", "⟳".dimmed());
         std::io::Write::flush(&mut std::io::stdout()).ok();
-        match CodeExecutor::check_docker_available() {
+        let available = CodeExecutor::check_docker_daemon().and_then(|()| {
+            print!("\r\x1b[2K");
+            match CodeExecutor::check_sandbox_image() {
+                // Offer to build a missing image instead of falling back straight away
+                Err(e) if confirm(&format!("{} Build it now?", e)) => CodeExecutor::build_sandbox_image(),
+                other => other,
+            }
+        });
+        match available {
             Ok(()) => {
                 print!("\r\x1b[2K");
-                println!("{} {}", "✔".green(), "Docker sandbox mode enabled.".white());
+                println!("{} {}", "✔".green(), "Docker sandbox mode enabled.".emphasis());
                 true
             }
             Err(e) => {
                 print!("\r\x1b[2K");
                 println!("{} {}", "✖ Docker sandbox not available:".red().bold(), e);
-                println!("  {} Falling back to host execution.", "⚠".yellow());
-                println!("  {} To enable Docker, run: docker build -t python-sandbox .", "ℹ".blue());
+                println!("  {} Falling back to host execution.", "⚠".warning());
+                println!("  {} To enable Docker, run: pymakebot build-sandbox", "ℹ".blue());
                 false
             }
         }
@@ -267,9 +476,17 @@ fn init_repl_context(config: &AppConfig) -> Option<ReplContext> {
         false
     };
 
-    let executor = CodeExecutor::new(&config.generated_dir, use_docker, config.use_venv, &config.python_executable)
-        .expect("Failed to create generated scripts directory");
-    let logger = Logger::new(&config.log_dir).expect("Failed to create logger");
+    let executor = build_executor(config, use_docker);
+    let pruned = executor.prune_generated();
+    if !pruned.is_empty() {
+        println!("{} {}", "🧹 Pruned old generated scripts:".bright_black(), pruned.len());
+    }
+    let logger = Logger::new(&config.log_dir)
+        .expect("Failed to create logger")
+        .with_format(config.log_format)
+        .with_redaction(config.log_redact_secrets)
+        .with_max_file_bytes(config.max_log_file_bytes)
+        .with_max_files(config.max_log_files);
     let metrics = SessionMetrics::new();
 
     Some(ReplContext {
@@ -278,10 +495,310 @@ fn init_repl_context(config: &AppConfig) -> Option<ReplContext> {
         metrics,
         linter_available,
         security_scanner_available,
+        formatter_available,
         use_docker,
     })
 }
 
+/// Check every external tool and the provider connection, printing a
+/// checklist (`pymakebot doctor` / `/doctor`). Tools the current config
+/// relies on are marked ✗ when missing; optional ones only get a ⚠.
+/// Returns whether everything the config needs is available.
+pub async fn run_doctor(config: &AppConfig) -> bool {
+    let is_python = config.language == Language::Python;
+    let tool_config = config.clone();
+    let tools = tokio::task::spawn_blocking(move || {
+        let config = tool_config;
+        let found = |available: bool, name: &str| {
+            if available { Ok(format!("{} found", name)) } else { Err(format!("{} not found", name)) }
+        };
+        let interpreter = config.language.interpreter().unwrap_or(&config.python_executable);
+        vec![
+            (
+                format!("{} interpreter", config.language.name()),
+                CodeExecutor::interpreter_version(interpreter).map_err(|e| e.to_string()),
+                true,
+            ),
+            (
+                "Docker sandbox".to_string(),
+                CodeExecutor::check_docker_available()
+                    .map(|()| "daemon running, python-sandbox image present".to_string())
+                    .map_err(|e| e.to_string()),
+                config.use_docker,
+            ),
+            (
+                "Linter".to_string(),
+                found(CodeExecutor::check_linter_available(), "ruff"),
+                config.use_linting && is_python,
+            ),
+            (
+                "Security scanner".to_string(),
+                found(CodeExecutor::check_security_scanner_available(), "bandit"),
+                config.use_security_check && is_python,
+            ),
+            (
+                "Formatter".to_string(),
+                found(CodeExecutor::check_formatter_available(), "ruff format or black"),
+                config.format_generated_code && is_python,
+            ),
+            ("Test runner".to_string(), found(CodeExecutor::check_pytest_available(), "pytest"), false),
+        ]
+    })
+    .await
+    .unwrap_or_default();
+
+    let provider = api::probe_provider(config).await.map_err(|e| format!("{:#}", e));
+
+    println!("\n{}", "━━━━━━━━━━━━━━ Doctor ━━━━━━━━━━━━━━".frame().bold());
+    let mut healthy = true;
+    for (name, outcome, required) in tools.into_iter().chain([("LLM provider".to_string(), provider, true)]) {
+        match outcome {
+            Ok(detail) => println!("  {} {:<18} {}", "✔".green(), name, detail.dimmed()),
+            Err(detail) if required => {
+                healthy = false;
+                println!("  {} {:<18} {}", "✗".red().bold(), name, detail.red());
+            }
+            Err(detail) => println!("  {} {:<18} {}", "⚠".warning(), name, format!("{} (optional)", detail).dimmed()),
+        }
+    }
+    println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".frame());
+    if healthy {
+        println!("{}", "✓ Everything the current configuration needs is available.".green());
+    } else {
+        println!("{}", "✗ Some required tools are missing or unreachable.".red());
+    }
+    healthy
+}
+
+/// Outcome of a one-shot generation (`pymakebot generate`), printed as a
+/// single JSON object with `--json`.
+#[derive(Debug, Serialize)]
+pub struct GenerationReport {
+    pub code: String,
+    pub script_path: String,
+    pub syntax_error: Option<String>,
+    /// `None` when linting is disabled or ruff isn't installed.
+    pub lint: Option<LintResult>,
+    /// `None` when scanning is disabled or bandit isn't installed.
+    pub security: Option<SecurityResult>,
+    pub dependencies: Vec<String>,
+    pub execution: Option<ExecutionReport>,
+    /// Why the script wasn't executed, if it wasn't.
+    pub skipped: Option<String>,
+}
+
+impl GenerationReport {
+    /// Whether the script ran and exited with status 0 — or, with
+    /// `dry_run`, passed the syntax check without executing.
+    pub fn passed(&self, dry_run: bool) -> bool {
+        let executed_ok = self.execution.as_ref().map_or(dry_run, |e| e.exit_code == Some(0));
+        self.syntax_error.is_none() && executed_ok
+    }
+}
+
+/// Captured result of the execution in a [`GenerationReport`].
+#[derive(Debug, Serialize)]
+pub struct ExecutionReport {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub timing: ExecutionTiming,
+}
+
+/// Generate code for a single prompt without any REPL interaction: write,
+/// check and (unless something blocks it) execute the script. Nothing is
+/// asked — lint errors, blocking security findings, interactive scripts and
+/// `dry_run` skip execution, and dependencies are only installed with
+/// `auto_install_deps` or `auto_confirm`.
+pub async fn generate_once(config: &AppConfig, prompt: &str) -> anyhow::Result<GenerationReport> {
+    let messages = vec![Message { role: "user".to_string(), content: prompt.to_string() }];
+    let raw_response = api::generate_code_with_history(&messages, config, &CancellationToken::new()).await?;
+
+    let config = config.clone();
+    let prompt = prompt.to_string();
+    tokio::task::spawn_blocking(move || check_and_execute(&config, &prompt, &raw_response))
+        .await
+        .context("Generation task panicked")?
+}
+
+/// Blocking half of [`generate_once`].
+fn check_and_execute(config: &AppConfig, prompt: &str, raw_response: &str) -> anyhow::Result<GenerationReport> {
+    let is_python = config.language == Language::Python;
+    let use_docker = config.use_docker && CodeExecutor::check_docker_available().is_ok();
+    let executor = build_executor(config, use_docker);
+
+    let script_path = match extract_project(raw_response) {
+        Some(files) => executor.write_project(&files)?,
+        None => executor.write_script_for_prompt(&extract_python_code(raw_response), Some(prompt))?,
+    };
+    if is_python && config.format_generated_code && CodeExecutor::check_formatter_available() {
+        let _ = executor.format_code(&script_path);
+    }
+    let code = fs::read_to_string(&script_path)?;
+
+    let mut report = GenerationReport {
+        code: code.clone(),
+        script_path: script_path.display().to_string(),
+        syntax_error: None,
+        lint: None,
+        security: None,
+        dependencies: executor.detect_script_dependencies(&code, &script_path),
+        execution: None,
+        skipped: None,
+    };
+    let mut timing = ExecutionTiming::default();
+
+    if let Err(e) = executor.syntax_check(&script_path) {
+        report.syntax_error = Some(e);
+        report.skipped = Some("syntax error".to_string());
+        return Ok(report);
+    }
+    if is_python && config.use_linting && CodeExecutor::check_linter_available() {
+        let (lint, lint_ms) = timed(|| executor.lint_check(&script_path));
+        timing.lint_ms = lint_ms;
+        report.lint = lint.ok();
+    }
+    if is_python && config.use_security_check && CodeExecutor::check_security_scanner_available() {
+        let (security, security_ms) = timed(|| executor.security_check(&script_path));
+        timing.security_ms = security_ms;
+        report.security = security.ok();
+    }
+
+    report.skipped = if config.dry_run {
+        Some("dry run".to_string())
+    } else if report.lint.as_ref().is_some_and(|l| l.has_errors) {
+        Some("lint errors".to_string())
+    } else if report.security.as_ref().is_some_and(|s| s.blocks_at(config.security_block_level)) {
+        Some(format!("security findings at or above {} severity", config.security_block_level.threshold().unwrap_or(SecuritySeverity::High)))
+    } else if executor.needs_interactive_mode(&code) {
+        Some("script needs interactive mode".to_string())
+    } else {
+        None
+    };
+    if report.skipped.is_some() {
+        return Ok(report);
+    }
+
+    let (venv, venv_ms) = timed(|| executor.prepare_venv(&report.dependencies));
+    timing.venv_ms = venv_ms;
+    let (venv, deps_ready) = venv.unwrap_or(None).unzip();
+    if !report.dependencies.is_empty() && deps_ready != Some(true) && (config.auto_install_deps || config.auto_confirm) {
+        let (_, install_ms) = timed(|| executor.install_packages(&report.dependencies, venv.as_deref()));
+        timing.install_ms = install_ms;
+    }
+    let result = executor.execute_script(
+        &script_path, ExecutionMode::Captured, config.execution_timeout_secs, venv.as_deref(), &report.dependencies,
+    );
+    if let Some(ref venv_path) = venv {
+        executor.cleanup_venv(venv_path);
+    }
+    let result = result?;
+    timing.run_ms = result.timing.run_ms;
+    save_execution_record(config, &result, timing, Some(prompt), report.lint.as_ref(), report.security.as_ref());
+    report.execution = Some(ExecutionReport {
+        stdout: result.stdout,
+        stderr: result.stderr,
+        exit_code: result.exit_code,
+        timing,
+    });
+    Ok(report)
+}
+
+/// Scan `script_path` with bandit and display the findings. Returns whether
+/// to go on — findings at or above `security_block_level` ask first, except
+/// with `dry_run` — and the scan result for the execution report.
+fn security_gate(
+    executor: &CodeExecutor,
+    config: &AppConfig,
+    script_path: &Path,
+    timing: &mut ExecutionTiming,
+) -> (bool, Option<SecurityResult>) {
+    let (security_outcome, security_ms) = timed(|| executor.security_check(script_path));
+    timing.security_ms += security_ms;
+    match security_outcome {
+        Ok(sec_result) => {
+            display_security_results(&sec_result, script_path);
+            let proceed = !sec_result.blocks_at(config.security_block_level)
+                || config.dry_run
+                || confirm(&format!(
+                    "Security issues at or above {} severity found. Proceed anyway?",
+                    config.security_block_level.threshold().unwrap_or(SecuritySeverity::High)
+                ));
+            (proceed, Some(sec_result))
+        }
+        Err(e) => {
+            println!("{} {}", "⚠️  Security scan failed:".warning(), e);
+            println!("{}", "Proceeding without security scanning...".dimmed());
+            (true, None)
+        }
+    }
+}
+
+/// With `write_execution_report`, save the `<script>.result.json` audit
+/// record of a run. Failures are reported on stderr and never abort the run.
+fn save_execution_record(
+    config: &AppConfig,
+    result: &CodeExecutionResult,
+    timing: ExecutionTiming,
+    prompt: Option<&str>,
+    lint: Option<&LintResult>,
+    security: Option<&SecurityResult>,
+) {
+    if !config.write_execution_report {
+        return;
+    }
+    let mut record = ExecutionRecord::new(result, timing, &config.model, &config.provider);
+    record.prompt = prompt;
+    record.lint = lint;
+    record.security = security;
+    if let Err(e) = record.write() {
+        eprintln!("{} {:#}", "⚠️  Could not write the execution report:".warning(), e);
+    }
+}
+
+/// With `verbose`, print the model's reply as received, before code extraction.
+fn print_raw_response(config: &AppConfig, raw_response: &str) {
+    if config.verbose {
+        println!("\n{}", "── Raw model response ──".dimmed());
+        println!("{}", raw_response.dimmed());
+        println!("{}", "────────────────────────".dimmed());
+    }
+}
+
+/// Print a [`GenerationReport`] for humans (the non-`--json` output).
+pub fn print_report(report: &GenerationReport) {
+    display_code(&report.code);
+    println!("{} {}", "Script saved at:".dimmed(), report.script_path);
+    if let Some(ref e) = report.syntax_error {
+        println!("{} {}", "✗ Syntax error detected:".red().bold(), e);
+    }
+    if let Some(ref lint) = report.lint {
+        display_lint_results(lint, Path::new(&report.script_path));
+    }
+    if let Some(ref security) = report.security {
+        display_security_results(security, Path::new(&report.script_path));
+    }
+    if !report.dependencies.is_empty() {
+        println!("{} {}", "Detected dependencies:".warning(), report.dependencies.join(", ").warning());
+    }
+    if let Some(ref reason) = report.skipped {
+        println!("{} {}", "Not executed:".warning(), reason);
+    }
+    if let Some(ref execution) = report.execution {
+        println!("\n{}", "━━━━━━━━━━━ Execution Result ━━━━━━━━━━━".bright_blue().bold());
+        if !execution.stdout.is_empty() {
+            println!("\n{}:", "STDOUT".green().bold());
+            println!("{}", execution.stdout);
+        }
+        if !execution.stderr.is_empty() {
+            println!("\n{}:", "STDERR".red().bold());
+            println!("{}", execution.stderr);
+        }
+        println!("\n{} {}  {} {}", "Exit code:".dimmed(), exit_code_label(execution.exit_code), "Timing:".dimmed(), execution.timing.to_string().dimmed());
+        println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".bright_blue());
+    }
+}
+
 // Interactive REPL entry point
 pub async fn start_repl(config: &AppConfig) {
     print_banner();
@@ -295,7 +812,7 @@ pub async fn start_repl(config: &AppConfig) {
         None => return,
     };
 
-    start_repl_loop(config, ctx.executor, ctx.logger, ctx.metrics, ctx.linter_available, ctx.security_scanner_available, None).await;
+    start_repl_loop(config, ctx, None).await;
 }
 
 /// Start the REPL with the web dashboard running in the background.
@@ -315,37 +832,114 @@ pub async fn start_repl_with_dashboard(config: &AppConfig) {
     };
 
     // Create a second executor for the dashboard's REST API
-    let dashboard_executor = CodeExecutor::new(
-        &config.generated_dir, ctx.use_docker, config.use_venv, &config.python_executable
-    ).expect("Failed to create generated scripts directory");
+    let dashboard_executor = build_executor(config, ctx.use_docker);
 
     // Create shared dashboard state and spawn the web server
-    let state = DashboardState::new(config.clone(), dashboard_executor);
+    let state = match crate::dashboard::state::default_sessions_path() {
+        Some(path) => DashboardState::with_sessions_file(config.clone(), dashboard_executor, path),
+        None => DashboardState::new(config.clone(), dashboard_executor),
+    };
+    let dashboard_host = config.dashboard_host.clone();
     let dashboard_port = config.dashboard_port;
 
     let server_state = state.clone();
     tokio::spawn(async move {
-        if let Err(e) = crate::dashboard::start_dashboard(server_state, dashboard_port).await {
+        if let Err(e) = crate::dashboard::start_dashboard(server_state, &dashboard_host, dashboard_port).await {
             eprintln!("{} {}", "✗ Dashboard server error:".red(), e);
         }
     });
 
     println!("{} {}",
         "✓ Dashboard running at:".green(),
-        format!("http://localhost:{}", dashboard_port).bright_white().underline());
+        dashboard_url(config).emphasis().underline());
+    if is_unspecified_host(&config.dashboard_host) && config.dashboard_token().is_none() {
+        println!("{}", format!(
+            "⚠ WARNING: dashboard is bound to {} and reachable from other machines. \
+             It has no authentication and can run arbitrary Python code! Set dashboard_token.",
+            config.dashboard_host
+        ).red().bold());
+    }
+
+    start_repl_loop(config, ctx, Some(state)).await;
+}
+
+/// Browser URL for the dashboard. Wildcard binds are shown as `localhost`,
+/// and the token (if any) is included so the link opens authenticated.
+fn dashboard_url(config: &AppConfig) -> String {
+    let host = if is_unspecified_host(&config.dashboard_host) {
+        "localhost"
+    } else {
+        config.dashboard_host.as_str()
+    };
+    match config.dashboard_token() {
+        Some(token) => format!("http://{}:{}/?token={}", host, config.dashboard_port, token),
+        None => format!("http://{}:{}", host, config.dashboard_port),
+    }
+}
+
+/// Whether `host` binds every interface (`0.0.0.0` or `::`).
+fn is_unspecified_host(host: &str) -> bool {
+    host.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_unspecified())
+}
+
+/// Cancels the REPL's in-flight LLM request when Ctrl-C is pressed.
+static ACTIVE_GENERATION: std::sync::Mutex<Option<CancellationToken>> = std::sync::Mutex::new(None);
+
+/// Send the conversation to the LLM so that Ctrl-C (see
+/// [`install_sigint_guard`]) cancels the request instead of exiting.
+async fn generate_interruptible(messages: &[Message], config: &AppConfig) -> Result<String, ApiError> {
+    let cancel = CancellationToken::new();
+    *ACTIVE_GENERATION.lock().unwrap() = Some(cancel.clone());
+    let result = api::generate_code_with_history(messages, config, &cancel).await;
+    ACTIVE_GENERATION.lock().unwrap().take();
+    result
+}
+
+/// Ask the LLM to explain `code`, cancellable with Ctrl-C like a generation.
+async fn explain_interruptible(code: &str, config: &AppConfig) -> Result<String, ApiError> {
+    let cancel = CancellationToken::new();
+    *ACTIVE_GENERATION.lock().unwrap() = Some(cancel.clone());
+    let result = api::explain_code(code, config, &cancel).await;
+    ACTIVE_GENERATION.lock().unwrap().take();
+    result
+}
 
-    start_repl_loop(config, ctx.executor, ctx.logger, ctx.metrics, ctx.linter_available, ctx.security_scanner_available, Some(state)).await;
+/// Keep Ctrl-C from killing the bot while an interactive script is running
+/// or a generation is in flight.
+///
+/// The terminal delivers SIGINT to the whole foreground process group, so the
+/// script still receives it and stops; the bot just returns to the prompt.
+/// During a generation, Ctrl-C cancels the request. Otherwise it exits as before.
+fn install_sigint_guard() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if let Some(cancel) = ACTIVE_GENERATION.lock().unwrap().take() {
+                cancel.cancel();
+                continue;
+            }
+            if !interactive_child_running() {
+                println!("\nGoodbye!");
+                std::process::exit(130);
+            }
+        }
+    });
 }
 
 async fn start_repl_loop(
     config: &AppConfig,
-    executor: CodeExecutor,
-    logger: Logger,
-    mut metrics: SessionMetrics,
-    linter_available: bool,
-    security_scanner_available: bool,
+    ctx: ReplContext,
     dashboard: Option<Arc<DashboardState>>,
 ) {
+    let mut session = ReplSession::new(config, ctx, dashboard);
+
+    install_sigint_guard();
+
+    // Warm up a local Ollama model in the background; failures surface on the first request
+    let preload_config = config.clone();
+    tokio::spawn(async move {
+        let _ = api::preload_model(&preload_config).await;
+    });
+
     // Set up rustyline editor with tab-completion
     let rl_config = Config::builder()
         .auto_add_history(true)
@@ -355,17 +949,32 @@ async fn start_repl_loop(
     let mut rl = Editor::with_config(rl_config).expect("Failed to create line editor");
     rl.set_helper(Some(CommandCompleter));
 
-    // Conversation history for multi-turn refinement
-    let mut conversation_history: Vec<Message> = Vec::new();
-    let mut last_generated_code = String::new();
-
-    // Track last synced metrics for delta-based dashboard updates
-    let mut last_synced_metrics = SessionMetrics::new();
+    // Text to pre-fill the next input line with (from `/template use`)
+    let mut pending_input: Option<String> = None;
 
     loop {
+        let ReplSession {
+            config,
+            executor,
+            logger,
+            metrics,
+            linter_available,
+            security_scanner_available,
+            formatter_available,
+            dashboard,
+            conversation_history,
+            last_generated_code,
+            last_user_prompt,
+            last_run,
+            last_synced_metrics,
+        } = &mut session;
+
         // Two-line prompt for better visibility
-        let prompt = format!("\n{} {}\n{} ", "╭──".bright_black(), "🤖".yellow(), "╰── ➤".bright_magenta());
-        let readline = rl.readline(&prompt);
+        let prompt = format!("\n{} {}\n{} ", "╭──".bright_black(), "🤖".warning(), "╰── ➤".bright_magenta());
+        let readline = match pending_input.take() {
+            Some(initial) => rl.readline_with_initial(&prompt, (&initial, "")),
+            None => rl.readline(&prompt),
+        };
         let prompt = match readline {
             Ok(line) => line.trim().to_string(),
             Err(ReadlineError::Interrupted | ReadlineError::Eof) => {
@@ -394,64 +1003,206 @@ async fn start_repl_loop(
             println!("  {bar} {}         Show this help output", "/help".green().bold());
             println!("  {bar} {}        Clear conversation history", "/clear".green().bold());
             println!("  {bar} {}       Refine the last generated code", "/refine".green().bold());
-            println!("  {bar} {} <file> Save last code to a file", "/save".green().bold());
+            println!("  {bar} {} [temp] Regenerate the last answer (default: temperature +0.2)", "/retry".green().bold());
+            println!("  {bar} {} <file> [--reqs] Save last code (and optionally requirements.txt)", "/save".green().bold());
             println!("  {bar} {}      Show conversation history", "/history".green().bold());
-            println!("  {bar} {}        Show session statistics", "/stats".green().bold());
+            println!("  {bar} {}       Estimate the context size of the next request", "/tokens".green().bold());
+            println!("  {bar} {} [reset] Show per-model session statistics, or reset them", "/stats".green().bold());
             println!("  {bar} {}         List all previously generated scripts", "/list".green().bold());
-            println!("  {bar} {} <file>  Execute a previously generated script", "/run".green().bold());
-            println!("  {bar} {}     Show current LLM provider info", "/provider".green().bold());
+            println!("  {bar} {} <term> Find scripts by name or content", "/search".green().bold());
+            println!("  {bar} {} <file> [< input]  Execute a previously generated script (optional stdin file)", "/run".green().bold());
+            println!("  {bar} {}        Re-run the last executed script without regenerating", "/rerun".green().bold());
+            println!("  {bar} {} <file> Generate and run every prompt in a file, then summarize", "/batch".green().bold());
+            println!("  {bar} {} <file> Delete a previously generated script", "/delete".green().bold());
+            println!("  {bar} {}        Delete old scripts per generated_max_files / generated_max_age_days", "/clean".green().bold());
+            println!("  {bar} {} [name [url]] Show provider info or switch provider", "/provider".green().bold());
+            println!("  {bar} {} [name] List available models or switch to another", "/model".green().bold());
             println!("  {bar} {}         Lint the last generated code (ruff)", "/lint".green().bold());
             println!("  {bar} {}     Run security scan (bandit)", "/security".green().bold());
+            println!("  {bar} {}       Format the last generated code (ruff/black)", "/format".green().bold());
+            println!("  {bar} {}         Edit the last generated code in $EDITOR, then offer to run it", "/open".green().bold());
+            println!("  {bar} {}      Explain the last generated code section by section", "/explain".green().bold());
+            println!("  {bar} {}    Add docstrings and type hints to the last generated code", "/docstring".green().bold());
             println!("  {bar} {}    Show dashboard URL", "/dashboard".green().bold());
+            println!("  {bar} {}       Check external tools and the provider connection", "/doctor".green().bold());
+            println!("  {bar} {} save|use|list <name>  Manage reusable prompt templates", "/template".green().bold());
             println!("{}", "  ╰────────────────────────────────────────────".bright_black());
             println!();
             continue;
         }
 
+        if let Some(args) = prompt.strip_prefix("/template").filter(|a| a.is_empty() || a.starts_with(' ')) {
+            let Some(path) = templates_path() else {
+                println!("{}", "✗ Could not determine home directory for templates.".red());
+                continue;
+            };
+            let mut templates = match load_templates(&path) {
+                Ok(t) => t,
+                Err(e) => {
+                    println!("{} {:#}", "✗ Failed to load templates:".red(), e);
+                    continue;
+                }
+            };
+
+            let args = args.trim();
+            let (action, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            let (name, text) = rest.trim().split_once(char::is_whitespace).unwrap_or((rest.trim(), ""));
+
+            match action {
+                "" | "list" => {
+                    if templates.is_empty() {
+                        println!("{}", "No templates saved. Use /template save <name> to create one.".warning());
+                    } else {
+                        println!("\n{}", "  ╭── Prompt Templates ────────────────────────".frame());
+                        for (name, text) in &templates {
+                            println!("  {} {}: {}", "│".frame(), name.emphasis().bold(), text.dimmed());
+                        }
+                        println!("{}", "  ╰────────────────────────────────────────────".frame());
+                    }
+                }
+                "save" => {
+                    if name.is_empty() {
+                        println!("{}", "Usage: /template save <name> [prompt]".warning());
+                        continue;
+                    }
+                    // Save the given text, falling back to the last prompt sent
+                    let text = if !text.trim().is_empty() {
+                        text.trim().to_string()
+                    } else if !last_user_prompt.is_empty() {
+                        last_user_prompt.clone()
+                    } else {
+                        ask_user("Enter the prompt to save: ")
+                    };
+                    if text.is_empty() {
+                        println!("{}", "Save cancelled.".warning());
+                        continue;
+                    }
+                    templates.insert(name.to_string(), text);
+                    match save_templates(&path, &templates) {
+                        Ok(()) => println!("{} {}", "✓ Template saved:".green(), name.emphasis()),
+                        Err(e) => println!("{} {:#}", "✗ Failed to save template:".red(), e),
+                    }
+                }
+                "use" => match templates.get(name) {
+                    Some(text) => pending_input = Some(text.clone()),
+                    None => println!("{} {}", "✗ No template named:".red(), name),
+                },
+                _ => println!("{}", "Usage: /template save <name> [prompt] | use <name> | list".warning()),
+            }
+            continue;
+        }
+
+        if prompt == "/doctor" {
+            run_doctor(config).await;
+            continue;
+        }
+
         if prompt == "/dashboard" {
             if let Some(ref ds) = dashboard {
                 println!("{} {}",
-                    "Dashboard running at:".bright_cyan(),
-                    format!("http://localhost:{}", ds.config.dashboard_port).bright_white().underline());
+                    "Dashboard running at:".frame(),
+                    dashboard_url(&ds.config).emphasis().underline());
             } else {
-                println!("{}", "Dashboard is not enabled. Set enable_dashboard = true in pymakebot.toml".yellow());
+                println!("{}", "Dashboard is not enabled. Set enable_dashboard = true in pymakebot.toml".warning());
+            }
+            continue;
+        }
+
+        if let Some(args) = prompt.strip_prefix("/stats").filter(|a| a.is_empty() || a.starts_with(' ')) {
+            match args.trim() {
+                "" => metrics.display(),
+                "reset" => {
+                    metrics.reset();
+                    // Deltas to the dashboard restart from zero too
+                    *last_synced_metrics = SessionMetrics::new();
+                    println!("{}", "✓ Session statistics reset.".green());
+                }
+                _ => println!("{}", "Usage: /stats [reset]".warning()),
             }
             continue;
         }
 
-        if prompt == "/stats" {
-            metrics.display();
+        if let Some(args) = prompt.strip_prefix("/provider ") {
+            let mut parts = args.split_whitespace();
+            let Some(name) = parts.next() else {
+                println!("{}", "Usage: /provider <huggingface|ollama|openai> [api_url]".warning());
+                continue;
+            };
+            match api::switch_provider(config, name, parts.next()) {
+                Ok(url) => {
+                    let display = Provider::from_config(&config.provider).map_or("", |p| p.display_name());
+                    println!("{} {}", "✓ Provider switched to:".green(), display.emphasis());
+                    println!("  {}  {}", "API URL:".dimmed(), url.emphasis());
+                    println!("  {}    {}", "Model:".dimmed(), config.model.emphasis());
+                    println!("  {}", "Use /model to pick a model for this provider.".dimmed());
+                }
+                Err(e) => println!("{} {:#}", "✗ Could not switch provider:".red(), e),
+            }
             continue;
         }
 
         if prompt == "/provider" {
             if let Ok(p) = Provider::from_config(&config.provider) {
-                println!("\n{}", "LLM Provider Info:".bright_cyan().bold());
-                println!("  {} {}", "Provider:".dimmed(), p.display_name().bright_white());
-                println!("  {}    {}", "Model:".dimmed(), config.model.bright_white());
+                println!("\n{}", "LLM Provider Info:".frame().bold());
+                println!("  {} {}", "Provider:".dimmed(), p.display_name().emphasis());
+                println!("  {}    {}", "Model:".dimmed(), config.model.emphasis());
                 if let Ok(url) = p.resolve_api_url(&config.api_url) {
-                    println!("  {}  {}", "API URL:".dimmed(), url.bright_white());
+                    println!("  {}  {}", "API URL:".dimmed(), url.emphasis());
                 }
                 println!();
             }
             continue;
         }
 
+        if let Some(args) = prompt.strip_prefix("/model").filter(|a| a.is_empty() || a.starts_with(' ')) {
+            let name = args.trim();
+            if !name.is_empty() {
+                config.model = name.to_string();
+                println!("{} {}", "✓ Model switched to:".green(), config.model.emphasis());
+                continue;
+            }
+
+            let provider = match Provider::from_config(&config.provider) {
+                Ok(p) => p,
+                Err(e) => {
+                    println!("{} {}", "✗".red(), e);
+                    continue;
+                }
+            };
+            let spinner = start_spinner("Fetching models...", config.spinner);
+            let models = api::list_models(provider, config.offline).await;
+            stop_spinner(&spinner);
+
+            const MAX_LISTED: usize = 30;
+            println!("\n{}", format!("  ╭── {} Models ───────────────────────", provider.display_name()).frame());
+            for model in models.iter().take(MAX_LISTED) {
+                let marker = if *model == config.model { "●".green() } else { " ".normal() };
+                println!("  {} {} {}", "│".frame(), marker, model.emphasis());
+            }
+            if models.len() > MAX_LISTED {
+                println!("  {} {}", "│".frame(), format!("... and {} more", models.len() - MAX_LISTED).dimmed());
+            }
+            println!("{}", "  ╰────────────────────────────────────────────".frame());
+            println!("  {} {}", "Current:".dimmed(), config.model.emphasis());
+            println!("  {}", "Switch with /model <name>".dimmed());
+            continue;
+        }
+
         // /lint command — run ruff on the last generated code
         if prompt == "/lint" {
             if last_generated_code.is_empty() {
-                println!("{}", "No code to lint. Generate some code first!".yellow());
+                println!("{}", "No code to lint. Generate some code first!".warning());
                 continue;
             }
-            if !linter_available {
-                println!("{}", "Linter (ruff) is not available. Install with: pip install ruff".yellow());
+            if !*linter_available {
+                println!("{}", "Linter (ruff) is not available. Install with: pip install ruff".warning());
                 continue;
             }
             // Write to a temp file for linting
-            match executor.write_script(&last_generated_code) {
+            match executor.write_script(last_generated_code) {
                 Ok(path) => {
                     match executor.lint_check(&path) {
-                        Ok(lint_result) => display_lint_results(&lint_result),
+                        Ok(lint_result) => display_lint_results(&lint_result, &path),
                         Err(e) => println!("{} {}", "✗ Lint error:".red(), e),
                     }
                 }
@@ -463,17 +1214,17 @@ async fn start_repl_loop(
         // /security command — run bandit on the last generated code
         if prompt == "/security" {
             if last_generated_code.is_empty() {
-                println!("{}", "No code to scan. Generate some code first!".yellow());
+                println!("{}", "No code to scan. Generate some code first!".warning());
                 continue;
             }
-            if !security_scanner_available {
-                println!("{}", "Security scanner (bandit) is not available. Install with: pip install bandit".yellow());
+            if !*security_scanner_available {
+                println!("{}", "Security scanner (bandit) is not available. Install with: pip install bandit".warning());
                 continue;
             }
-            match executor.write_script(&last_generated_code) {
+            match executor.write_script(last_generated_code) {
                 Ok(path) => {
                     match executor.security_check(&path) {
-                        Ok(sec_result) => display_security_results(&sec_result),
+                        Ok(sec_result) => display_security_results(&sec_result, &path),
                         Err(e) => println!("{} {}", "✗ Security scan error:".red(), e),
                     }
                 }
@@ -482,183 +1233,511 @@ async fn start_repl_loop(
             continue;
         }
 
-        if prompt == "/clear" {
-            conversation_history.clear();
-            last_generated_code.clear();
-            println!("{}", "✓ Conversation history cleared.".green());
-            continue;
-        }
-
-        if prompt == "/history" {
-            if conversation_history.is_empty() {
-                println!("{}", "No conversation history yet.".yellow());
-            } else {
-                println!("\n{}", "  ╭── Conversation History ────────────────────".bright_cyan());
-                for (i, msg) in conversation_history.iter().enumerate() {
-                    let role_color = if msg.role == "user" {
-                        msg.role.bright_blue()
-                    } else {
-                        msg.role.bright_green()
-                    };
-                    let preview = if msg.content.len() > 80 {
-                        let end = find_char_boundary(&msg.content, 80);
-                        format!("{}...", &msg.content[..end]).replace('\n', " ")
-                    } else {
-                        msg.content.replace('\n', " ")
-                    };
-                    println!("  {} {}. [{}] {}", "│".bright_cyan(), i + 1, role_color, preview.dimmed());
-                }
-                println!("{}", "  ╰────────────────────────────────────────────".bright_cyan());
-                println!();
-            }
-            continue;
-        }
-
-        if prompt.starts_with("/save") {
+        // /format command — run ruff format (or black) on the last generated code
+        if prompt == "/format" {
             if last_generated_code.is_empty() {
-                println!("{}", "No code to save. Generate some code first!".yellow());
+                println!("{}", "No code to format. Generate some code first!".warning());
                 continue;
             }
-
-            let parts: Vec<&str> = prompt.split_whitespace().collect();
-            let filename = if parts.len() > 1 {
-                parts[1].to_string()
-            } else {
-                ask_user("Enter filename (e.g., script.py): ")
-            };
-
-            if filename.is_empty() {
-                println!("{}", "Save cancelled.".yellow());
+            if !*formatter_available {
+                println!("{}", "No formatter (ruff or black) is available. Install with: pip install ruff".warning());
                 continue;
             }
-
-            match fs::write(&filename, &last_generated_code) {
-                Ok(_) => println!("{} {}", "✓ Code saved to:".green(), filename.bright_white()),
-                Err(e) => println!("{} {}", "✗ Failed to save file:".red(), e),
+            match executor.write_script(last_generated_code) {
+                Ok(path) => match executor.format_code(&path) {
+                    Ok(true) => match fs::read_to_string(&path) {
+                        Ok(formatted) => {
+                            *last_generated_code = formatted;
+                            display_code(last_generated_code);
+                            println!("{}", "✓ Code formatted.".green());
+                        }
+                        Err(e) => println!("{} {}", "✗ Failed to read formatted script:".red(), e),
+                    },
+                    Ok(false) => println!("{}", "✓ Code is already formatted.".green()),
+                    Err(e) => println!("{} {}", "✗ Format error:".red(), e),
+                },
+                Err(e) => println!("{} {}", "✗ Failed to write script for formatting:".red(), e),
             }
             continue;
         }
 
-        if prompt == "/list" {
-            match fs::read_dir(&config.generated_dir) {
-                Ok(entries) => {
-                    let mut scripts: Vec<_> = entries
-                        .filter_map(|e| e.ok())
-                        .filter(|e| e.path().extension().is_some_and(|ext| ext == "py"))
-                        .collect();
-
-                    if scripts.is_empty() {
-                        println!("{}", "No generated scripts found.".yellow());
-                    } else {
-                        scripts.sort_by_key(|e| e.file_name());
-                        println!("\n{}", "  ╭── Generated Scripts ───────────────────────".bright_cyan());
-                        for (i, entry) in scripts.iter().enumerate() {
-                            println!("  {} {}. {}", "│".bright_cyan(), i + 1, entry.file_name().to_string_lossy().bright_white());
-                        }
-                        println!("{}", "  ╰────────────────────────────────────────────".bright_cyan());
-                        println!();
+        if prompt == "/explain" {
+            if last_generated_code.is_empty() {
+                println!("{}", "No code to explain. Generate some code first!".warning());
+                continue;
+            }
+            let _ = logger.log_api_request("Explain the last generated code");
+            metrics.record_request(&config.model);
+            let spinner = start_spinner("Explaining code...", config.spinner);
+            let api_started = Instant::now();
+            let api_result = explain_interruptible(last_generated_code, config).await;
+            metrics.record_api_latency(api_started.elapsed());
+            stop_spinner(&spinner);
+
+            // Prose, not code: printed as is and kept out of the conversation
+            match api_result {
+                Ok(explanation) => {
+                    let _ = logger.log_api_response(&explanation);
+                    println!("\n{}", "  ╭── Explanation ─────────────────────────────".frame());
+                    for line in explanation.trim().lines() {
+                        println!("  {} {}", "│".frame(), line);
+                    }
+                    println!("{}", "  ╰────────────────────────────────────────────".frame());
+                    println!();
+                }
+                Err(e) => {
+                    if !matches!(e, ApiError::Cancelled) {
+                        metrics.api_errors += 1;
+                        let _ = logger.log_error(&format!("API error during /explain: {}", e));
                     }
+                    print_api_error("✗ Could not explain the code:", &e);
                 }
-                Err(e) => println!("{} {}", "✖ Failed to list scripts:".red(), e),
             }
             continue;
         }
 
-        if prompt.starts_with("/run") {
-            let parts: Vec<&str> = prompt.split_whitespace().collect();
-            let filename = if parts.len() > 1 {
-                parts[1].to_string()
-            } else {
-                ask_user("Enter script filename (e.g., script_20251209_152023.py): ")
-            };
-
-            if filename.is_empty() {
-                println!("{}", "Run cancelled.".yellow());
+        if prompt == "/docstring" {
+            if last_generated_code.is_empty() {
+                println!("{}", "No code to document. Generate some code first!".warning());
                 continue;
             }
-
-            let script_path = if filename.starts_with(&format!("{}/", config.generated_dir)) {
-                filename
-            } else {
-                format!("{}/{}", config.generated_dir, filename)
+            if executor.language() != Language::Python {
+                println!("{}", "/docstring only supports Python scripts.".warning());
+                continue;
+            }
+            let instruction = format!(
+                "Add PEP 257 docstrings to the module and every function and class, and type hints to every \
+                 function signature, in the code below. Do not change its behavior, names or output.\n\n```python\n{}\n```",
+                last_generated_code
+            );
+            let messages = vec![Message { role: "user".to_string(), content: instruction }];
+            let _ = logger.log_api_request("Add docstrings and type hints");
+            metrics.record_request(&config.model);
+            let spinner = start_spinner("Adding docstrings and type hints...", config.spinner);
+            let api_started = Instant::now();
+            let api_result = generate_interruptible(&messages, config).await;
+            metrics.record_api_latency(api_started.elapsed());
+            stop_spinner(&spinner);
+
+            let raw_response = match api_result {
+                Ok(raw) => raw,
+                Err(e) => {
+                    if !matches!(e, ApiError::Cancelled) {
+                        metrics.api_errors += 1;
+                        let _ = logger.log_error(&format!("API error during /docstring: {}", e));
+                    }
+                    print_api_error("✗ API error:", &e);
+                    continue;
+                }
             };
+            let _ = logger.log_api_response(&raw_response);
+            print_raw_response(config, &raw_response);
+            let documented = extract_python_code(&raw_response);
+            if documented.trim().is_empty() {
+                println!("{}", "✗ No code in the reply; keeping the previous version.".red());
+                continue;
+            }
 
-            match fs::read_to_string(&script_path) {
-                Ok(code) => {
-                    println!("\n{}", format!("Running: {}", script_path).bright_cyan());
+            // Only keep the new version if it still compiles and lints cleanly
+            let path = match executor.write_script(&documented) {
+                Ok(path) => path,
+                Err(e) => {
+                    println!("{} {}", "✗ Failed to write script:".red(), e);
+                    continue;
+                }
+            };
+            if let Err(e) = executor.syntax_check(&path) {
+                println!("{} {}", "✗ The documented code has syntax errors; keeping the previous version:".red(), e);
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            if *linter_available {
+                match executor.lint_check(&path) {
+                    Ok(lint_result) if lint_result.has_errors => {
+                        display_lint_results(&lint_result, &path);
+                        println!("{}", "✗ The documented code has lint errors; keeping the previous version.".red());
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) => println!("{} {}", "⚠️  Lint check failed:".warning(), e),
+                }
+            }
 
-                    // Create a venv for this execution (host mode only)
-                    let venv = executor.create_venv().unwrap_or_else(|e| {
-                        println!("{} {}", "⚠️  Failed to create venv:".yellow(), e);
-                        println!("{}", "Proceeding without virtual environment...".dimmed());
-                        None
-                    });
+            *last_generated_code = documented;
+            // A short form of the request: the previous code is already in the history
+            conversation_history.push(Message {
+                role: "user".to_string(),
+                content: "Add docstrings and type hints without changing behavior.".to_string(),
+            });
+            conversation_history.push(Message { role: "assistant".to_string(), content: last_generated_code.clone() });
+            trim_history(conversation_history, config.max_history_messages, config.max_context_tokens);
+            display_code(last_generated_code);
+            println!("{} {}", "✓ Docstrings and type hints added. Saved to:".green(), path.display().to_string().emphasis());
+            continue;
+        }
 
-                    // Check for dependencies
-                    let deps = executor.detect_dependencies(&code);
-                    if !deps.is_empty() {
-                        println!("\n{} {}",
-                            "⚠️  Detected non-standard dependencies:".yellow(),
-                            deps.join(", ").bright_yellow());
-                        if config.auto_install_deps || confirm("Install these dependencies?") {
-                            if let Err(e) = executor.install_packages(&deps, venv.as_deref()) {
-                                println!("{} {}", "⚠️  Failed to install dependencies:".yellow(), e);
-                                println!("{}", "Proceeding anyway...".dimmed());
-                            }
-                        }
+        if prompt == "/open" {
+            if last_generated_code.is_empty() {
+                println!("{}", "No code to edit. Generate some code first!".warning());
+                continue;
+            }
+            // Reuse the last run script when it holds this code, so edits
+            // don't leave a near-duplicate behind
+            let existing = last_run
+                .as_ref()
+                .map(|(path, _, _)| path.clone())
+                .filter(|path| fs::read_to_string(path).is_ok_and(|code| code == *last_generated_code));
+            let script_path = match existing {
+                Some(path) => path,
+                None => match executor.write_script(last_generated_code) {
+                    Ok(path) => path.display().to_string(),
+                    Err(e) => {
+                        println!("{} {}", "✗ Failed to write script:".red(), e);
+                        continue;
                     }
+                },
+            };
 
-                    // Detect if interactive mode is needed
-                    let mode = if executor.needs_interactive_mode(&code) {
-                        println!("{}", "🎮 Interactive mode detected (pygame/input/GUI)".bright_magenta().bold());
-                        println!("{}", "   Running with inherited stdio for user interaction...".dimmed());
-                        ExecutionMode::Interactive
+            match open_in_editor(Path::new(&script_path)) {
+                Ok(true) => {}
+                Ok(false) => {
+                    println!("{}", "✗ The editor exited with an error; keeping the previous code.".red());
+                    continue;
+                }
+                Err(e) => {
+                    println!("{} {}", "✗ Could not start the editor (set $EDITOR):".red(), e);
+                    continue;
+                }
+            }
+            let edited = match fs::read_to_string(&script_path) {
+                Ok(code) => code,
+                Err(e) => {
+                    println!("{} {}", "✗ Failed to read edited script:".red(), e);
+                    continue;
+                }
+            };
+            if edited == *last_generated_code {
+                println!("{}", "No changes made.".dimmed());
+                continue;
+            }
+
+            // Follow-ups and /refine should build on the edited version
+            if let Some(reply) = conversation_history.last_mut().filter(|m| m.role == "assistant") {
+                reply.content = edited.clone();
+            }
+            *last_generated_code = edited;
+            display_code(last_generated_code);
+            println!("{} {}", "✓ Edits saved to:".green(), script_path.emphasis());
+
+            // Hand edits get the same checks as generated code before running
+            if let Err(syntax_err) = executor.syntax_check(Path::new(&script_path)) {
+                println!("{} {}", "✗ Syntax error detected:".red().bold(), syntax_err);
+                continue;
+            }
+            if *security_scanner_available
+                && !security_gate(executor, config, Path::new(&script_path), &mut ExecutionTiming::default()).0
+            {
+                continue;
+            }
+
+            if !config.dry_run && confirm_or_auto("Execute the edited script?", config.auto_confirm) {
+                let mode = run_saved_script(
+                    executor, config, logger, metrics, &script_path, last_generated_code, None, None,
+                ).await;
+                *last_run = Some((script_path, None, mode));
+            }
+            continue;
+        }
+
+        if prompt == "/clear" {
+            conversation_history.clear();
+            last_generated_code.clear();
+            println!("{}", "✓ Conversation history cleared.".green());
+            continue;
+        }
+
+        if prompt == "/history" {
+            if conversation_history.is_empty() {
+                println!("{}", "No conversation history yet.".warning());
+            } else {
+                println!("\n{}", "  ╭── Conversation History ────────────────────".frame());
+                for (i, msg) in conversation_history.iter().enumerate() {
+                    let role_color = if msg.role == "user" {
+                        msg.role.bright_blue()
+                    } else {
+                        msg.role.bright_green()
+                    };
+                    let preview = if msg.content.len() > 80 {
+                        let end = find_char_boundary(&msg.content, 80);
+                        format!("{}...", &msg.content[..end]).replace('\n', " ")
                     } else {
-                        ExecutionMode::Captured
+                        msg.content.replace('\n', " ")
                     };
+                    println!("  {} {}. [{}] {}", "│".frame(), i + 1, role_color, preview.dimmed());
+                }
+                println!("{}", "  ╰────────────────────────────────────────────".frame());
+                println!();
+            }
+            continue;
+        }
 
-                    match executor.run_existing_script(&script_path, mode, config.execution_timeout_secs, venv.as_deref(), &deps) {
-                        Ok(result) => {
-                            let success = result.is_success();
-                            if success {
-                                metrics.successful_executions += 1;
-                            } else {
-                                metrics.failed_executions += 1;
-                            }
+        if prompt == "/tokens" {
+            let system = estimate_tokens(&api::system_prompt(config.language));
+            let history: usize = conversation_history.iter().map(|m| estimate_tokens(&m.content)).sum();
+            let bar = "│".frame();
+            println!("\n{}", "  ╭── Context Size (estimated) ────────────────".frame());
+            println!("  {bar} System prompt:    ~{} tokens", system);
+            println!("  {bar} History:          ~{} tokens in {} messages", history, conversation_history.len());
+            println!("  {bar} Next request:     ~{} tokens, plus up to {} for the reply", system + history, config.max_tokens);
+            let percent = (history * 100).checked_div(config.max_context_tokens);
+            if let Some(percent) = percent {
+                println!("  {bar} History budget:   {}% of max_context_tokens ({})", percent, config.max_context_tokens);
+            }
+            println!("{}", "  ╰────────────────────────────────────────────".frame());
+            if percent.is_some_and(|p| p >= 80) {
+                println!("{}", "⚠️  Near the history budget: the oldest turns will be dropped soon. Use /clear to start fresh.".warning());
+            }
+            println!();
+            continue;
+        }
+
+        if prompt.starts_with("/save") {
+            if last_generated_code.is_empty() {
+                println!("{}", "No code to save. Generate some code first!".warning());
+                continue;
+            }
 
-                            let _ = logger.log_execution(success, &result.stdout);
+            let args: Vec<&str> = prompt.split_whitespace().skip(1).collect();
+            let with_reqs = args.contains(&"--reqs");
+            let filename = match args.iter().find(|a| !a.starts_with("--")) {
+                Some(name) => name.to_string(),
+                None => ask_user("Enter filename (e.g., script.py): "),
+            };
 
-                            println!("\n{}", "━━━━━━━━━━━ Execution Result ━━━━━━━━━━━".bright_blue().bold());
-                            if !result.stdout.is_empty() {
-                                println!("\n{}:", "STDOUT".green().bold());
-                                println!("{}", result.stdout);
-                            }
-                            if !result.stderr.is_empty() {
-                                println!("\n{}:", "STDERR".red().bold());
-                                println!("{}", result.stderr);
-                            }
-                            println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".bright_blue());
-                        }
-                        Err(e) => {
-                            metrics.failed_executions += 1;
-                            let _ = logger.log_error(&format!("Execution error: {}", e));
-                            println!("{} {}", "✗ Execution error:".red(), e);
-                        }
+            if filename.is_empty() {
+                println!("{}", "Save cancelled.".warning());
+                continue;
+            }
+
+            match fs::write(&filename, &last_generated_code) {
+                Ok(_) => println!("{} {}", "✓ Code saved to:".green(), filename.emphasis()),
+                Err(e) => {
+                    println!("{} {}", "✗ Failed to save file:".red(), e);
+                    continue;
+                }
+            }
+
+            // Optionally write the detected dependencies next to the script
+            let deps = executor.detect_dependencies(last_generated_code);
+            if deps.is_empty() {
+                if with_reqs {
+                    println!("{}", "No third-party dependencies detected; requirements.txt not written.".dimmed());
+                }
+                continue;
+            }
+            let reqs_path = Path::new(&filename).with_file_name("requirements.txt");
+            if with_reqs || confirm(&format!("Also write {} ({})?", reqs_path.display(), deps.join(", "))) {
+                match fs::write(&reqs_path, requirements_txt(&deps)) {
+                    Ok(_) => println!("{} {}", "✓ Requirements saved to:".green(), reqs_path.display().to_string().emphasis()),
+                    Err(e) => println!("{} {}", "✗ Failed to write requirements.txt:".red(), e),
+                }
+            }
+            continue;
+        }
+
+        if prompt == "/list" {
+            // Oldest first, so the newest script ends up next to the prompt
+            let scripts = list_generated_scripts(Path::new(&config.generated_dir));
+            if scripts.is_empty() {
+                println!("{}", "No generated scripts found.".warning());
+            } else {
+                println!("\n{}", "  ╭── Generated Scripts ───────────────────────".frame());
+                for (i, (name, created)) in scripts.iter().rev().enumerate() {
+                    let created = created.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S");
+                    println!("  {} {}. {}  {}", "│".frame(), i + 1, name.emphasis(), created.to_string().dimmed());
+                }
+                println!("{}", "  ╰────────────────────────────────────────────".frame());
+                println!();
+            }
+            continue;
+        }
+
+        if let Some(term) = prompt.strip_prefix("/search").filter(|a| a.is_empty() || a.starts_with(' ')) {
+            let term = term.trim();
+            if term.is_empty() {
+                println!("{}", "Usage: /search <term>".warning());
+                continue;
+            }
+            let needle = term.to_lowercase();
+            let dir = Path::new(&config.generated_dir);
+            let matches: Vec<_> = list_generated_scripts(dir)
+                .into_iter()
+                .filter_map(|(name, _)| {
+                    let hit = find_in_file(&dir.join(&name), term);
+                    (hit.is_some() || name.to_lowercase().contains(&needle)).then_some((name, hit))
+                })
+                .collect();
+            if matches.is_empty() {
+                println!("{}", format!("No scripts match '{}'.", term).warning());
+            } else {
+                println!("\n{}", format!("  ╭── Scripts matching '{}' ", term).frame());
+                for (name, hit) in &matches {
+                    println!("  {} {}", "│".frame(), name.emphasis());
+                    if let Some((line, snippet)) = hit {
+                        println!("  {}   {}", "│".frame(), format!("{}: {}", line, snippet).dimmed());
                     }
+                }
+                println!("{}", "  ╰────────────────────────────────────────────".frame());
+                println!();
+            }
+            continue;
+        }
+
+        if let Some(args) = prompt.strip_prefix("/delete").filter(|a| a.is_empty() || a.starts_with(' ')) {
+            let filename = match args.split_whitespace().next() {
+                Some(name) => name.to_string(),
+                None => ask_user("Enter script filename to delete (e.g., script_20251209_152023.py): "),
+            };
+            if filename.is_empty() {
+                println!("{}", "Delete cancelled.".warning());
+                continue;
+            }
+            if !is_generated_script_name(&filename) {
+                println!("{} {}", "✗ Not a generated script name (expected a timestamped name such as script_20251209_152023.py):".red(), filename);
+                continue;
+            }
 
-                    // Clean up the venv
-                    if let Some(ref venv_path) = venv {
-                        executor.cleanup_venv(venv_path);
+            let script_path = Path::new(&config.generated_dir).join(&filename);
+            if !script_path.exists() {
+                println!("{} {}", "✗ Script not found:".red(), script_path.display());
+                continue;
+            }
+            if !confirm(&format!("Delete {}?", script_path.display())) {
+                println!("{}", "Delete cancelled.".warning());
+                continue;
+            }
+            match fs::remove_file(&script_path) {
+                Ok(()) => {
+                    let _ = fs::remove_file(ExecutionRecord::path_for(&script_path));
+                    println!("{} {}", "✓ Deleted:".green(), filename.emphasis());
+                    if let Some(ref ds) = dashboard {
+                        ds.broadcast(ExecutionEvent::ScriptDeleted { filename });
                     }
                 }
+                Err(e) => println!("{} {}", "✗ Failed to delete script:".red(), e),
+            }
+            continue;
+        }
+
+        if prompt == "/clean" {
+            if !config.retention_policy().is_enabled() {
+                println!("{}", "No retention policy set: configure generated_max_files or generated_max_age_days.".warning());
+                continue;
+            }
+            let pruned = executor.prune_generated();
+            if pruned.is_empty() {
+                println!("{}", "Nothing to clean.".dimmed());
+            }
+            for filename in pruned {
+                println!("{} {}", "✓ Deleted:".green(), filename.emphasis());
+                if let Some(ref ds) = dashboard {
+                    ds.broadcast(ExecutionEvent::ScriptDeleted { filename });
+                }
+            }
+            continue;
+        }
+
+        if let Some(args) = prompt.strip_prefix("/batch").filter(|a| a.is_empty() || a.starts_with(' ')) {
+            let file = args.trim();
+            if file.is_empty() {
+                println!("{}", "Usage: /batch <file>".warning());
+                continue;
+            }
+            let prompts = match fs::read_to_string(file) {
+                Ok(content) => parse_batch_prompts(&content),
+                Err(e) => {
+                    println!("{} {}", "✗ Failed to read batch file:".red(), e);
+                    continue;
+                }
+            };
+            if prompts.is_empty() {
+                println!("{}", "No prompts found in batch file.".warning());
+                continue;
+            }
+            run_batch(&mut session, &prompts).await;
+            continue;
+        }
+
+        if prompt == "/rerun" {
+            if config.dry_run {
+                println!("{}", "Dry run mode is on: scripts are never executed.".warning());
+                continue;
+            }
+            let Some((script_path, stdin_arg, mode)) = last_run.clone() else {
+                println!("{}", "No script has been executed yet. Generate or /run one first!".warning());
+                continue;
+            };
+            match fs::read_to_string(&script_path) {
+                Ok(code) => {
+                    run_saved_script(
+                        executor, config, logger, metrics, &script_path, &code, stdin_arg.as_deref(), Some(mode),
+                    ).await;
+                }
                 Err(e) => println!("{} {}", "✗ Failed to read script:".red(), e),
             }
             continue;
         }
 
+        if let Some(run_args) = prompt.strip_prefix("/run") {
+            if config.dry_run {
+                println!("{}", "Dry run mode is on: scripts are never executed.".warning());
+                continue;
+            }
+            let (script_arg, stdin_arg) = parse_run_args(run_args);
+            let filename = match script_arg {
+                Some(name) => name,
+                None => ask_user("Enter script filename (e.g., script_20251209_152023.py): "),
+            };
+
+            if filename.is_empty() {
+                println!("{}", "Run cancelled.".warning());
+                continue;
+            }
+
+            let relative = filename
+                .strip_prefix(&format!("{}/", config.generated_dir))
+                .unwrap_or(&filename);
+            // Only scripts inside generated_dir may be run (no `../` escapes)
+            let script_path = match resolve_within(Path::new(&config.generated_dir), relative) {
+                Some(path) => path.display().to_string(),
+                None => {
+                    println!("{} {}", "✗ Script not found in generated directory:".red(), filename);
+                    continue;
+                }
+            };
+
+            match fs::read_to_string(&script_path) {
+                Ok(code) => {
+                    let mode = run_saved_script(
+                        executor, config, logger, metrics, &script_path, &code, stdin_arg.as_deref(), None,
+                    ).await;
+                    *last_run = Some((script_path, stdin_arg, mode));
+                }
+                Err(e) => println!("{} {}", "✗ Failed to read script:".red(), e),
+            }
+            continue;
+        }
+
+        // Set by /retry: sampling temperature for this request, and the reply it replaced
+        let mut temperature_override: Option<f32> = None;
+        let mut retry_popped: Option<Message> = None;
+        // Set by /refine with compact_refine: send only the code and the request
+        let mut compact = false;
+
         if prompt == "/refine" {
             if last_generated_code.is_empty() {
-                println!("{}", "No code to refine. Generate some code first!".yellow());
+                println!("{}", "No code to refine. Generate some code first!".warning());
                 continue;
             }
             print!("{}", "What would you like to change or add? ".cyan());
@@ -670,416 +1749,663 @@ async fn start_repl_loop(
             if refinement.is_empty() {
                 continue;
             }
+            let Some(content) = check_prompt_length(config, format!("Please refine the previous code: {}", refinement)) else {
+                continue;
+            };
 
             // Add refinement request to history
             conversation_history.push(Message {
                 role: "user".to_string(),
-                content: format!("Please refine the previous code: {}", refinement),
+                content,
             });
+            compact = config.compact_refine;
+        } else if let Some(args) = prompt.strip_prefix("/retry").filter(|a| a.is_empty() || a.starts_with(' ')) {
+            // Sample again at a higher temperature unless one is given
+            let temperature = match args.trim() {
+                "" => (config.temperature + 0.2).min(2.0),
+                value => match value.parse::<f32>() {
+                    Ok(t) if (0.0..=2.0).contains(&t) => t,
+                    _ => {
+                        println!("{}", "Usage: /retry [temperature between 0.0 and 2.0]".warning());
+                        continue;
+                    }
+                },
+            };
+            match prepare_retry(conversation_history) {
+                Ok(popped) => retry_popped = popped,
+                Err(msg) => {
+                    println!("{}", msg.warning());
+                    continue;
+                }
+            }
+            println!("{} {}", "↻ Retrying at temperature".cyan(), temperature.to_string().emphasis());
+            temperature_override = Some(temperature);
         } else {
             // Regular prompt - add to history
+            let Some(prompt) = check_prompt_length(config, prompt) else {
+                continue;
+            };
+            *last_user_prompt = prompt.clone();
             conversation_history.push(Message {
                 role: "user".to_string(),
-                content: prompt.clone(),
+                content: prompt,
             });
         }
 
-        // Log the request
-        let _ = logger.log_api_request(&conversation_history.last().unwrap().content);
-        metrics.total_requests += 1;
+        process_prompt(&mut session, temperature_override, retry_popped, compact).await;
+    }
+
+    // Don't lose dashboard chat changes still waiting for a debounced save
+    if let Some(ref ds) = session.dashboard {
+        if let Err(e) = ds.save_sessions().await {
+            println!("{} {:#}", "⚠️  Could not save dashboard chats:".warning(), e);
+        }
+    }
+
+    // Display session statistics on exit
+    println!("\n{}", "Session ended.".frame());
+    session.metrics.display();
+}
 
-        // Call Hugging Face with conversation history
-        let spinner = start_spinner("Generating code...");
-        let api_result = api::generate_code_with_history(&conversation_history, config).await;
-        stop_spinner(&spinner);
+/// Run every prompt in a `/batch` file through generate → check → execute,
+/// each in a fresh conversation with `auto_confirm` on, then print a
+/// pass/fail summary. Ctrl-C stops the batch at the current prompt.
+async fn run_batch(session: &mut ReplSession, prompts: &[String]) {
+    let mut config = session.config.clone();
+    config.auto_confirm = true;
+    let mut results: Vec<(bool, String, Duration)> = Vec::new();
+
+    for (i, prompt) in prompts.iter().enumerate() {
+        let first_line = prompt.lines().next().unwrap_or_default();
+        println!("\n{} {}", format!("▶ [{}/{}]", i + 1, prompts.len()).frame().bold(), first_line.emphasis());
+
+        let started = Instant::now();
+        let messages = vec![Message { role: "user".to_string(), content: prompt.clone() }];
+        let _ = session.logger.log_api_request(prompt);
+        session.metrics.record_request(&config.model);
+        let api_result = generate_interruptible(&messages, &config).await;
+        session.metrics.record_api_latency(started.elapsed());
+        let raw_response = match api_result {
+            Ok(r) => r,
+            Err(ApiError::Cancelled) => {
+                println!("{}", "⚠️  Batch cancelled.".warning());
+                break;
+            }
+            Err(e) => {
+                session.metrics.api_errors += 1;
+                let _ = session.logger.log_error(&format!("API error during /batch: {}", e));
+                print_api_error("API Error:", &e);
+                results.push((false, format!("{} API error", "✗".red()), started.elapsed()));
+                continue;
+            }
+        };
+        let _ = session.logger.log_api_response(&raw_response);
+        print_raw_response(&config, &raw_response);
+
+        let task_config = config.clone();
+        let task_prompt = prompt.clone();
+        let report = tokio::task::spawn_blocking(move || check_and_execute(&task_config, &task_prompt, &raw_response))
+            .await
+            .context("Generation task panicked")
+            .and_then(|r| r);
+        let (passed, outcome) = match report {
+            Ok(report) => {
+                print_report(&report);
+                if let Some(ref execution) = report.execution {
+                    let success = execution.exit_code == Some(0);
+                    session.metrics.record_execution(&config.model, success);
+                    let _ = session.logger.log_execution(success, &execution.stdout);
+                }
+                let outcome = if report.passed(config.dry_run) {
+                    format!("{} pass", "✓".green())
+                } else if let Some(ref reason) = report.skipped {
+                    format!("{} skipped: {}", "✗".red(), reason)
+                } else if report.syntax_error.is_some() {
+                    format!("{} syntax error", "✗".red())
+                } else {
+                    let code = report.execution.as_ref().and_then(|e| e.exit_code);
+                    format!("{} exit {}", "✗".red(), exit_code_label(code))
+                };
+                (report.passed(config.dry_run), outcome)
+            }
+            Err(e) => {
+                println!("{} {:#}", "✗ Error:".red(), e);
+                (false, format!("{} error", "✗".red()))
+            }
+        };
+        results.push((passed, outcome, started.elapsed()));
+    }
 
-        match api_result {
-            Ok(raw_response) => {
-                // Log the response
-                let _ = logger.log_api_response(&raw_response);
+    let passed = results.iter().filter(|(passed, _, _)| *passed).count();
+    println!("\n{}", "  ╭── Batch Summary ───────────────────────────".frame());
+    for (i, (_, outcome, elapsed)) in results.iter().enumerate() {
+        let first_line = prompts[i].lines().next().unwrap_or_default();
+        let label = &first_line[..find_char_boundary(first_line, 50)];
+        println!("  {} {:>3}. {:<50} {:>8}  {}", "│".frame(), i + 1, label, format_ms(elapsed.as_millis() as u64).dimmed(), outcome);
+    }
+    println!("  {} {}", "│".frame(), format!("{}/{} passed", passed, results.len()).bold());
+    println!("{}", "  ╰────────────────────────────────────────────".frame());
+}
 
-                // Extract clean Python code from the response
-                let code = extract_python_code(&raw_response);
-                last_generated_code = code.clone();
+/// Ask the model to fix the code after a syntax, lint or runtime error.
+///
+/// `error_context` is sent as a user message; on success the fixed code is
+/// shown and recorded as the assistant's reply, and returned. On failure the
+/// error is counted, logged and printed, and the request is dropped from the
+/// history again, so every caller leaves the history in the same state.
+async fn auto_refine(
+    history: &mut Vec<Message>,
+    config: &AppConfig,
+    logger: &Logger,
+    metrics: &mut SessionMetrics,
+    error_context: String,
+) -> Result<String, ApiError> {
+    let _ = logger.log_api_request(&format!("Auto-refine: {}", error_context));
+    metrics.record_request(&config.model);
+    history.push(Message { role: "user".to_string(), content: error_context });
+
+    let spinner = start_spinner("Auto-refining code...", config.spinner);
+    let api_started = Instant::now();
+    let api_result = generate_interruptible(history, config).await;
+    metrics.record_api_latency(api_started.elapsed());
+    stop_spinner(&spinner);
+
+    match api_result {
+        Ok(raw_response) => {
+            let _ = logger.log_api_response(&raw_response);
+            print_raw_response(config, &raw_response);
+            let fixed_code = extract_python_code(&raw_response);
+            history.push(Message {
+                role: "assistant".to_string(),
+                content: fixed_code.clone(),
+            });
+            trim_history(history, config.max_history_messages, config.max_context_tokens);
+            display_code(&fixed_code);
+            Ok(fixed_code)
+        }
+        Err(e) => {
+            if !matches!(e, ApiError::Cancelled) {
+                metrics.api_errors += 1;
+                let _ = logger.log_error(&format!("API error during auto-refine: {}", e));
+            }
+            print_api_error("✗ API error during auto-refine:", &e);
+            history.pop();
+            Err(e)
+        }
+    }
+}
 
-                // Add assistant response to history
-                conversation_history.push(Message {
-                    role: "assistant".to_string(),
-                    content: code.clone(),
-                });
+/// Whether this generation may auto-refine again after `attempts` tries.
+/// Once the cap is hit, says so and points at the last attempt.
+fn auto_refine_allowed(attempts: u32, max_attempts: u32, script_path: &Path) -> bool {
+    if attempts < max_attempts {
+        return true;
+    }
+    if max_attempts > 0 {
+        println!(
+            "{} {}",
+            "⚠️  Auto-refine limit reached — here's the last attempt:".warning(),
+            script_path.display()
+        );
+    }
+    false
+}
 
-                // Trim history to configured limit
-                trim_history(&mut conversation_history, config.max_history_messages);
+/// Send the conversation to the model and handle the reply: extract, save,
+/// check and (optionally) execute the code.
+///
+/// The user's request must already be the last message in the session's
+/// conversation history. `retry_popped` is the reply replaced by `/retry`,
+/// restored if the request fails.
+async fn process_prompt(
+    session: &mut ReplSession,
+    temperature_override: Option<f32>,
+    retry_popped: Option<Message>,
+    compact: bool,
+) {
+    let ReplSession {
+        config,
+        executor,
+        logger,
+        metrics,
+        linter_available,
+        security_scanner_available,
+        formatter_available,
+        dashboard,
+        conversation_history,
+        last_generated_code,
+        last_user_prompt,
+        last_run,
+        last_synced_metrics,
+    } = session;
+
+    // A long new prompt can push the history over the token budget
+    trim_history(conversation_history, config.max_history_messages, config.max_context_tokens);
+
+    // Log the request
+    let _ = logger.log_api_request(&conversation_history.last().unwrap().content);
+    metrics.record_request(&config.model);
+
+    // Call Hugging Face with conversation history
+    let request_config = AppConfig {
+        temperature: temperature_override.unwrap_or(config.temperature),
+        ..config.clone()
+    };
+    let spinner = start_spinner("Generating code...", config.spinner);
+    let api_started = Instant::now();
+    let compact_messages = compact
+        .then(|| conversation_history.last().map(|request| compact_refine_messages(last_generated_code, request)))
+        .flatten();
+    let request_messages = compact_messages.as_deref().unwrap_or(conversation_history);
+    let api_result = generate_interruptible(request_messages, &request_config).await;
+    metrics.record_api_latency(api_started.elapsed());
+    stop_spinner(&spinner);
+
+    match api_result {
+        Ok(raw_response) => {
+            // Log the response
+            let _ = logger.log_api_response(&raw_response);
+            print_raw_response(config, &raw_response);
+
+            // Extract a multi-file project when the model annotated its
+            // code fences with filenames, otherwise a single script
+            let project = extract_project(&raw_response);
+            let mut code = match project {
+                Some(ref files) => format_project(files),
+                None => extract_python_code(&raw_response),
+            };
 
-                display_code(&code);
+            // Write the script first, then syntax-check before executing.
+            // It's named after the original request, not a later refine or
+            // error message
+            let request = (!last_user_prompt.is_empty()).then(|| last_user_prompt.clone());
+            let written = match project {
+                Some(ref files) => executor.write_project(files),
+                None => executor.write_script_for_prompt(&code, request.as_deref()),
+            };
+            let script_path = match written {
+                Ok(p) => p,
+                Err(e) => {
+                    display_code(&code);
+                    println!("{} {}", "✗ Failed to write script:".red(), e);
+                    return;
+                }
+            };
 
-                // Write the script first, then syntax-check before executing
-                let script_path = match executor.write_script(&code) {
-                    Ok(p) => p,
-                    Err(e) => {
-                        println!("{} {}", "✗ Failed to write script:".red(), e);
-                        continue;
+            // For projects, the rest of the flow works on the entry point
+            let history_content = code.clone();
+            if let Some(ref files) = project {
+                if let Some(dir) = script_path.parent() {
+                    println!("\n{} {}", "📁 Project written to:".frame(), dir.display().to_string().emphasis());
+                }
+                for file in files {
+                    println!("   {} {}", "•".bright_black(), file.path);
+                }
+                code = fs::read_to_string(&script_path).unwrap_or_default();
+            }
+
+            // Auto-format in place so the displayed and saved code match
+            if config.format_generated_code && *formatter_available {
+                match executor.format_code(&script_path) {
+                    Ok(true) => {
+                        if let Ok(formatted) = fs::read_to_string(&script_path) {
+                            code = formatted;
+                        }
                     }
+                    Ok(false) => {}
+                    Err(e) => println!("{} {}", "⚠️  Formatting skipped:".warning(), e),
+                }
+            }
+            *last_generated_code = code.clone();
+
+            // Add assistant response to history (all files for a project)
+            conversation_history.push(Message {
+                role: "assistant".to_string(),
+                content: if project.is_some() { history_content } else { code.clone() },
+            });
+
+            // Trim history to configured limit
+            trim_history(conversation_history, config.max_history_messages, config.max_context_tokens);
+
+            display_code(&code);
+
+            // Sync state to dashboard and broadcast event
+            if let Some(ref ds) = dashboard {
+                let session_id = sync_to_dashboard(ds, metrics, last_synced_metrics, conversation_history, last_generated_code).await;
+                *last_synced_metrics = metrics.clone();
+                ds.broadcast(ExecutionEvent::CodeGenerated {
+                    code: code.clone(),
+                    script_path: script_path.display().to_string(),
+                    session_id,
+                    raw_response: config.verbose.then(|| raw_response.clone()),
+                });
+            }
+
+            // Auto-refines used by this generation, capped by max_auto_refine_attempts
+            let mut refine_attempts = 0;
+
+            // Syntax check, auto-refining until it passes or the cap is hit
+            while let Err(syntax_err) = executor.syntax_check(&script_path) {
+                println!("\n{} {}", "✗ Syntax error detected:".red().bold(), syntax_err);
+                if !auto_refine_allowed(refine_attempts, config.max_auto_refine_attempts, &script_path)
+                    || !confirm_or_auto("Auto-refine to fix this error?", config.auto_confirm_refine)
+                {
+                    return;
+                }
+                refine_attempts += 1;
+                let context = format!("The code has a syntax error. Please fix it:\n{}", syntax_err);
+                let Ok(fixed_code) = auto_refine(conversation_history, config, logger, metrics, context).await else {
+                    return;
                 };
+                *last_generated_code = fixed_code.clone();
 
-                // Sync state to dashboard and broadcast event
-                if let Some(ref ds) = dashboard {
-                    sync_to_dashboard(ds, &metrics, &last_synced_metrics, &conversation_history, &last_generated_code).await;
-                    last_synced_metrics = metrics.clone();
-                    ds.broadcast(ExecutionEvent::CodeGenerated {
-                        code: code.clone(),
-                        script_path: script_path.display().to_string(),
-                    });
+                // Overwrite the script with the fixed code; the loop re-checks its syntax
+                if let Err(e) = fs::write(&script_path, &fixed_code) {
+                    println!("{} {}", "✗ Failed to write fixed script:".red(), e);
+                    return;
                 }
+            }
+
+            // Phase timings for the result block
+            let mut timing = ExecutionTiming::default();
+            // Kept for the execution report
+            let mut lint_report = None;
+            let mut security_report = None;
+
+            // Run lint check (ruff) if available, re-linting after each auto-refine
+            let mut lint_pending = *linter_available;
+            while lint_pending {
+                let (lint_outcome, lint_ms) = timed(|| executor.lint_check(&script_path));
+                timing.lint_ms += lint_ms;
+                match lint_outcome {
+                    Ok(lint_result) => {
+                        display_lint_results(&lint_result, &script_path);
+                        if lint_result.has_errors {
+                            if auto_refine_allowed(refine_attempts, config.max_auto_refine_attempts, &script_path)
+                                && confirm_or_auto("Auto-refine to fix lint errors?", config.auto_confirm_refine)
+                            {
+                                refine_attempts += 1;
+                                // Build a lint error summary for the LLM
+                                let lint_issues: String = lint_result.diagnostics
+                                    .iter()
+                                    .map(|d| d.to_string())
+                                    .collect::<Vec<_>>()
+                                    .join("\n");
+                                let context = format!(
+                                    "The code has the following lint issues (from ruff). Please fix them:\n{}",
+                                    lint_issues
+                                );
+                                let Ok(fixed_code) = auto_refine(conversation_history, config, logger, metrics, context).await else {
+                                    return;
+                                };
+                                *last_generated_code = fixed_code.clone();
 
-                // Syntax check
-                if let Err(syntax_err) = executor.syntax_check(&script_path) {
-                    println!("\n{} {}", "✗ Syntax error detected:".red().bold(), syntax_err);
-                    if confirm("Auto-refine to fix this error?") {
-                        // Add syntax error to conversation history for auto-refine
-                        conversation_history.push(Message {
-                            role: "user".to_string(),
-                            content: format!(
-                                "The code has a syntax error. Please fix it:\n{}",
-                                syntax_err
-                            ),
-                        });
-                        // Skip execution, let the loop iterate to call the API again
-                        // by falling through (we already pushed the user message)
-                        metrics.total_requests += 1;
-                        let _ = logger.log_api_request(&format!("Auto-refine syntax: {}", syntax_err));
-
-                        let spinner = start_spinner("Auto-refining code...");
-                        let api_result = api::generate_code_with_history(&conversation_history, config).await;
-                        stop_spinner(&spinner);
-
-                        match api_result {
-                            Ok(raw_response) => {
-                                let _ = logger.log_api_response(&raw_response);
-                                let fixed_code = extract_python_code(&raw_response);
-                                last_generated_code = fixed_code.clone();
-
-                                conversation_history.push(Message {
-                                    role: "assistant".to_string(),
-                                    content: fixed_code.clone(),
-                                });
-                                trim_history(&mut conversation_history, config.max_history_messages);
-
-                                display_code(&fixed_code);
-
-                                // Overwrite the script with the fixed code
                                 if let Err(e) = fs::write(&script_path, &fixed_code) {
                                     println!("{} {}", "✗ Failed to write fixed script:".red(), e);
-                                    continue;
+                                    return;
                                 }
 
-                                // Re-check syntax
-                                if let Err(err2) = executor.syntax_check(&script_path) {
-                                    println!("{} {}", "✗ Still has syntax errors:".red(), err2);
-                                    continue;
+                                // Re-check syntax after lint fix
+                                if let Err(syn_err) = executor.syntax_check(&script_path) {
+                                    println!("{} {}", "✗ Fixed code has syntax errors:".red(), syn_err);
+                                    return;
                                 }
-                            }
-                            Err(e) => {
-                                metrics.api_errors += 1;
-                                let _ = logger.log_error(&format!("API error during auto-refine: {}", e));
-                                println!("{} {}", "✗ API error during auto-refine:".red(), e);
-                                conversation_history.pop();
                                 continue;
+                            } else if !config.dry_run && !confirm("Proceed with execution despite lint errors?") {
+                                return;
                             }
                         }
-                    } else {
-                        continue;
+                        lint_report = Some(lint_result);
+                    }
+                    Err(e) => {
+                        println!("{} {}", "⚠️  Lint check failed:".warning(), e);
+                        println!("{}", "Proceeding without linting...".dimmed());
                     }
                 }
+                lint_pending = false;
+            }
 
-                // Run lint check (ruff) if available
-                if linter_available {
-                    match executor.lint_check(&script_path) {
-                        Ok(lint_result) => {
-                            display_lint_results(&lint_result);
-                            if lint_result.has_errors {
-                                if confirm("Auto-refine to fix lint errors?") {
-                                    // Build a lint error summary for the LLM
-                                    let lint_issues: String = lint_result.diagnostics
-                                        .iter()
-                                        .map(|d| d.message.as_str())
-                                        .collect::<Vec<_>>()
-                                        .join("\n");
-                                    conversation_history.push(Message {
-                                        role: "user".to_string(),
-                                        content: format!(
-                                            "The code has the following lint issues (from ruff). Please fix them:\n{}",
-                                            lint_issues
-                                        ),
-                                    });
-                                    metrics.total_requests += 1;
-                                    let _ = logger.log_api_request(&format!("Auto-refine lint: {}", lint_issues));
-
-                                    let spinner = start_spinner("Auto-refining code...");
-                                    let api_result = api::generate_code_with_history(&conversation_history, config).await;
-                                    stop_spinner(&spinner);
-
-                                    match api_result {
-                                        Ok(raw_response) => {
-                                            let _ = logger.log_api_response(&raw_response);
-                                            let fixed_code = extract_python_code(&raw_response);
-                                            last_generated_code = fixed_code.clone();
-
-                                            conversation_history.push(Message {
-                                                role: "assistant".to_string(),
-                                                content: fixed_code.clone(),
-                                            });
-                                            trim_history(&mut conversation_history, config.max_history_messages);
-
-                                            display_code(&fixed_code);
-
-                                            if let Err(e) = fs::write(&script_path, &fixed_code) {
-                                                println!("{} {}", "✗ Failed to write fixed script:".red(), e);
-                                                continue;
-                                            }
-
-                                            // Re-check syntax after lint fix
-                                            if let Err(syn_err) = executor.syntax_check(&script_path) {
-                                                println!("{} {}", "✗ Fixed code has syntax errors:".red(), syn_err);
-                                                continue;
-                                            }
-                                        }
-                                        Err(e) => {
-                                            metrics.api_errors += 1;
-                                            let _ = logger.log_error(&format!("API error during lint auto-refine: {}", e));
-                                            println!("{} {}", "✗ API error during auto-refine:".red(), e);
-                                            conversation_history.pop();
-                                            continue;
-                                        }
-                                    }
-                                } else if !confirm("Proceed with execution despite lint errors?") {
-                                    continue;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            println!("{} {}", "⚠️  Lint check failed:".yellow(), e);
-                            println!("{}", "Proceeding without linting...".dimmed());
-                        }
-                    }
+            // Run security check (bandit) if available
+            if *security_scanner_available {
+                let (proceed, sec_result) = security_gate(executor, config, &script_path, &mut timing);
+                if !proceed {
+                    return;
+                }
+                security_report = sec_result;
+            }
+
+            // Dry run: report dependencies, never execute
+            if config.dry_run {
+                let deps = executor.detect_script_dependencies(last_generated_code, &script_path);
+                if !deps.is_empty() {
+                    println!("\n{} {}",
+                        "Detected non-standard dependencies:".warning(),
+                        deps.join(", ").warning());
+                    warn_missing_packages(&deps, config).await;
                 }
+                println!("{} {}", "Dry run: script not executed. Saved at:".warning(), script_path.display());
+                return;
+            }
 
-                // Run security check (bandit) if available
-                if security_scanner_available {
-                    match executor.security_check(&script_path) {
-                        Ok(sec_result) => {
-                            display_security_results(&sec_result);
-                            if sec_result.has_high_severity
-                                && !confirm("HIGH severity security issues found. Proceed anyway?")
-                            {
-                                continue;
-                            }
-                        }
-                        Err(e) => {
-                            println!("{} {}", "⚠️  Security scan failed:".yellow(), e);
-                            println!("{}", "Proceeding without security scanning...".dimmed());
+            if executor.language() == Language::Python && !executor.needs_interactive_mode(last_generated_code) {
+                warn_hang_risk(last_generated_code, config.execution_timeout_secs);
+            }
+
+            if confirm_or_auto("Execute this script?", config.auto_confirm) {
+                // Check for dependencies
+                let deps = executor.detect_script_dependencies(last_generated_code, &script_path);
+
+                // Create a venv for this execution (host mode only)
+                let (venv, venv_ms) = timed(|| executor.prepare_venv(&deps));
+                timing.venv_ms = venv_ms;
+                let (venv, deps_ready) = venv.unwrap_or_else(|e| {
+                    println!("{} {}", "⚠️  Failed to create venv:".warning(), e);
+                    println!("{}", "Proceeding without virtual environment...".dimmed());
+                    None
+                }).unzip();
+
+                if !deps.is_empty() && deps_ready != Some(true) {
+                    println!("\n{} {}",
+                        "⚠️  Detected non-standard dependencies:".warning(),
+                        deps.join(", ").warning());
+                    warn_missing_packages(&deps, config).await;
+                    if config.auto_install_deps || confirm_or_auto("Install these dependencies?", config.auto_confirm) {
+                        let (installed, install_ms) = timed(|| executor.install_packages(&deps, venv.as_deref()));
+                        timing.install_ms = install_ms;
+                        if let Err(e) = installed {
+                            println!("{} {}", "⚠️  Failed to install dependencies:".warning(), e);
+                            println!("{}", "Proceeding anyway...".dimmed());
                         }
                     }
                 }
 
-                if confirm("Execute this script?") {
-                    // Create a venv for this execution (host mode only)
-                    let venv = executor.create_venv().unwrap_or_else(|e| {
-                        println!("{} {}", "⚠️  Failed to create venv:".yellow(), e);
-                        println!("{}", "Proceeding without virtual environment...".dimmed());
-                        None
+                // Detect if interactive mode is needed
+                let mode = if executor.needs_interactive_mode(last_generated_code) {
+                    println!("{}", "🎮 Interactive mode detected (pygame/input/GUI/server)".bright_magenta().bold());
+                    println!("{}", "   Running with inherited stdio for user interaction (Ctrl-C stops the script)...".dimmed());
+                    ExecutionMode::Interactive
+                } else {
+                    ExecutionMode::Captured
+                };
+
+                // Broadcast execution start to dashboard
+                let run_id = DashboardState::new_run_id();
+                if let Some(ref ds) = dashboard {
+                    ds.broadcast(ExecutionEvent::ExecutionStarted {
+                        run_id: run_id.clone(),
+                        script_path: script_path.display().to_string(),
                     });
+                }
 
-                    // Check for dependencies
-                    let deps = executor.detect_dependencies(&last_generated_code);
-                    if !deps.is_empty() {
-                        println!("\n{} {}",
-                            "⚠️  Detected non-standard dependencies:".yellow(),
-                            deps.join(", ").bright_yellow());
-                        if config.auto_install_deps || confirm("Install these dependencies?") {
-                            if let Err(e) = executor.install_packages(&deps, venv.as_deref()) {
-                                println!("{} {}", "⚠️  Failed to install dependencies:".yellow(), e);
-                                println!("{}", "Proceeding anyway...".dimmed());
-                            }
+                log_docker_network(executor, &deps, logger);
+                let run_started = Instant::now();
+                match executor.execute_script(&script_path, mode, config.execution_timeout_secs, venv.as_deref(), &deps) {
+                    Ok(result) => {
+                        let elapsed = run_started.elapsed();
+                        *last_run = Some((result.script_path.display().to_string(), None, mode));
+                        timing.run_ms = result.timing.run_ms;
+                        let success = result.is_success();
+                        metrics.record_execution(&config.model, success);
+
+                        let _ = logger.log_execution(success, &result.stdout);
+                        save_execution_record(
+                            config, &result, timing, request.as_deref(), lint_report.as_ref(), security_report.as_ref(),
+                        );
+
+                        // Broadcast execution result to dashboard
+                        if let Some(ref ds) = dashboard {
+                            broadcast_execution_output(ds, &run_id, &result.stdout, &result.stderr);
+                            ds.broadcast(ExecutionEvent::ExecutionCompleted {
+                                run_id: run_id.clone(),
+                                success,
+                                exit_code: result.exit_code,
+                                timing,
+                            });
+                            sync_to_dashboard(ds, metrics, last_synced_metrics, conversation_history, last_generated_code).await;
+                            *last_synced_metrics = metrics.clone();
                         }
-                    }
 
-                    // Detect if interactive mode is needed
-                    let mode = if executor.needs_interactive_mode(&last_generated_code) {
-                        println!("{}", "🎮 Interactive mode detected (pygame/input/GUI)".bright_magenta().bold());
-                        println!("{}", "   Running with inherited stdio for user interaction...".dimmed());
-                        ExecutionMode::Interactive
-                    } else {
-                        ExecutionMode::Captured
-                    };
+                        print_execution_result(&result, elapsed, &timing, true, config.pager_lines);
 
-                    // Broadcast execution start to dashboard
-                    if let Some(ref ds) = dashboard {
-                        ds.broadcast(ExecutionEvent::ExecutionStarted {
-                            script_path: script_path.display().to_string(),
-                        });
-                    }
-
-                    match executor.execute_script(&script_path, mode, config.execution_timeout_secs, venv.as_deref(), &deps) {
-                        Ok(result) => {
-                            let success = result.is_success();
-                            if success {
-                                metrics.successful_executions += 1;
-                            } else {
-                                metrics.failed_executions += 1;
+                        // Offer auto-refine on runtime errors, retrying until the
+                        // fixed script succeeds or the cap is hit
+                        let mut runtime_error = (!success && !result.stderr.is_empty()).then(|| result.stderr.clone());
+                        while let Some(stderr) = runtime_error.take() {
+                            if !auto_refine_allowed(refine_attempts, config.max_auto_refine_attempts, &script_path)
+                                || !confirm_or_auto("Auto-refine to fix this runtime error?", config.auto_confirm_refine)
+                            {
+                                break;
                             }
-
-                            let _ = logger.log_execution(success, &result.stdout);
-
-                            // Broadcast execution result to dashboard
-                            if let Some(ref ds) = dashboard {
-                                broadcast_execution_output(ds, &result.stdout, &result.stderr);
-                                ds.broadcast(ExecutionEvent::ExecutionCompleted {
-                                    success,
-                                    exit_code: result.exit_code,
-                                });
-                                sync_to_dashboard(ds, &metrics, &last_synced_metrics, &conversation_history, &last_generated_code).await;
-                                last_synced_metrics = metrics.clone();
+                            refine_attempts += 1;
+                            let context = format!("The code crashed with this runtime error. Please fix it:\n{}", stderr);
+                            let Ok(fixed_code) = auto_refine(conversation_history, config, logger, metrics, context).await else {
+                                break;
+                            };
+                            *last_generated_code = fixed_code.clone();
+
+                            // Detect updated deps for the fixed code
+                            let fixed_deps = executor.detect_script_dependencies(&fixed_code, &script_path);
+
+                            // Overwrite the script with the fixed code
+                            if let Err(e) = fs::write(&script_path, &fixed_code) {
+                                println!("{} {}", "✗ Failed to write fixed script:".red(), e);
+                                break;
                             }
-
-                            println!("\n{}", "━━━━━━━━━━━ Execution Result ━━━━━━━━━━━".bright_blue().bold());
-                            println!("{} {:?}", "Script saved at:".dimmed(), result.script_path);
-                            if !result.stdout.is_empty() {
-                                println!("\n{}:", "STDOUT".green().bold());
-                                println!("{}", result.stdout);
+                            if let Err(syn_err) = executor.syntax_check(&script_path) {
+                                println!("{} {}", "✗ Fixed code has syntax errors:".red(), syn_err);
+                                break;
                             }
-                            if !result.stderr.is_empty() {
-                                println!("\n{}:", "STDERR".red().bold());
-                                println!("{}", result.stderr);
+                            // The fix is new code: scan it again before running it
+                            let mut retry_timing = ExecutionTiming::default();
+                            let mut retry_security = None;
+                            if *security_scanner_available {
+                                let (proceed, sec_result) = security_gate(executor, config, &script_path, &mut retry_timing);
+                                if !proceed {
+                                    break;
+                                }
+                                retry_security = sec_result;
+                            }
+                            if !confirm_or_auto("Execute the fixed script?", config.auto_confirm) {
+                                break;
                             }
-                            println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".bright_blue());
 
-                            // Offer auto-refine on runtime errors
-                            if !success && !result.stderr.is_empty()
-                                && confirm("Auto-refine to fix this runtime error?")
-                            {
-                                conversation_history.push(Message {
-                                    role: "user".to_string(),
-                                    content: format!(
-                                        "The code crashed with this runtime error. Please fix it:\n{}",
-                                        result.stderr
-                                    ),
-                                });
-                                metrics.total_requests += 1;
-                                let _ = logger.log_api_request(&format!("Auto-refine runtime: {}", result.stderr));
-
-                                let spinner = start_spinner("Auto-refining code...");
-                                let api_result = api::generate_code_with_history(&conversation_history, config).await;
-                                stop_spinner(&spinner);
-
-                                match api_result {
-                                    Ok(raw_response) => {
-                                        let _ = logger.log_api_response(&raw_response);
-                                        let fixed_code = extract_python_code(&raw_response);
-                                        last_generated_code = fixed_code.clone();
-
-                                        conversation_history.push(Message {
-                                            role: "assistant".to_string(),
-                                            content: fixed_code.clone(),
-                                        });
-                                        trim_history(&mut conversation_history, config.max_history_messages);
-
-                                        display_code(&fixed_code);
-
-                                        // Detect updated deps for the fixed code
-                                        let fixed_deps = executor.detect_dependencies(&fixed_code);
-
-                                        // Overwrite the script with the fixed code
-                                        if let Err(e) = fs::write(&script_path, &fixed_code) {
-                                            println!("{} {}", "✗ Failed to write fixed script:".red(), e);
-                                        } else if let Err(syn_err) = executor.syntax_check(&script_path) {
-                                            println!("{} {}", "✗ Fixed code has syntax errors:".red(), syn_err);
-                                        } else if confirm("Execute the fixed script?") {
-                                            // Reuse the same venv for the retry execution
-                                            match executor.execute_script(&script_path, mode, config.execution_timeout_secs, venv.as_deref(), &fixed_deps) {
-                                                Ok(retry_result) => {
-                                                    let retry_success = retry_result.is_success();
-                                                    if retry_success {
-                                                        metrics.successful_executions += 1;
-                                                    } else {
-                                                        metrics.failed_executions += 1;
-                                                    }
-                                                    let _ = logger.log_execution(retry_success, &retry_result.stdout);
-
-                                                    println!("\n{}", "━━━━━━━━━━━ Execution Result ━━━━━━━━━━━".bright_blue().bold());
-                                                    println!("{} {:?}", "Script saved at:".dimmed(), retry_result.script_path);
-                                                    if !retry_result.stdout.is_empty() {
-                                                        println!("\n{}:", "STDOUT".green().bold());
-                                                        println!("{}", retry_result.stdout);
-                                                    }
-                                                    if !retry_result.stderr.is_empty() {
-                                                        println!("\n{}:", "STDERR".red().bold());
-                                                        println!("{}", retry_result.stderr);
-                                                    }
-                                                    println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".bright_blue());
-                                                }
-                                                Err(e) => {
-                                                    metrics.failed_executions += 1;
-                                                    let _ = logger.log_error(&format!("Execution error: {}", e));
-                                                    println!("{} {}", "✗ Execution error:".red(), e);
-                                                }
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        metrics.api_errors += 1;
-                                        let _ = logger.log_error(&format!("API error during auto-refine: {}", e));
-                                        println!("{} {}", "✗ API error during auto-refine:".red(), e);
-                                        conversation_history.pop();
+                            // Reuse the same venv for the retry execution
+                            let retry_started = Instant::now();
+                            match executor.execute_script(&script_path, mode, config.execution_timeout_secs, venv.as_deref(), &fixed_deps) {
+                                Ok(retry_result) => {
+                                    let retry_success = retry_result.is_success();
+                                    metrics.record_execution(&config.model, retry_success);
+                                    let _ = logger.log_execution(retry_success, &retry_result.stdout);
+                                    retry_timing.run_ms = retry_result.timing.run_ms;
+                                    save_execution_record(
+                                        config, &retry_result, retry_timing, request.as_deref(), None, retry_security.as_ref(),
+                                    );
+
+                                    print_execution_result(&retry_result, retry_started.elapsed(), &retry_timing, true, config.pager_lines);
+                                    if !retry_success && !retry_result.stderr.is_empty() {
+                                        runtime_error = Some(retry_result.stderr);
                                     }
                                 }
+                                Err(e) => {
+                                    metrics.record_execution(&config.model, false);
+                                    let _ = logger.log_error(&format!("Execution error: {}", e));
+                                    println!("{} {}", "✗ Execution error:".red(), e);
+                                }
                             }
                         }
-                        Err(e) => {
-                            metrics.failed_executions += 1;
-                            let _ = logger.log_error(&format!("Execution error: {}", e));
-                            println!("{} {}", "✗ Execution error:".red(), e);
-                        }
                     }
-
-                    // Clean up the venv after execution is done
-                    if let Some(ref venv_path) = venv {
-                        executor.cleanup_venv(venv_path);
+                    Err(e) => {
+                        metrics.record_execution(&config.model, false);
+                        let _ = logger.log_error(&format!("Execution error: {}", e));
+                        println!("{} {}", "✗ Execution error:".red(), e);
                     }
                 }
+
+                // Clean up the venv after execution is done
+                if let Some(ref venv_path) = venv {
+                    executor.cleanup_venv(venv_path);
+                }
             }
-            Err(e) => {
+        }
+        Err(e) => {
+            if !matches!(e, ApiError::Cancelled) {
                 metrics.api_errors += 1;
                 let _ = logger.log_error(&format!("API error: {}", e));
-                println!("{} {}", "✗ API error:".red(), e);
-                // Remove the last user message if API call failed
+            }
+            print_api_error("✗ API error:", &e);
+            // Remove the last user message if API call failed; a failed
+            // retry restores the previous reply instead
+            if temperature_override.is_some() {
+                conversation_history.extend(retry_popped);
+            } else {
                 conversation_history.pop();
             }
         }
     }
+}
 
-    // Display session statistics on exit
-    println!("\n{}", "Session ended.".bright_cyan());
-    metrics.display();
+/// Print an API error, followed by a hint on how to fix it when there is one.
+fn print_api_error(label: &str, e: &ApiError) {
+    if matches!(e, ApiError::Cancelled) {
+        println!("{}", "⚠️  Generation cancelled.".warning());
+        return;
+    }
+    println!("{} {}", label.red(), e);
+    if let Some(hint) = e.hint() {
+        println!("  {}", hint.dimmed());
+    }
 }
 
 /// Sync local REPL state to the shared dashboard state.
 ///
 /// Uses delta-based merging for metrics so that dashboard-originated
 /// metrics (from /api/generate) are not overwritten by the REPL sync.
+/// The conversation is mirrored into the "Terminal" chat session, whose ID
+/// is returned.
 async fn sync_to_dashboard(
     ds: &Arc<DashboardState>,
     metrics: &SessionMetrics,
     last_synced: &SessionMetrics,
     history: &[Message],
     last_code: &str,
-) {
+) -> String {
     {
         let mut m = ds.metrics.write().await;
         m.total_requests += metrics.total_requests.saturating_sub(last_synced.total_requests);
         m.successful_executions += metrics.successful_executions.saturating_sub(last_synced.successful_executions);
         m.failed_executions += metrics.failed_executions.saturating_sub(last_synced.failed_executions);
         m.api_errors += metrics.api_errors.saturating_sub(last_synced.api_errors);
+        m.total_api_latency_ms += metrics.total_api_latency_ms.saturating_sub(last_synced.total_api_latency_ms);
+        m.api_call_count += metrics.api_call_count.saturating_sub(last_synced.api_call_count);
+        m.min_api_latency_ms = m.min_api_latency_ms.into_iter().chain(metrics.min_api_latency_ms).min();
+        m.max_api_latency_ms = m.max_api_latency_ms.into_iter().chain(metrics.max_api_latency_ms).max();
+        for (model, stats) in &metrics.per_model {
+            let synced = last_synced.per_model.get(model).cloned().unwrap_or_default();
+            let entry = m.per_model.entry(model.clone()).or_default();
+            entry.requests += stats.requests.saturating_sub(synced.requests);
+            entry.successes += stats.successes.saturating_sub(synced.successes);
+            entry.failures += stats.failures.saturating_sub(synced.failures);
+        }
     }
     {
         let mut h = ds.conversation_history.write().await;
@@ -1089,68 +2415,298 @@ async fn sync_to_dashboard(
         let mut c = ds.last_generated_code.write().await;
         *c = last_code.to_string();
     }
+    ds.sync_repl_session(history, last_code).await
+}
+
+/// Run a script from `generated_dir` (for `/run` and `/rerun`): create a
+/// venv, offer to install its dependencies, execute it and print the result.
+/// `mode` forces the execution mode; otherwise it is detected from the code.
+/// Returns the mode the script ran in.
+#[allow(clippy::too_many_arguments)]
+async fn run_saved_script(
+    executor: &CodeExecutor,
+    config: &AppConfig,
+    logger: &Logger,
+    metrics: &mut SessionMetrics,
+    script_path: &str,
+    code: &str,
+    stdin_arg: Option<&str>,
+    mode: Option<ExecutionMode>,
+) -> ExecutionMode {
+    println!("\n{}", format!("Running: {}", script_path).frame());
+    let mut timing = ExecutionTiming::default();
+
+    // Check for dependencies
+    let deps = executor.detect_script_dependencies(code, Path::new(script_path));
+
+    // Create a venv for this execution (host mode only)
+    let (venv, venv_ms) = timed(|| executor.prepare_venv(&deps));
+    timing.venv_ms = venv_ms;
+    let (venv, deps_ready) = venv.unwrap_or_else(|e| {
+        println!("{} {}", "⚠️  Failed to create venv:".warning(), e);
+        println!("{}", "Proceeding without virtual environment...".dimmed());
+        None
+    }).unzip();
+
+    if !deps.is_empty() && deps_ready != Some(true) {
+        println!("\n{} {}",
+            "⚠️  Detected non-standard dependencies:".warning(),
+            deps.join(", ").warning());
+        warn_missing_packages(&deps, config).await;
+        if config.auto_install_deps || confirm_or_auto("Install these dependencies?", config.auto_confirm) {
+            let (installed, install_ms) = timed(|| executor.install_packages(&deps, venv.as_deref()));
+            timing.install_ms = install_ms;
+            if let Err(e) = installed {
+                println!("{} {}", "⚠️  Failed to install dependencies:".warning(), e);
+                println!("{}", "Proceeding anyway...".dimmed());
+            }
+        }
+    }
+
+    // Detect if interactive mode is needed (unless re-running with a known
+    // mode). A stdin redirect always runs captured so output is still
+    // collected and timed.
+    let mode = if let Some(mode) = mode {
+        mode
+    } else if let Some(input) = stdin_arg {
+        println!("{}", format!("   Reading stdin from: {}", input).dimmed());
+        ExecutionMode::Captured
+    } else if executor.needs_interactive_mode(code) {
+        println!("{}", "🎮 Interactive mode detected (pygame/input/GUI/server)".bright_magenta().bold());
+        println!("{}", "   Running with inherited stdio for user interaction (Ctrl-C stops the script)...".dimmed());
+        ExecutionMode::Interactive
+    } else {
+        ExecutionMode::Captured
+    };
+    if mode == ExecutionMode::Captured && executor.language() == Language::Python {
+        warn_hang_risk(code, config.execution_timeout_secs);
+    }
+
+    log_docker_network(executor, &deps, logger);
+    let run_started = Instant::now();
+    match executor.run_existing_script(
+        script_path, mode, config.execution_timeout_secs, venv.as_deref(), &deps,
+        stdin_arg.map(Path::new),
+    ) {
+        Ok(result) => {
+            let elapsed = run_started.elapsed();
+            timing.run_ms = result.timing.run_ms;
+            let success = result.is_success();
+            metrics.record_execution(&config.model, success);
+
+            let _ = logger.log_execution(success, &result.stdout);
+            save_execution_record(config, &result, timing, None, None, None);
+
+            print_execution_result(&result, elapsed, &timing, false, config.pager_lines);
+        }
+        Err(e) => {
+            metrics.record_execution(&config.model, false);
+            let _ = logger.log_error(&format!("Execution error: {}", e));
+            println!("{} {}", "✗ Execution error:".red(), e);
+        }
+    }
+
+    // Clean up the venv
+    if let Some(ref venv_path) = venv {
+        executor.cleanup_venv(venv_path);
+    }
+
+    mode
+}
+
+/// Exit status for display: the code (green for 0, red otherwise), or
+/// "killed/timeout" for scripts stopped by a signal or the timeout.
+fn exit_code_label(exit_code: Option<i32>) -> ColoredString {
+    match exit_code {
+        Some(0) => "0".green().bold(),
+        Some(code) => code.to_string().red().bold(),
+        None => "killed/timeout".red().bold(),
+    }
+}
+
+/// Print the execution result block: output, exit status, wall-clock
+/// duration of the run and the phase timings. Output longer than
+/// `pager_lines` goes through the pager.
+fn print_execution_result(
+    result: &CodeExecutionResult,
+    elapsed: std::time::Duration,
+    timing: &ExecutionTiming,
+    show_path: bool,
+    pager_lines: usize,
+) {
+    println!("\n{}", "━━━━━━━━━━━ Execution Result ━━━━━━━━━━━".bright_blue().bold());
+    if show_path {
+        println!("{} {:?}", "Script saved at:".dimmed(), result.script_path);
+    }
+    if !result.stdout.is_empty() {
+        println!("\n{}:", "STDOUT".green().bold());
+        print_paged(&result.stdout, pager_lines);
+    }
+    if !result.stderr.is_empty() {
+        println!("\n{}:", "STDERR".red().bold());
+        print_paged(&result.stderr, pager_lines);
+    }
+    println!("\n{} {}  {} {}", "Exit code:".dimmed(), exit_code_label(result.exit_code),
+        "Duration:".dimmed(), format_ms(elapsed.as_millis() as u64));
+    println!("{} {}", "Timing:".dimmed(), timing.to_string().dimmed());
+    println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".bright_blue());
+}
+
+/// Open `path` in `$VISUAL`, `$EDITOR` or a platform default and wait for
+/// the editor to exit. Returns whether it exited successfully.
+fn open_in_editor(path: &Path) -> io::Result<bool> {
+    let default = if cfg!(windows) { "notepad" } else { "vi" };
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| default.to_string());
+    // Allow arguments, e.g. EDITOR="code --wait"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or(default);
+    let status = std::process::Command::new(program).args(parts).arg(path).status()?;
+    Ok(status.success())
+}
+
+/// Print `text`, through `$PAGER` (default `less -R`) when it has more than
+/// `pager_lines` lines (0 = never page). Falls back to a plain print when
+/// stdout isn't a terminal or the pager can't be started.
+fn print_paged(text: &str, pager_lines: usize) {
+    if pager_lines == 0 || text.lines().count() <= pager_lines || !io::stdout().is_terminal() {
+        println!("{}", text);
+        return;
+    }
+
+    let pager = std::env::var("PAGER").ok().filter(|p| !p.trim().is_empty()).unwrap_or_else(|| "less -R".to_string());
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().unwrap_or("less");
+    let child = std::process::Command::new(program).args(parts).stdin(std::process::Stdio::piped()).spawn();
+    let Ok(mut child) = child else {
+        println!("{}", text);
+        return;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager closing early (e.g. `q` in less) is a broken pipe, not an error
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let _ = child.wait();
+    println!("{}", format!("({} lines shown in {})", text.lines().count(), program).dimmed());
+}
+
+/// Warn (never block) when a script about to run captured looks like it
+/// loops forever and would only stop at the execution timeout.
+fn warn_hang_risk(code: &str, timeout_secs: u64) {
+    if let Some(reason) = analyze_hang_risk(code) {
+        println!("{} {}", "⚠️  This looks like it may run forever:".warning(), reason);
+        println!("{}", format!(
+            "   Interactive mode or a short timeout recommended (it will be stopped after {}s).",
+            timeout_secs
+        ).dimmed());
+    }
+}
+
+/// Show and log the sandbox network policy for a Docker run, so network
+/// failures inside the container can be traced back to it.
+fn log_docker_network(executor: &CodeExecutor, deps: &[String], logger: &Logger) {
+    if let Some(network) = executor.docker_network_summary(deps) {
+        println!("{}", format!("   Docker network: {}", network).dimmed());
+        let _ = logger.log(&format!("Docker network: {}", network));
+    }
+}
+
+/// Warn about detected dependencies that don't exist on PyPI, so a typo
+/// like `reqeusts` is reported before pip fails on it.
+async fn warn_missing_packages(deps: &[String], config: &AppConfig) {
+    if !config.check_pypi || config.offline {
+        return;
+    }
+    for package in api::find_missing_pypi_packages(deps).await {
+        println!("{} package '{}' not found on PyPI — likely a typo or a local module",
+            "⚠️ ".warning(), package.warning());
+    }
 }
 
 /// Send stdout and stderr lines as individual log events to the dashboard.
-fn broadcast_execution_output(ds: &Arc<DashboardState>, stdout: &str, stderr: &str) {
-    let ts = chrono::Local::now().format("%H:%M:%S").to_string();
+fn broadcast_execution_output(ds: &Arc<DashboardState>, run_id: &str, stdout: &str, stderr: &str) {
     for line in stdout.lines() {
-        ds.broadcast(ExecutionEvent::LogLine {
-            timestamp: ts.clone(),
-            stream: "stdout".to_string(),
-            content: line.to_string(),
-        });
+        ds.broadcast_log(run_id, "stdout", line);
     }
     for line in stderr.lines() {
-        ds.broadcast(ExecutionEvent::LogLine {
-            timestamp: ts.clone(),
-            stream: "stderr".to_string(),
-            content: line.to_string(),
-        });
+        ds.broadcast_log(run_id, "stderr", line);
     }
 }
 
 /// Display lint results with colored output.
-fn display_lint_results(result: &crate::python_exec::LintResult) {
+fn display_lint_results(result: &LintResult, script_path: &Path) {
     if result.passed {
         println!("{}", "✓ Lint check passed — no issues found.".green());
         return;
     }
+    let code = fs::read_to_string(script_path).unwrap_or_default();
 
-    println!("\n{}", "━━━━━━━━━━━━ Lint Results ━━━━━━━━━━━━".bright_yellow().bold());
+    println!("\n{}", "━━━━━━━━━━━━ Lint Results ━━━━━━━━━━━━".warning().bold());
     for diag in &result.diagnostics {
         let icon = match diag.severity {
             LintSeverity::Error => "  ✗".red().bold(),
-            LintSeverity::Warning => "  ⚠".yellow(),
+            LintSeverity::Warning => "  ⚠".warning(),
         };
-        println!("{} {}", icon, diag.message);
+        let location = format!("{}:{}", diag.line, diag.column).bright_black();
+        if diag.code.is_empty() {
+            println!("{} {} {}", icon, location, diag.message);
+        } else {
+            println!("{} {} {} {}", icon, location, diag.code.emphasis().bold(), diag.message);
+        }
+        print_snippet(&code, diag.line);
     }
     if !result.summary.is_empty() {
         println!("\n{}", result.summary.dimmed());
     }
-    println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".bright_yellow());
+    println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".warning());
+}
+
+/// Print the code around a diagnostic's `line`, with that line highlighted.
+fn print_snippet(code: &str, line: u32) {
+    let line = line as usize;
+    let snippet = snippet_around(code, line, SNIPPET_CONTEXT_LINES);
+    let width = snippet.last().map_or(1, |(n, _)| n.to_string().len());
+    for (n, text) in snippet {
+        let gutter = format!("{:>width$} │", n).bright_black();
+        if n == line {
+            println!("      {} {} {}", "▶".red().bold(), gutter, text.bold());
+        } else {
+            println!("        {} {}", gutter, text.dimmed());
+        }
+    }
 }
 
 /// Display security scan results with colored output.
-fn display_security_results(result: &crate::python_exec::SecurityResult) {
+fn display_security_results(result: &SecurityResult, script_path: &Path) {
     if result.passed {
         println!("{}", "✓ Security scan passed — no issues found.".green());
         return;
     }
+    let code = fs::read_to_string(script_path).unwrap_or_default();
 
     println!("\n{}", "━━━━━━━━━━ Security Scan Results ━━━━━━━━━━".bright_red().bold());
     for diag in &result.diagnostics {
         let icon = match diag.severity {
             SecuritySeverity::High => "  ✗".red().bold(),
-            SecuritySeverity::Medium => "  ⚠".yellow(),
+            SecuritySeverity::Medium => "  ⚠".warning(),
             SecuritySeverity::Low => "  ℹ".dimmed(),
         };
         let sev_label = match diag.severity {
             SecuritySeverity::High => format!("[{}]", diag.severity).red().bold().to_string(),
-            SecuritySeverity::Medium => format!("[{}]", diag.severity).yellow().to_string(),
+            SecuritySeverity::Medium => format!("[{}]", diag.severity).warning().to_string(),
             SecuritySeverity::Low => format!("[{}]", diag.severity).dimmed().to_string(),
         };
         println!("{} {} {}", icon, sev_label, diag.message);
+        print_snippet(&code, diag.line_number);
+        if let (Some(id), Some(link)) = (diag.cwe, diag.cwe_link()) {
+            println!("      {} {}", format!("CWE-{}:", id).dimmed(), link.dimmed());
+        }
+        if let Some(ref more_info) = diag.more_info {
+            println!("      {} {}", "More info:".dimmed(), more_info.dimmed());
+        }
     }
     if !result.summary.is_empty() {
         println!("\n{}", result.summary.dimmed());
@@ -1158,3 +2714,44 @@ fn display_security_results(result: &crate::python_exec::SecurityResult) {
     println!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".bright_red());
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_batch_counts_api_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server.mock("POST", "/v1/chat/completions").with_status(500).create_async().await;
+
+        let dir = std::env::temp_dir().join(format!("pymakebot_batch_{}", uuid::Uuid::new_v4()));
+        let config = AppConfig {
+            provider: "openai-compatible".to_string(),
+            api_url: format!("{}/v1/chat/completions", server.url()),
+            max_retries: 0,
+            generated_dir: dir.join("generated").display().to_string(),
+            spinner: false,
+            ..AppConfig::default()
+        };
+        let ctx = ReplContext {
+            executor: build_executor(&config, false),
+            logger: Logger::new(dir.join("logs").to_str().unwrap()).unwrap(),
+            metrics: SessionMetrics::new(),
+            linter_available: false,
+            security_scanner_available: false,
+            formatter_available: false,
+            use_docker: false,
+        };
+        let mut session = ReplSession::new(&config, ctx, None);
+
+        run_batch(&mut session, &["first prompt".to_string(), "second prompt".to_string()]).await;
+        assert_eq!(session.metrics.api_errors, 2);
+        assert_eq!(session.metrics.api_call_count, 2);
+        let log: String = std::fs::read_dir(dir.join("logs"))
+            .unwrap()
+            .flatten()
+            .map(|entry| std::fs::read_to_string(entry.path()).unwrap())
+            .collect();
+        assert!(log.contains("API error during /batch"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
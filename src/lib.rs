@@ -2,8 +2,11 @@ use anyhow::Result;
 use dotenvy::dotenv;
 
 pub mod api;
+pub mod bench;
 pub mod config;
 pub mod dashboard;
+pub mod generator;
+pub mod highlight;
 pub mod python_exec;
 pub mod interface;
 pub mod utils;
@@ -17,7 +20,18 @@ pub async fn run() -> Result<()> {
     // Load environment variables from .env
     dotenv().ok();
 
-    let config = config::AppConfig::load();
+    let mut config = config::AppConfig::load();
+    config.apply_args(std::env::args().skip(1));
+    highlight::apply_color_settings(config.color, config.theme);
+    if let Some(notice) = api::apply_offline(&mut config)? {
+        eprintln!("{}", notice);
+    }
+
+    for warning in config.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+    config.validate()?;
+    check_python(&config)?;
 
     if config.enable_dashboard {
         interface::start_repl_with_dashboard(&config).await;
@@ -28,6 +42,124 @@ pub async fn run() -> Result<()> {
     Ok(())
 }
 
+/// Fail fast when `python_executable` (e.g. from `--python` or
+/// `PYMAKEBOT_PYTHON`) doesn't run, rather than at the first execution.
+/// Like venv creation, `python` is accepted as a fallback, so hosts that
+/// only ship `python` keep working with the default.
+fn check_python(config: &config::AppConfig) -> Result<()> {
+    if config.language != python_exec::Language::Python {
+        return Ok(());
+    }
+    let candidates = [config.python_executable.as_str(), "python"];
+    if candidates.iter().any(|python| python_exec::python_version(python).is_some()) {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "python_executable {:?} could not be run (`--version` failed); check --python, PYMAKEBOT_PYTHON or pymakebot.toml",
+        config.python_executable
+    )
+}
+
+/// Load configuration the same way [`run`] does, then check every external
+/// tool and the provider connection. Returns whether all required checks
+/// passed.
+pub async fn doctor() -> Result<bool> {
+    dotenv().ok();
+
+    let mut config = config::AppConfig::load();
+    config.apply_args(std::env::args().skip(2));
+    highlight::apply_color_settings(config.color, config.theme);
+    if let Some(notice) = api::apply_offline(&mut config)? {
+        eprintln!("{}", notice);
+    }
+
+    Ok(interface::run_doctor(&config).await)
+}
+
+/// Usage line for one-shot generation.
+pub const GENERATE_USAGE: &str =
+    "Usage: pymakebot generate \"<prompt>\" [--execute] [--json] [--yes] [--offline]";
+
+/// One-shot generation (`pymakebot generate "<prompt>" [--execute] [--json]`,
+/// or just `pymakebot "<prompt>"`): generate and check a single script
+/// without the REPL, then print the result — as one JSON object with
+/// `--json`. The script only runs with `--execute`. Returns whether it
+/// passed: exited with status 0 when executed, otherwise the syntax check.
+pub async fn generate() -> Result<bool> {
+    dotenv().ok();
+
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("generate") {
+        args.remove(0);
+    }
+    let json = args.iter().any(|a| a == "--json");
+    let execute = args.iter().any(|a| a == "--execute");
+    let prompt = config::positional_args(&args).join(" ");
+    if prompt.trim().is_empty() {
+        anyhow::bail!(GENERATE_USAGE);
+    }
+
+    let mut config = config::AppConfig::load();
+    config.apply_args(args.iter().cloned());
+    highlight::apply_color_settings(config.color, config.theme);
+    if let Some(notice) = api::apply_offline(&mut config)? {
+        eprintln!("{}", notice);
+    }
+    if !execute {
+        config.dry_run = true;
+    }
+    for warning in config.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+    config.validate()?;
+    check_python(&config)?;
+
+    let report = interface::generate_once(&config, &prompt).await?;
+    if json {
+        println!("{}", serde_json::to_string(&report)?);
+    } else {
+        interface::print_report(&report);
+    }
+    Ok(report.passed(config.dry_run))
+}
+
+/// Benchmark mode (`pymakebot bench <suite.toml> [--json|--csv]`): run every
+/// case of the suite against each model and print the report. Returns
+/// whether every case passed.
+pub async fn bench() -> Result<bool> {
+    dotenv().ok();
+
+    let args: Vec<String> = std::env::args().skip(2).collect();
+    let Some(suite_path) = config::positional_args(&args).into_iter().next() else {
+        anyhow::bail!("Usage: pymakebot bench <suite.toml> [--json|--csv] [--offline]");
+    };
+    let suite = bench::BenchSuite::load(std::path::Path::new(&suite_path))?;
+
+    let mut config = config::AppConfig::load();
+    config.apply_args(args.iter().cloned());
+    highlight::apply_color_settings(config.color, config.theme);
+    if let Some(notice) = api::apply_offline(&mut config)? {
+        eprintln!("{}", notice);
+    }
+    for warning in config.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+    config.validate()?;
+    check_python(&config)?;
+
+    let report = bench::run_suite(&config, &suite).await;
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if args.iter().any(|a| a == "--csv") {
+        print!("{}", report.to_csv());
+    } else {
+        report.display();
+    }
+    Ok(report.all_passed())
+}
+
 // Re-exports for library consumers: common useful types
+pub use api::{ApiError, Provider};
 pub use config::AppConfig;
+pub use generator::CodeGenerator;
 pub use python_exec::{CodeExecutor, ExecutionMode};
@@ -1,6 +1,50 @@
 use anyhow::Result;
+use python_maker_bot::{AppConfig, CodeExecutor};
+use std::path::Path;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `pymakebot init [--force]` writes a commented default config and exits
+    if args.first().map(String::as_str) == Some("init") {
+        let force = args.iter().any(|a| a == "--force");
+        let path = Path::new("pymakebot.toml");
+        AppConfig::write_default_toml(path, force)?;
+        println!("Wrote {} with default settings.", path.display());
+        return Ok(());
+    }
+
+    // `pymakebot build-sandbox` builds the Docker sandbox image from the embedded Dockerfile
+    if args.first().map(String::as_str) == Some("build-sandbox") {
+        CodeExecutor::build_sandbox_image()?;
+        println!("Built the python-sandbox image. Set use_docker = true to use it.");
+        return Ok(());
+    }
+
+    // `pymakebot doctor` checks tools and the provider, exiting 1 on problems
+    if args.first().map(String::as_str) == Some("doctor") {
+        let healthy = python_maker_bot::doctor().await?;
+        std::process::exit(if healthy { 0 } else { 1 });
+    }
+
+    // `pymakebot bench <suite.toml> [--json|--csv]` scores models on a prompt suite
+    if args.first().map(String::as_str) == Some("bench") {
+        let all_passed = python_maker_bot::bench().await?;
+        std::process::exit(if all_passed { 0 } else { 1 });
+    }
+
+    // `pymakebot generate "<prompt>" [--execute] [--json]` runs one prompt without
+    // the REPL; a bare multi-word `pymakebot "<prompt>"` is shorthand for it
+    if let Some(first) = args.first().filter(|a| !a.starts_with('-')) {
+        if first != "generate" && !first.contains(char::is_whitespace) {
+            eprintln!("Unknown command '{}'. Commands: init, build-sandbox, doctor, generate, bench.", first);
+            eprintln!("{}", python_maker_bot::GENERATE_USAGE);
+            std::process::exit(2);
+        }
+        let succeeded = python_maker_bot::generate().await?;
+        std::process::exit(if succeeded { 0 } else { 1 });
+    }
+
     python_maker_bot::run().await
 }
@@ -1,18 +1,61 @@
 use anyhow::{Context, Result};
 use regex::Regex;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 
 // Cached regexes — compiled once, reused across all calls
-static CODE_BLOCK_RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"```\s*(?:python)?\s*([\s\S]*?)\s*```").unwrap());
-static INCOMPLETE_BLOCK_RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"```\s*(?:python)?\s*\n([\s\S]*)$").unwrap());
+/// Fence tags stripped from code blocks (Python plus the other supported languages).
+const FENCE_TAGS: &str = "python|py|bash|shell|sh|javascript|js";
+static CODE_BLOCK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"```[ \t]*(?:(?:{FENCE_TAGS})\b)?\s*([\s\S]*?)\s*```")).unwrap()
+});
+static INCOMPLETE_BLOCK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"```[ \t]*(?:(?:{FENCE_TAGS})\b)?[ \t]*\n([\s\S]*)$")).unwrap()
+});
 static IMPORT_RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"^import\s+([a-zA-Z_][a-zA-Z0-9_]*)").unwrap());
+    LazyLock::new(|| Regex::new(r"^import\s+([^#;]+)").unwrap());
+static MODULE_NAME_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([a-zA-Z_][a-zA-Z0-9_]*)(?:\.[a-zA-Z0-9_.]*)?(?:\s+as\s+\w+)?$").unwrap());
 static FROM_IMPORT_RE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^from\s+([a-zA-Z_][a-zA-Z0-9_]*)\s+import").unwrap());
+/// Lines that start a Python statement: imports, definitions, decorators,
+/// comments, control flow, assignments and bare calls.
+static PYTHON_LINE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"^(?:#|@|import\s|from\s+[\w.]+\s+import\s|(?:async\s+)?def\s|class\s|",
+        r"(?:if|for|while|with|try|elif|else|except|finally|return|raise|assert|print)\b|",
+        r"[A-Za-z_][\w.]*(?:\s*,\s*[A-Za-z_][\w.]*)*(?:\[[^\]]*\])?\s*(?::[^=]+)?[-+*/%|&]?=(?:[^=]|$)|",
+        r#"[A-Za-z_][\w.]*\(|'''|""")"#
+    ))
+    .unwrap()
+});
+static FENCED_BLOCK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"```([^\n`]*)\n([\s\S]*?)```").unwrap());
+/// The `{ts}` part of a generated script name; milliseconds are optional
+/// (scripts written before they were added have none).
+static SCRIPT_TS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^0-9])(\d{8}_\d{6})(?:_(\d{3}))?(?:[^0-9]|$)").unwrap());
+
+/// `chrono` format of the `{ts}` placeholder in `script_name_template` (UTC).
+pub const SCRIPT_TS_FORMAT: &str = "%Y%m%d_%H%M%S_%3f";
+
+/// One file of a multi-file project emitted by the model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectFile {
+    /// Path relative to the project directory, e.g. `utils.py` or `pkg/core.py`.
+    pub path: String,
+    pub content: String,
+}
+
+impl ProjectFile {
+    /// Whether `path` is relative and stays inside the project directory:
+    /// only plain components, no `..`, root or drive prefix.
+    pub fn has_safe_path(&self) -> bool {
+        !self.path.is_empty()
+            && Path::new(&self.path).components().all(|c| matches!(c, std::path::Component::Normal(_)))
+    }
+}
 
 pub fn ensure_dir(path: &Path) -> Result<()> {
     if !path.exists() {
@@ -22,6 +65,54 @@ pub fn ensure_dir(path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// A scratch copy of some code in the system temp directory, deleted when
+/// dropped — including on panic or when the owning task is cancelled.
+#[derive(Debug)]
+pub struct TempScript {
+    path: PathBuf,
+}
+
+impl TempScript {
+    /// Write `code` to a uniquely named `<prefix>_<pid>_<uuid>_tmp.py`.
+    pub fn new(prefix: &str, code: &str) -> Result<Self> {
+        let name = format!("{}_{}_{}_tmp.py", prefix, std::process::id(), uuid::Uuid::new_v4().simple());
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, code).with_context(|| format!("Failed to write temp file {:?}", path))?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempScript {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Whether `name` is a scratch file from a lint/security check rather than a
+/// real script: `_*_tmp.py`, or the older `_lint_check_*` / `_security_check_*`.
+pub fn is_scratch_file(name: &str) -> bool {
+    name.starts_with('_')
+        && (name.ends_with("_tmp.py") || name.starts_with("_lint_check_") || name.starts_with("_security_check_"))
+}
+
+/// Rough token count of `text` for context budgeting: about 4 bytes per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Cut `prompt` to its first `max_chars` characters.
+/// Returns `None` when it already fits or the limit is 0 (unlimited).
+pub fn truncate_prompt(prompt: &str, max_chars: usize) -> Option<&str> {
+    if max_chars == 0 {
+        return None;
+    }
+    prompt.char_indices().nth(max_chars).map(|(end, _)| &prompt[..end])
+}
+
 /// Find the largest char boundary in `s` that is <= `max_bytes`.
 /// Safe for slicing: `&s[..find_char_boundary(s, max_bytes)]` never panics.
 pub fn find_char_boundary(s: &str, max_bytes: usize) -> usize {
@@ -66,8 +157,9 @@ pub fn extract_python_code(response: &str) -> String {
         }
     }
 
-    // If no markdown block found, clean up markdown artifacts and return
-    let cleaned = clean_markdown_artifacts(response.trim());
+    // If no markdown block found, clean up markdown artifacts and any
+    // prose around the code
+    let cleaned = strip_surrounding_prose(&clean_markdown_artifacts(response.trim()));
 
     // If the result is mostly markdown text, return a helpful comment
     if is_just_markdown_text(&cleaned) {
@@ -77,6 +169,62 @@ pub fn extract_python_code(response: &str) -> String {
     cleaned
 }
 
+/// Extract a multi-file project from fenced blocks annotated with a filename.
+///
+/// Recognized fence info strings: ```` ```python title=utils.py ````,
+/// `file=`/`filename=`/`path=` variants (optionally quoted), and a bare
+/// filename such as ```` ```requirements.txt ````. Unannotated blocks are
+/// ignored. Returns `None` when no block carries a filename, so plain
+/// responses keep going through [`extract_python_code`].
+pub fn extract_project(response: &str) -> Option<Vec<ProjectFile>> {
+    let mut files: Vec<ProjectFile> = Vec::new();
+    for capture in FENCED_BLOCK_RE.captures_iter(response) {
+        let info = capture.get(1).map_or("", |m| m.as_str());
+        let Some(path) = fence_filename(info) else {
+            continue;
+        };
+        let content = capture.get(2).map_or("", |m| m.as_str()).trim_end().to_string() + "\n";
+        // A later block with the same name replaces the earlier one
+        files.retain(|f| f.path != path);
+        files.push(ProjectFile { path, content });
+    }
+    if files.is_empty() {
+        None
+    } else {
+        Some(files)
+    }
+}
+
+/// Pull a filename out of a code fence info string, if it carries one.
+fn fence_filename(info: &str) -> Option<String> {
+    for token in info.split_whitespace() {
+        let value = match token.split_once('=') {
+            Some(("title" | "file" | "filename" | "path", value)) => value,
+            Some(_) => continue,
+            None => token,
+        };
+        let value = value.trim_matches(|c| c == '"' || c == '\'');
+        // A bare token only counts when it looks like a file (has an extension)
+        if (token.contains('=') || value.contains('.')) && !value.is_empty() {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+/// Render project files back into filename-annotated fences, the same
+/// shape [`extract_project`] parses (used for conversation history).
+pub fn format_project(files: &[ProjectFile]) -> String {
+    files
+        .iter()
+        .map(|f| {
+            let lang = if f.path.ends_with(".py") { "python " } else { "" };
+            format!("```{}title={}\n{}```", lang, f.path, f.content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
 /// Check if text is just markdown explanations without actual code
 fn is_just_markdown_text(text: &str) -> bool {
     let lines: Vec<&str> = text.lines().collect();
@@ -94,8 +242,12 @@ fn is_just_markdown_text(text: &str) -> bool {
             continue;
         }
 
+        // A shebang starts a script, not a heading
+        if trimmed.starts_with("#!") {
+            code_lines += 1;
+        }
         // Markdown indicators (explicit parentheses for clarity)
-        if trimmed.starts_with("###")
+        else if trimmed.starts_with("###")
            || trimmed.starts_with("##")
            || (trimmed.starts_with("#") && !trimmed.contains("=") && !trimmed.contains("import"))
            || trimmed.starts_with("Here is")
@@ -140,8 +292,34 @@ fn clean_markdown_artifacts(text: &str) -> String {
     result.trim().to_string()
 }
 
+/// Drop prose before the first line that looks like Python and sentence-like
+/// lines after the code ("Sure! Here's the script:", "Hope this helps.").
+/// Text with no Python-looking line is returned unchanged.
+fn strip_surrounding_prose(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let Some(first) = lines.iter().position(|l| PYTHON_LINE_RE.is_match(l)) else {
+        return text.to_string();
+    };
+    let last = lines
+        .iter()
+        .rposition(|l| !l.trim().is_empty() && !is_prose_line(l))
+        .unwrap_or(first)
+        .max(first);
+    lines[first..=last].join("\n")
+}
+
+/// An unindented sentence: starts with a capital letter, ends with
+/// punctuation and isn't a Python statement.
+fn is_prose_line(line: &str) -> bool {
+    let starts_sentence = line.chars().next().is_some_and(|c| c.is_uppercase());
+    let ends_sentence = line.trim_end().ends_with(['.', '!', '?', ':']);
+    starts_sentence && ends_sentence && !PYTHON_LINE_RE.is_match(line)
+}
+
 /// Extract all import statements from Python code
-/// Returns a list of package names (without submodules)
+/// Returns a list of package names (without submodules). Comma-separated
+/// `import a, b.c as d` lists yield every package; relative imports
+/// (`from . import x`) are skipped.
 pub fn extract_imports(code: &str) -> Vec<String> {
     let mut imports = Vec::new();
 
@@ -149,8 +327,12 @@ pub fn extract_imports(code: &str) -> Vec<String> {
         let trimmed = line.trim();
 
         if let Some(caps) = IMPORT_RE.captures(trimmed) {
-            if let Some(pkg) = caps.get(1) {
-                imports.push(pkg.as_str().to_string());
+            if let Some(list) = caps.get(1) {
+                for module in list.as_str().split(',') {
+                    if let Some(pkg) = MODULE_NAME_RE.captures(module.trim()).and_then(|c| c.get(1)) {
+                        imports.push(pkg.as_str().to_string());
+                    }
+                }
             }
         }
 
@@ -202,6 +384,284 @@ pub fn is_stdlib(package: &str) -> bool {
     STDLIB_MODULES.contains(&package)
 }
 
+/// Import names whose PyPI distribution is named differently.
+const IMPORT_TO_PYPI: &[(&str, &str)] = &[
+    ("Crypto", "pycryptodome"),
+    ("OpenSSL", "pyOpenSSL"),
+    ("PIL", "Pillow"),
+    ("attr", "attrs"),
+    ("bs4", "beautifulsoup4"),
+    ("cv2", "opencv-python"),
+    ("dateutil", "python-dateutil"),
+    ("discord", "discord.py"),
+    ("docx", "python-docx"),
+    ("dotenv", "python-dotenv"),
+    ("fitz", "PyMuPDF"),
+    ("jwt", "PyJWT"),
+    ("magic", "python-magic"),
+    ("serial", "pyserial"),
+    ("skimage", "scikit-image"),
+    ("sklearn", "scikit-learn"),
+    ("telegram", "python-telegram-bot"),
+    ("usb", "pyusb"),
+    ("win32api", "pywin32"),
+    ("yaml", "PyYAML"),
+    ("zmq", "pyzmq"),
+];
+
+/// PyPI package name for an import name (`cv2` → `opencv-python`).
+/// Unknown names are returned unchanged.
+pub fn pypi_name(import: &str) -> &str {
+    IMPORT_TO_PYPI
+        .iter()
+        .find(|(name, _)| *name == import)
+        .map_or(import, |(_, package)| package)
+}
+
+/// Map import names to the PyPI packages `pip install` needs.
+pub fn pypi_packages(imports: &[String]) -> Vec<String> {
+    imports.iter().map(|import| pypi_name(import).to_string()).collect()
+}
+
+/// Contents of a `requirements.txt` for `imports`: sorted, de-duplicated
+/// PyPI names, one per line.
+pub fn requirements_txt(imports: &[String]) -> String {
+    let mut packages = pypi_packages(imports);
+    packages.sort_by_key(|p| p.to_lowercase());
+    packages.dedup();
+    packages.iter().map(|p| format!("{}\n", p)).collect()
+}
+
+/// Resolve `relative` inside `base_dir`, following symlinks and `..`.
+///
+/// Returns the canonical path only if it exists and is still inside the
+/// canonicalized `base_dir`; anything that escapes (or doesn't exist) is `None`.
+pub fn resolve_within(base_dir: &Path, relative: &str) -> Option<PathBuf> {
+    let base = fs::canonicalize(base_dir).ok()?;
+    let resolved = fs::canonicalize(base.join(relative)).ok()?;
+    resolved.starts_with(&base).then_some(resolved)
+}
+
+/// Whether `name` is a bare generated-script filename (a `.py`, `.sh` or
+/// `.js` name carrying its `{ts}` timestamp, e.g. `script_*.py`) that is
+/// safe to join onto the generated directory: no separators or `..`.
+pub fn is_generated_script_name(name: &str) -> bool {
+    parse_script_timestamp(name).is_some() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+/// Creation time of a generated script, parsed from the `YYYYMMDD_HHMMSS`
+/// (optionally `_mmm`) timestamp in its name, written in UTC. `None` for
+/// other extensions, scratch files and names without a timestamp.
+pub fn parse_script_timestamp(name: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let (stem, ext) = name.rsplit_once('.')?;
+    if !matches!(ext, "py" | "sh" | "js") || is_scratch_file(name) {
+        return None;
+    }
+    let caps = SCRIPT_TS_RE.captures(stem)?;
+    let created = chrono::NaiveDateTime::parse_from_str(caps.get(1)?.as_str(), "%Y%m%d_%H%M%S").ok()?;
+    let millis = caps.get(2).map_or(0, |m| m.as_str().parse().unwrap_or(0));
+    Some((created + chrono::Duration::milliseconds(millis)).and_utc())
+}
+
+/// Lowercase the first few words of `text` into a filename-safe
+/// `count-lines-in-file` slug (at most 40 bytes; empty if nothing usable).
+pub fn slugify(text: &str, max_words: usize) -> String {
+    let mut slug = String::new();
+    let words = text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .take(max_words);
+    for word in words {
+        if !slug.is_empty() && slug.len() + word.len() + 1 > 40 {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_ascii_lowercase());
+    }
+    slug.truncate(40);
+    slug
+}
+
+/// Render `script_name_template` into a file stem. `{ts}` is the creation
+/// time, `{seq}` a zero-padded counter and `{slug}` the start of `prompt`
+/// (separators around an empty slug are dropped). Characters outside
+/// `[A-Za-z0-9_-]` are replaced with `_`.
+pub fn render_script_name(template: &str, created: chrono::DateTime<chrono::Utc>, seq: u32, prompt: Option<&str>) -> String {
+    let slug = prompt.map(|p| slugify(p, 6)).unwrap_or_default();
+    let mut name = template
+        .replace("{ts}", &created.format(SCRIPT_TS_FORMAT).to_string())
+        .replace("{seq}", &format!("{:04}", seq));
+    if slug.is_empty() {
+        for pattern in ["_{slug}", "-{slug}", "{slug}_", "{slug}-", "{slug}"] {
+            name = name.replace(pattern, "");
+        }
+    } else {
+        name = name.replace("{slug}", &slug);
+    }
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+/// Generated scripts in `dir` as `(filename, created)`, newest first. Only
+/// files named with a `{ts}` timestamp are listed, so scratch files and
+/// anything the user dropped into the directory are skipped.
+pub fn list_generated_scripts(dir: &Path) -> Vec<(String, chrono::DateTime<chrono::Utc>)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut scripts: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            parse_script_timestamp(&name).map(|created| (name, created))
+        })
+        .collect();
+    scripts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+    scripts
+}
+
+/// Largest script scanned by content search; bigger files only match by name.
+pub const SEARCH_MAX_FILE_BYTES: u64 = 512 * 1024;
+
+/// First line of the file at `path` containing `term` (case-insensitive),
+/// as `(line number, trimmed line)`, shortened to 120 bytes. Files over
+/// [`SEARCH_MAX_FILE_BYTES`] and unreadable files never match.
+pub fn find_in_file(path: &Path, term: &str) -> Option<(usize, String)> {
+    if term.is_empty() || fs::metadata(path).ok()?.len() > SEARCH_MAX_FILE_BYTES {
+        return None;
+    }
+    let content = fs::read_to_string(path).ok()?;
+    let needle = term.to_lowercase();
+    content.lines().enumerate().find_map(|(i, line)| {
+        if !line.to_lowercase().contains(&needle) {
+            return None;
+        }
+        let line = line.trim();
+        let snippet = if line.len() > 120 {
+            format!("{}...", &line[..find_char_boundary(line, 120)])
+        } else {
+            line.to_string()
+        };
+        Some((i + 1, snippet))
+    })
+}
+
+/// Lines of code shown above and below a diagnostic's line.
+pub const SNIPPET_CONTEXT_LINES: usize = 2;
+
+/// Lines of `code` within `context` lines of `line` (1-based), as
+/// `(line number, text)`, for showing a diagnostic in place. Empty when
+/// `line` is 0 or past the end.
+pub fn snippet_around(code: &str, line: usize, context: usize) -> Vec<(usize, &str)> {
+    if line == 0 {
+        return Vec::new();
+    }
+    let first = line.saturating_sub(context).max(1);
+    let snippet: Vec<(usize, &str)> = code
+        .lines()
+        .enumerate()
+        .map(|(i, text)| (i + 1, text))
+        .skip(first - 1)
+        .take(line + context + 1 - first)
+        .collect();
+    if snippet.iter().any(|&(n, _)| n == line) {
+        snippet
+    } else {
+        Vec::new()
+    }
+}
+
+/// [`snippet_around`] as plain text lines, `> 42 | code` marking `line`.
+pub fn format_snippet(code: &str, line: usize, context: usize) -> Vec<String> {
+    let snippet = snippet_around(code, line, context);
+    let width = snippet.last().map_or(1, |(n, _)| n.to_string().len());
+    snippet
+        .into_iter()
+        .map(|(n, text)| format!("{} {:>width$} | {}", if n == line { '>' } else { ' ' }, n, text))
+        .collect()
+}
+
+/// Human-readable file size: `512 B`, `2.3 KB`, `1.1 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    let b = bytes as f64;
+    if b < KB {
+        format!("{} B", bytes)
+    } else if b < KB * KB {
+        format!("{:.1} KB", b / KB)
+    } else {
+        format!("{:.1} MB", b / (KB * KB))
+    }
+}
+
+/// How long before `now` something happened: `just now`, `5 minutes ago`,
+/// `3 days ago`, or the date once it's more than 30 days old.
+pub fn format_relative_time(then: chrono::DateTime<chrono::Utc>, now: chrono::DateTime<chrono::Utc>) -> String {
+    let secs = (now - then).num_seconds();
+    let ago = |n: i64, unit: &str| format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" });
+    match secs {
+        ..=59 => "just now".to_string(),
+        60..=3599 => ago(secs / 60, "minute"),
+        3600..=86_399 => ago(secs / 3600, "hour"),
+        86_400..=2_591_999 => ago(secs / 86_400, "day"),
+        _ => then.format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// Split `/run` arguments into the script name and an optional stdin file.
+///
+/// Accepts `script.py < data.txt`, `script.py <data.txt` and
+/// `script.py --stdin data.txt`. Either part may be missing.
+pub fn parse_run_args(args: &str) -> (Option<String>, Option<String>) {
+    let (script_part, stdin_part) = if let Some((script, input)) = args.split_once('<') {
+        (script, Some(input))
+    } else if let Some((script, input)) = args.split_once("--stdin") {
+        (script, Some(input))
+    } else {
+        (args, None)
+    };
+
+    let script = script_part.split_whitespace().next().map(str::to_string);
+    let stdin = stdin_part
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string);
+    (script, stdin)
+}
+
+/// Split a `/batch` prompt file into prompts.
+///
+/// Without blank lines every line is its own prompt; once the file contains
+/// blank lines, each blank-line-separated block is one (multi-line) prompt.
+/// Lines starting with `#` are comments.
+pub fn parse_batch_prompts(content: &str) -> Vec<String> {
+    let lines: Vec<&str> = content
+        .lines()
+        .map(str::trim_end)
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .collect();
+    // Leading and trailing blank lines don't make a block file
+    let first = lines.iter().position(|l| !l.trim().is_empty());
+    let last = lines.iter().rposition(|l| !l.trim().is_empty());
+    let has_blocks = match (first, last) {
+        (Some(first), Some(last)) => lines[first..last].iter().any(|l| l.trim().is_empty()),
+        _ => false,
+    };
+
+    if !has_blocks {
+        return lines.iter().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+    }
+    lines
+        .split(|l| l.trim().is_empty())
+        .filter(|block| !block.is_empty())
+        .map(|block| block.join("\n").trim().to_string())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -220,6 +680,17 @@ mod tests {
         assert_eq!(result, "print('hello')");
     }
 
+    #[test]
+    fn test_extract_code_other_language_fences() {
+        assert_eq!(
+            extract_python_code("```bash\n#!/usr/bin/env bash\necho hi\n```"),
+            "#!/usr/bin/env bash\necho hi"
+        );
+        assert_eq!(extract_python_code("```javascript\nconsole.log(1);\n```"), "console.log(1);");
+        // A tag-like prefix on the first code line is not a fence tag
+        assert_eq!(extract_python_code("```\nshutil.rmtree(p)\n```"), "shutil.rmtree(p)");
+    }
+
     #[test]
     fn test_extract_python_code_plain_text() {
         let input = "print('hello')";
@@ -241,6 +712,18 @@ mod tests {
         assert!(result.contains("No Python code was generated"));
     }
 
+    #[test]
+    fn test_extract_python_code_strips_unfenced_prose() {
+        let input = "Sure! Here's the script you asked for\n\nimport sys\n\ndef main():\n    print(sys.argv)\n\nmain()\n\nThis prints the arguments.\nEnjoy!";
+        let result = extract_python_code(input);
+        assert_eq!(result, "import sys\n\ndef main():\n    print(sys.argv)\n\nmain()");
+
+        let input = "Here you go:\n# Compute a total\ntotal: int = 0\nfor i in range(3):\n    total += i\nelse:\n    print(total)";
+        let result = extract_python_code(input);
+        assert!(result.starts_with("# Compute a total"));
+        assert!(result.ends_with("    print(total)"));
+    }
+
     #[test]
     fn test_extract_python_code_mixed_markdown_and_code() {
         let input = "Here is your code:\n```python\nprint('hello')\n```\nThis code prints hello.";
@@ -287,6 +770,42 @@ mod tests {
         assert_eq!(result, vec!["numpy", "pandas", "requests"]);
     }
 
+    #[test]
+    fn test_pypi_name_mapping() {
+        assert_eq!(pypi_name("cv2"), "opencv-python");
+        assert_eq!(pypi_name("sklearn"), "scikit-learn");
+        assert_eq!(pypi_name("bs4"), "beautifulsoup4");
+        assert_eq!(pypi_name("PIL"), "Pillow");
+        assert_eq!(pypi_name("yaml"), "PyYAML");
+        assert_eq!(pypi_name("Crypto"), "pycryptodome");
+        // Unknown names pass through unchanged
+        assert_eq!(pypi_name("requests"), "requests");
+
+        let imports = vec!["numpy".to_string(), "cv2".to_string()];
+        assert_eq!(pypi_packages(&imports), vec!["numpy", "opencv-python"]);
+    }
+
+    #[test]
+    fn test_requirements_txt() {
+        let imports = vec!["yaml".to_string(), "numpy".to_string(), "cv2".to_string()];
+        assert_eq!(requirements_txt(&imports), "numpy\nopencv-python\nPyYAML\n");
+        assert_eq!(requirements_txt(&[]), "");
+    }
+
+    #[test]
+    fn test_extract_imports_comma_list() {
+        let code = "import os, numpy\nimport sys,json as j, xml.etree.ElementTree as ET  # stdlib";
+        let result = extract_imports(code);
+        assert_eq!(result, vec!["json", "numpy", "os", "sys", "xml"]);
+    }
+
+    #[test]
+    fn test_extract_imports_skips_relative() {
+        let code = "from . import helpers\nfrom .models import User\nfrom ..core import run\nimport requests";
+        let result = extract_imports(code);
+        assert_eq!(result, vec!["requests"]);
+    }
+
     #[test]
     fn test_extract_imports_duplicates() {
         let code = "import os\nfrom os import path\nimport os";
@@ -353,6 +872,23 @@ mod tests {
         let _ = fs::remove_dir_all(&temp_dir);
     }
 
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+
+    #[test]
+    fn test_truncate_prompt() {
+        assert_eq!(truncate_prompt("short", 10), None);
+        assert_eq!(truncate_prompt("anything", 0), None);
+        assert_eq!(truncate_prompt("hello world", 5), Some("hello"));
+        // Counts characters, not bytes
+        assert_eq!(truncate_prompt("caf\u{e9}!", 4), Some("caf\u{e9}"));
+        assert_eq!(truncate_prompt("caf\u{e9}", 4), None);
+    }
+
     #[test]
     fn test_find_char_boundary_ascii() {
         let s = "Hello, world!";
@@ -376,4 +912,223 @@ mod tests {
         assert_eq!(find_char_boundary(s, 4), 3); // mid-emoji, snaps back
         assert_eq!(find_char_boundary(s, 7), 7); // after emoji
     }
+
+    #[test]
+    fn test_parse_batch_prompts() {
+        assert_eq!(parse_batch_prompts("hello world\n# comment\nsort a list\n"), vec!["hello world", "sort a list"]);
+        assert_eq!(
+            parse_batch_prompts("\nwrite a CLI that\nreads a CSV\n\n\nprint primes\n"),
+            vec!["write a CLI that\nreads a CSV", "print primes"]
+        );
+        assert_eq!(parse_batch_prompts("\none\ntwo\n\n"), vec!["one", "two"]);
+        assert!(parse_batch_prompts("# only comments\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_run_args() {
+        assert_eq!(parse_run_args(""), (None, None));
+        assert_eq!(parse_run_args("script.py"), (Some("script.py".to_string()), None));
+        assert_eq!(
+            parse_run_args("script.py < data.txt"),
+            (Some("script.py".to_string()), Some("data.txt".to_string()))
+        );
+        assert_eq!(
+            parse_run_args("script.py <data.txt"),
+            (Some("script.py".to_string()), Some("data.txt".to_string()))
+        );
+        assert_eq!(
+            parse_run_args("script.py --stdin my data.csv"),
+            (Some("script.py".to_string()), Some("my data.csv".to_string()))
+        );
+        assert_eq!(parse_run_args("script.py <"), (Some("script.py".to_string()), None));
+    }
+
+    #[test]
+    fn test_extract_project_annotated_blocks() {
+        let response = "Here you go:\n\
+            ```python title=main.py\nfrom utils import greet\ngreet()\n```\n\
+            ```python title=\"utils.py\"\ndef greet():\n    print('hi')\n```\n\
+            ```requirements.txt\nrequests\n```";
+        let files = extract_project(response).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].path, "main.py");
+        assert_eq!(files[0].content, "from utils import greet\ngreet()\n");
+        assert_eq!(files[1].path, "utils.py");
+        assert_eq!(files[2].path, "requirements.txt");
+        assert_eq!(files[2].content, "requests\n");
+    }
+
+    #[test]
+    fn test_extract_project_plain_blocks_is_none() {
+        assert!(extract_project("```python\nprint('hello')\n```").is_none());
+        assert!(extract_project("print('hello')").is_none());
+    }
+
+    #[test]
+    fn test_format_project_roundtrip() {
+        let files = vec![
+            ProjectFile { path: "main.py".to_string(), content: "import helpers\n".to_string() },
+            ProjectFile { path: "helpers.py".to_string(), content: "X = 1\n".to_string() },
+        ];
+        assert_eq!(extract_project(&format_project(&files)).unwrap(), files);
+    }
+
+    #[test]
+    fn test_render_script_name() {
+        let created = chrono::NaiveDate::from_ymd_opt(2025, 12, 9)
+            .unwrap()
+            .and_hms_milli_opt(15, 20, 23, 42)
+            .unwrap()
+            .and_utc();
+        assert_eq!(render_script_name("script_{ts}", created, 1, None), "script_20251209_152023_042");
+        assert_eq!(
+            render_script_name("{ts}_{slug}", created, 1, Some("Count the lines in a file, please")),
+            "20251209_152023_042_count-the-lines-in-a-file"
+        );
+        assert_eq!(render_script_name("{ts}_{slug}", created, 1, Some("!!!")), "20251209_152023_042");
+        assert_eq!(render_script_name("run{seq}_{ts}/x", created, 7, None), "run0007_20251209_152023_042_x");
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Write a CLI to parse CSV files", 4), "write-a-cli-to");
+        assert!(slugify("", 4).is_empty());
+        assert!(slugify(&"a".repeat(100), 4).len() <= 40);
+    }
+
+    #[test]
+    fn test_is_generated_script_name() {
+        assert!(is_generated_script_name("script_20251209_152023.py"));
+        assert!(!is_generated_script_name("script_.py"));
+        assert!(!is_generated_script_name("main.py"));
+        assert!(!is_generated_script_name("script_1.txt"));
+        assert!(!is_generated_script_name("../script_1.py"));
+        assert!(!is_generated_script_name("script_..%2f.py"));
+        assert!(!is_generated_script_name("script_a/b.py"));
+        assert!(!is_generated_script_name("script_a\\b.py"));
+        assert!(is_generated_script_name("script_20251209_152023.sh"));
+        assert!(is_generated_script_name("script_20251209_152023.js"));
+        assert!(is_generated_script_name("20251209_152023_042_count-lines.py"));
+    }
+
+    #[test]
+    fn test_find_in_file() {
+        let path = std::env::temp_dir().join(format!("pymakebot_find_{}.py", uuid::Uuid::new_v4()));
+        fs::write(&path, "import os\n\n    print(\"Hello World\")  \n").unwrap();
+        assert_eq!(find_in_file(&path, "hello"), Some((3, "print(\"Hello World\")".to_string())));
+        assert_eq!(find_in_file(&path, "missing"), None);
+        assert_eq!(find_in_file(&path, ""), None);
+
+        fs::write(&path, "x".repeat(SEARCH_MAX_FILE_BYTES as usize + 1)).unwrap();
+        assert_eq!(find_in_file(&path, "x"), None);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_snippet_around() {
+        let code = "a\nb\nc\nd\ne\nf";
+        assert_eq!(snippet_around(code, 3, 2), vec![(1, "a"), (2, "b"), (3, "c"), (4, "d"), (5, "e")]);
+        assert_eq!(snippet_around(code, 1, 2), vec![(1, "a"), (2, "b"), (3, "c")]);
+        assert_eq!(snippet_around(code, 6, 1), vec![(5, "e"), (6, "f")]);
+        assert!(snippet_around(code, 0, 2).is_empty());
+        assert!(snippet_around(code, 7, 2).is_empty());
+
+        let code: String = (1..=12).map(|i| format!("line{i}\n")).collect();
+        assert_eq!(format_snippet(&code, 10, 1), vec!["   9 | line9", "> 10 | line10", "  11 | line11"]);
+    }
+
+    #[test]
+    fn test_format_size_and_relative_time() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(2355), "2.3 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+
+        let now = chrono::Utc::now();
+        let ago = |secs| format_relative_time(now - chrono::Duration::seconds(secs), now);
+        assert_eq!(ago(5), "just now");
+        assert_eq!(ago(-5), "just now");
+        assert_eq!(ago(60), "1 minute ago");
+        assert_eq!(ago(5 * 60 + 30), "5 minutes ago");
+        assert_eq!(ago(2 * 3600), "2 hours ago");
+        assert_eq!(ago(3 * 86_400), "3 days ago");
+        assert_eq!(ago(90 * 86_400), (now - chrono::Duration::days(90)).format("%Y-%m-%d").to_string());
+    }
+
+    #[test]
+    fn test_list_generated_scripts() {
+        let dir = std::env::temp_dir().join(format!("pymakebot_list_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "script_20251209_152023.py",
+            "script_20240101_000000.sh",
+            "script_20251209_090000.py",
+            "script_notes.py",
+            "_lint_check_tmp.py",
+            "helper.py",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let names: Vec<String> = list_generated_scripts(&dir).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec![
+            "script_20251209_152023.py",
+            "script_20251209_090000.py",
+            "script_20240101_000000.sh",
+        ]);
+        let created = parse_script_timestamp("script_20251209_152023.py").unwrap();
+        assert_eq!(created.format("%Y-%m-%d %H:%M:%S").to_string(), "2025-12-09 15:20:23");
+        assert!(parse_script_timestamp("script_20251309_152023.py").is_none());
+        let created = parse_script_timestamp("20251209_152023_042_count-lines.py").unwrap();
+        assert_eq!(created.format("%H:%M:%S%.3f").to_string(), "15:20:23.042");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_project_file_has_safe_path() {
+        let file = |path: &str| ProjectFile { path: path.to_string(), content: String::new() };
+        assert!(file("main.py").has_safe_path());
+        assert!(file("pkg/core.py").has_safe_path());
+        assert!(!file("").has_safe_path());
+        assert!(!file("../evil.py").has_safe_path());
+        assert!(!file("pkg/../../evil.py").has_safe_path());
+        assert!(!file("/etc/cron.d/evil").has_safe_path());
+    }
+
+    #[test]
+    fn test_resolve_within() {
+        let base = Path::new("test_resolve_within_dir");
+        let _ = fs::remove_dir_all(base);
+        fs::create_dir_all(base.join("project_1")).unwrap();
+        fs::write(base.join("script_1.py"), "print(1)").unwrap();
+        fs::write(base.join("project_1").join("main.py"), "print(2)").unwrap();
+        fs::write("test_resolve_within_outside.py", "print(3)").unwrap();
+
+        assert!(resolve_within(base, "script_1.py").is_some());
+        assert!(resolve_within(base, "project_1/main.py").is_some());
+        assert!(resolve_within(base, "project_1/../script_1.py").is_some());
+        assert!(resolve_within(base, "../test_resolve_within_outside.py").is_none());
+        assert!(resolve_within(base, "/etc/passwd").is_none());
+        assert!(resolve_within(base, "missing.py").is_none());
+
+        let _ = fs::remove_file("test_resolve_within_outside.py");
+        let _ = fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_temp_script_removed_on_drop() {
+        let tmp = TempScript::new("_lint_check", "print('hi')").unwrap();
+        let path = tmp.path().to_path_buf();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "print('hi')");
+        assert!(is_scratch_file(&path.file_name().unwrap().to_string_lossy()));
+        drop(tmp);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_is_scratch_file() {
+        assert!(is_scratch_file("_security_check_tmp.py"));
+        assert!(is_scratch_file("_lint_check_1234.py"));
+        assert!(!is_scratch_file("script_20250101_120000.py"));
+        assert!(!is_scratch_file("my_tmp.py"));
+    }
 }
@@ -196,3 +196,205 @@ fn test_conversation_history_management() {
     history.clear();
     assert_eq!(history.len(), 0);
 }
+
+#[test]
+fn test_prompt_templates_roundtrip() {
+    use python_maker_bot::interface::{load_templates, save_templates};
+    use std::collections::BTreeMap;
+
+    let test_dir = PathBuf::from("test_templates_dir");
+    let _ = fs::remove_dir_all(&test_dir);
+    let path = test_dir.join("nested").join("templates.toml");
+
+    // Missing file loads as empty
+    assert!(load_templates(&path).unwrap().is_empty());
+
+    let mut templates = BTreeMap::new();
+    templates.insert("cli".to_string(), "a click CLI with subcommands".to_string());
+    templates.insert("fast api".to_string(), "a FastAPI app with \"one\" endpoint".to_string());
+    save_templates(&path, &templates).unwrap();
+
+    assert_eq!(load_templates(&path).unwrap(), templates);
+
+    let _ = fs::remove_dir_all(&test_dir);
+}
+
+#[test]
+fn test_prepare_retry() {
+    use python_maker_bot::api::Message;
+    use python_maker_bot::interface::prepare_retry;
+
+    let msg = |role: &str, content: &str| Message { role: role.to_string(), content: content.to_string() };
+
+    // Trailing reply is removed so the prompt can be resent
+    let mut history = vec![msg("user", "make a game"), msg("assistant", "print('v1')")];
+    let popped = prepare_retry(&mut history).unwrap();
+    assert_eq!(popped.unwrap().content, "print('v1')");
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].role, "user");
+
+    // Nothing generated yet: history is left untouched
+    let mut empty: Vec<Message> = Vec::new();
+    assert!(prepare_retry(&mut empty).is_err());
+    let mut orphan = vec![msg("assistant", "print('x')")];
+    assert!(prepare_retry(&mut orphan).is_err());
+    assert_eq!(orphan.len(), 1);
+}
+
+#[test]
+fn test_compact_refine_messages() {
+    use python_maker_bot::api::Message;
+    use python_maker_bot::interface::compact_refine_messages;
+
+    let request = Message { role: "user".to_string(), content: "Please refine the previous code: add colors".to_string() };
+    let messages = compact_refine_messages("print('hi')", &request);
+    assert_eq!(messages.len(), 2);
+    assert_eq!((messages[0].role.as_str(), messages[0].content.as_str()), ("assistant", "print('hi')"));
+    assert_eq!(messages[1].content, request.content);
+}
+
+#[test]
+fn test_trim_history() {
+    use python_maker_bot::api::Message;
+    use python_maker_bot::interface::trim_history;
+
+    let msg = |role: &str, content: &str| Message { role: role.to_string(), content: content.to_string() };
+    let turns = |n: usize, size: usize| -> Vec<Message> {
+        (0..n).flat_map(|i| [msg("user", &format!("prompt {i}")), msg("assistant", &"x".repeat(size))]).collect()
+    };
+
+    // Message count: oldest pairs go first
+    let mut history = turns(3, 10);
+    trim_history(&mut history, 4, 0);
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[0].content, "prompt 1");
+
+    // Token budget: a few big replies are trimmed even though the count is low
+    let mut history = turns(3, 4000);
+    trim_history(&mut history, 20, 1500);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].content, "prompt 2");
+
+    // The system prompt and the latest turn survive an impossible budget
+    let mut history = vec![msg("system", "be brief")];
+    history.extend(turns(2, 4000));
+    trim_history(&mut history, 20, 10);
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].role, "system");
+    assert_eq!(history[1].content, "prompt 1");
+
+    // A count limit of 0 or 1 still keeps the system prompt and the new prompt
+    for max_messages in [0, 1] {
+        let mut history = vec![msg("system", "be brief")];
+        history.extend(turns(2, 10));
+        history.push(msg("user", "prompt 2"));
+        trim_history(&mut history, max_messages, 0);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].role, "system");
+        assert_eq!(history.last().unwrap().content, "prompt 2");
+    }
+    let mut history = turns(2, 10);
+    trim_history(&mut history, 0, 0);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].content, "prompt 1");
+}
+
+#[tokio::test]
+async fn test_generate_once_reports_execution() {
+    use python_maker_bot::interface::generate_once;
+    use python_maker_bot::AppConfig;
+
+    let mut server = mockito::Server::new_async().await;
+    let body = serde_json::json!({
+        "choices": [{ "message": { "role": "assistant", "content": "```python\nprint('one shot')\n```" } }]
+    });
+    let _mock = server
+        .mock("POST", "/v1/chat/completions")
+        .with_body(body.to_string())
+        .create_async()
+        .await;
+
+    let test_dir = "test_generate_once_dir";
+    let _ = fs::remove_dir_all(test_dir);
+    let config = AppConfig {
+        provider: "openai-compatible".to_string(),
+        api_url: format!("{}/v1/chat/completions", server.url()),
+        max_retries: 0,
+        generated_dir: test_dir.to_string(),
+        use_venv: false,
+        use_linting: false,
+        use_security_check: false,
+        ..AppConfig::default()
+    };
+
+    let report = generate_once(&config, "print a greeting").await.unwrap();
+    assert_eq!(report.code, "print('one shot')");
+    assert!(report.skipped.is_none());
+    let execution = report.execution.as_ref().unwrap();
+    assert_eq!(execution.stdout.trim(), "one shot");
+    assert_eq!(execution.exit_code, Some(0));
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["execution"]["exit_code"], 0);
+    assert!(json["script_path"].as_str().unwrap().starts_with(test_dir));
+
+    // Dry run stops before execution
+    let dry = AppConfig { dry_run: true, ..config };
+    let report = generate_once(&dry, "print a greeting").await.unwrap();
+    assert!(report.execution.is_none());
+    assert_eq!(report.skipped.as_deref(), Some("dry run"));
+
+    let _ = fs::remove_dir_all(test_dir);
+}
+
+#[tokio::test]
+async fn test_dashboard_health_probes() {
+    use python_maker_bot::dashboard::{start_dashboard, DashboardState};
+    use python_maker_bot::{AppConfig, CodeExecutor};
+
+    let mut provider = mockito::Server::new_async().await;
+    let config = AppConfig {
+        provider: "openai-compatible".to_string(),
+        api_url: format!("{}/v1/chat/completions", provider.url()),
+        dashboard_token: Some("s3cret".to_string()),
+        ..AppConfig::default()
+    };
+    let state = DashboardState::new(config, CodeExecutor::new("test_health_dir", false, false, "python3").unwrap());
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    tokio::spawn(async move { start_dashboard(state, "127.0.0.1", port).await.unwrap() });
+    let base = format!("http://127.0.0.1:{}", port);
+    let client = reqwest::Client::new();
+
+    // Health needs no token and doesn't touch the provider
+    let mut health = None;
+    for _ in 0..50 {
+        if let Ok(resp) = client.get(format!("{}/api/health", base)).send().await {
+            health = Some(resp);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let health = health.expect("dashboard did not start");
+    assert_eq!(health.status(), 200);
+    let json: serde_json::Value = health.json().await.unwrap();
+    assert_eq!(json["status"], "ok");
+    assert_eq!(json["active_sessions"], 1);
+    assert_eq!(json["provider"], "openai-compatible");
+
+    // Other routes still require the token
+    assert_eq!(client.get(format!("{}/api/stats", base)).send().await.unwrap().status(), 401);
+
+    let denied = provider.mock("GET", "/v1/chat/completions").with_status(401).expect(1).create_async().await;
+    let ready = client.get(format!("{}/api/ready", base)).send().await.unwrap();
+    assert_eq!(ready.status(), 503);
+    // The unauthenticated probe doesn't leak where the provider lives
+    assert!(!ready.text().await.unwrap().contains(&provider.url()));
+
+    // Within the cache window the provider isn't probed again
+    let _up = provider.mock("GET", "/v1/chat/completions").with_status(405).create_async().await;
+    assert_eq!(client.get(format!("{}/api/ready", base)).send().await.unwrap().status(), 503);
+    denied.assert_async().await;
+
+    let _ = fs::remove_dir_all("test_health_dir");
+}